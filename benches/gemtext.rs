@@ -0,0 +1,87 @@
+//! Parsing and wrapping of large gemtext pages, the work done for every page loaded and every
+//! frame drawn. Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use url::Url;
+
+// Taurus is only a binary, so the modules measured are built again here
+#[path = "../src/gemtext.rs"]
+#[allow(dead_code)]
+mod gemtext;
+#[path = "../src/app/wrap.rs"]
+#[allow(dead_code)]
+mod wrap;
+
+use gemtext::{GemTextLine, GemTextParser};
+
+/// A gemlog post like those of real capsules, with every kind of line
+const POST: &str = "\
+# A post about nothing in particular
+
+Some text long enough to be wrapped on most terminals, with a link pasted in it like \
+gemini://example.org/a/rather/long/path/to/a/page.gmi so the long words are broken too.
+
+## Links
+=> /about.gmi About
+=> gemini://example.org/ Example
+=> https://example.com/ On the web
+* A list item, which is wrapped with a hanging indent when it is longer than a line
+* Another one
+> A quote from an earlier post
+>> And a quote of a quote, as replies to replies have them
+
+```ascii art
+  /\\_/\\
+ ( o.o )
+  > ^ <
+```
+";
+
+/// A page of `posts` posts, like a long gemlog archive or a page of a search engine
+fn capsule(posts: usize) -> String {
+    POST.repeat(posts)
+}
+
+fn parse(c: &mut Criterion) {
+    let url = Url::parse("gemini://example.org/gemlog/").unwrap();
+    let mut group = c.benchmark_group("parse");
+    for posts in [10, 100, 1000] {
+        let page = capsule(posts);
+        group.throughput(Throughput::Bytes(page.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(posts), &page, |b, page| {
+            b.iter(|| GemTextParser::new(black_box(page), url.clone()).count())
+        });
+    }
+    group.finish();
+}
+
+fn layout(c: &mut Criterion) {
+    let url = Url::parse("gemini://example.org/gemlog/").unwrap();
+    let mut group = c.benchmark_group("layout");
+    for posts in [10, 100, 1000] {
+        let page = capsule(posts);
+        let lines: Vec<_> = GemTextParser::new(&page, url.clone()).flatten().collect();
+        group.throughput(Throughput::Elements(lines.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(posts), &lines, |b, lines| {
+            b.iter(|| {
+                lines
+                    .iter()
+                    .map(|line| match line {
+                        GemTextLine::PreFormatted(text) => wrap::chunks(text, 80).len(),
+                        GemTextLine::Text(text)
+                        | GemTextLine::Heading { text, .. }
+                        | GemTextLine::ListItem(text)
+                        | GemTextLine::Quote { text, .. }
+                        | GemTextLine::Link { text, .. } => {
+                            wrap::wrap(black_box(text), 76, true).len()
+                        }
+                    })
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, layout);
+criterion_main!(benches);
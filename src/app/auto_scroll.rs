@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Scrolls a page one line at a time to read it hands free
+pub struct AutoScroll {
+    /// The page scrolled, auto scrolling stops when another one is shown
+    pub url: Url,
+    interval: Duration,
+    paused: bool,
+    last: Instant,
+}
+
+impl AutoScroll {
+    pub fn new(url: Url, interval: Duration, now: Instant) -> Self {
+        Self {
+            url,
+            interval: interval.clamp(MIN_INTERVAL, MAX_INTERVAL),
+            paused: false,
+            last: now,
+        }
+    }
+
+    /// Scrolls a fifth faster
+    pub fn faster(&mut self) {
+        self.interval = (self.interval * 4 / 5).max(MIN_INTERVAL);
+    }
+
+    pub fn slower(&mut self) {
+        self.interval = (self.interval * 5 / 4).min(MAX_INTERVAL);
+    }
+
+    pub fn play_pause(&mut self, now: Instant) {
+        self.paused = !self.paused;
+        self.last = now;
+    }
+
+    /// Whether the next line should be scrolled, counting from then if so
+    pub fn due(&mut self, now: Instant) -> bool {
+        if self.paused || now.duration_since(self.last) < self.interval {
+            return false;
+        }
+        self.last = now;
+        true
+    }
+
+    /// Time until the next line is scrolled, to wait no longer for input
+    pub fn until_next(&self, now: Instant) -> Option<Duration> {
+        (!self.paused).then(|| self.interval.saturating_sub(now.duration_since(self.last)))
+    }
+
+    pub fn describe(&self) -> String {
+        if self.paused {
+            return "Auto-scroll paused".to_string();
+        }
+        format!(
+            "Auto-scroll every {:.1}s",
+            self.interval.as_millis() as f64 / 1000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lines_scrolled_at_the_interval() {
+        let now = Instant::now();
+        let url = Url::parse("gemini://a.com/").unwrap();
+        let mut auto_scroll = AutoScroll::new(url, Duration::from_millis(1000), now);
+        assert!(!auto_scroll.due(now + Duration::from_millis(999)));
+        assert!(auto_scroll.due(now + Duration::from_millis(1000)));
+        assert!(!auto_scroll.due(now + Duration::from_millis(1500)));
+        auto_scroll.faster();
+        assert_eq!("Auto-scroll every 0.8s", auto_scroll.describe());
+        auto_scroll.play_pause(now + Duration::from_millis(1500));
+        assert!(!auto_scroll.due(now + Duration::from_secs(60)));
+        assert_eq!(None, auto_scroll.until_next(now));
+        for _ in 0..20 {
+            auto_scroll.slower();
+        }
+        assert_eq!("Auto-scroll paused", auto_scroll.describe());
+        auto_scroll.play_pause(now);
+        assert_eq!("Auto-scroll every 10.0s", auto_scroll.describe());
+    }
+}
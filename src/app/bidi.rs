@@ -0,0 +1,107 @@
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo, Level};
+
+use super::wrap::wrap;
+
+/// Languages written from right to left
+const RTL_LANGUAGES: [&str; 11] = [
+    "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ps", "sd", "ug", "ur",
+];
+
+/// Whether a page should be displayed right to left, from the `lang` parameter of its mime type
+/// or, when missing, from the script most of its text is written in.
+pub fn is_rtl(mime: &str, text: &str) -> bool {
+    match lang(mime) {
+        Some(lang) => {
+            let primary = lang.split(['-', '_']).next().unwrap_or_default();
+            RTL_LANGUAGES.contains(&primary.to_ascii_lowercase().as_str())
+        }
+        None => is_mostly_rtl(text),
+    }
+}
+
+fn lang(mime: &str) -> Option<&str> {
+    mime.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("lang")
+            .then(|| value.trim().split(',').next().unwrap_or_default())
+    })
+}
+
+fn is_mostly_rtl(text: &str) -> bool {
+    let (mut rtl, mut ltr) = (0usize, 0usize);
+    for c in text.chars() {
+        match bidi_class(c) {
+            BidiClass::R | BidiClass::AL => rtl += 1,
+            BidiClass::L => ltr += 1,
+            _ => {}
+        }
+    }
+    rtl > ltr
+}
+
+/// Wraps a right to left line to `width` columns, returning each line in the order its
+/// characters should be drawn from left to right. Terminals draw text in the order it is
+/// written, so the reordering has to be done here. Wrapping is done first, so lines keep their
+/// logical order.
+pub fn visual_lines(text: &str, width: usize, break_long_words: bool) -> Vec<String> {
+    wrap(text, width, break_long_words)
+        .into_iter()
+        .map(|line| {
+            let info = BidiInfo::new(&line, Some(Level::rtl()));
+            let Some(paragraph) = info.paragraphs.first() else {
+                return line;
+            };
+            let (levels, runs) = info.visual_runs(paragraph, paragraph.range.clone());
+            let mut visual = String::with_capacity(line.len());
+            for run in runs {
+                let text = &line[run.clone()];
+                if levels[run.start].is_rtl() {
+                    visual.extend(text.chars().rev().map(mirror));
+                } else {
+                    visual.push_str(text);
+                }
+            }
+            visual
+        })
+        .collect()
+}
+
+/// Characters drawn reversed in right to left text, like the brackets of `[1]`
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn direction_from_lang_or_text() {
+        assert!(is_rtl("text/gemini; lang=ar", "hello"));
+        assert!(is_rtl("text/gemini;LANG=he-IL", ""));
+        assert!(!is_rtl("text/gemini; lang=en", "שלום עולם"));
+        assert!(is_rtl("text/gemini", "שלום עולם, hi"));
+        assert!(!is_rtl("text/gemini", "hello world"));
+    }
+
+    #[test]
+    fn wrapped_then_reordered() {
+        // Hebrew alef bet, then gimel dalet, in logical order
+        let lines = visual_lines("אב גד abc", 5, false);
+        assert_eq!(vec!["דג בא", "abc"], lines);
+        assert_eq!(vec!["אב [0]"], visual_lines("[0] בא", 10, false));
+    }
+}
@@ -0,0 +1,316 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::sync;
+
+pub const BOOKMARKS_URL: &str = "about:bookmarks";
+/// How long deletions are kept, in seconds, for the machines sharing the file to learn about
+/// them. Those older are forgotten, a machine left off longer could bring their bookmark back.
+const DELETIONS_KEPT: u64 = 180 * 24 * 60 * 60;
+
+/// Pages kept under a name to open them again, saved every time they change. The file may be
+/// shared by several machines, what they changed is merged when it is read and written.
+#[derive(Default)]
+pub struct Bookmarks {
+    path: Option<PathBuf>,
+    entries: Vec<Bookmark>,
+    deleted: Vec<Deleted>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Bookmark {
+    pub name: String,
+    pub url: Url,
+    /// When it was added or renamed, in seconds since the Unix epoch
+    #[serde(default)]
+    pub updated: u64,
+}
+
+/// A bookmark deleted, kept so copies of the file where it is still there do not bring it back
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Deleted {
+    url: Url,
+    deleted: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct BookmarksFile {
+    #[serde(default)]
+    bookmarks: Vec<Bookmark>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deleted: Vec<Deleted>,
+}
+
+impl BookmarksFile {
+    /// The latest change to each bookmark of the files, in the order of the first file having
+    /// it. A deletion wins over a change made at the same time.
+    fn merge(files: impl IntoIterator<Item = BookmarksFile>) -> Self {
+        let mut merged = BookmarksFile::default();
+        for file in files {
+            for bookmark in file.bookmarks {
+                match merged
+                    .bookmarks
+                    .iter_mut()
+                    .find(|kept| kept.url == bookmark.url)
+                {
+                    Some(kept) if kept.updated < bookmark.updated => *kept = bookmark,
+                    Some(_) => {}
+                    None => merged.bookmarks.push(bookmark),
+                }
+            }
+            for deleted in file.deleted {
+                match merged
+                    .deleted
+                    .iter_mut()
+                    .find(|kept| kept.url == deleted.url)
+                {
+                    Some(kept) => kept.deleted = kept.deleted.max(deleted.deleted),
+                    None => merged.deleted.push(deleted),
+                }
+            }
+        }
+        let deleted = &merged.deleted;
+        merged.bookmarks.retain(|bookmark| {
+            !deleted
+                .iter()
+                .any(|deleted| deleted.url == bookmark.url && deleted.deleted >= bookmark.updated)
+        });
+        let bookmarks = &merged.bookmarks;
+        merged
+            .deleted
+            .retain(|deleted| !bookmarks.iter().any(|bookmark| bookmark.url == deleted.url));
+        merged
+    }
+
+    /// Forgets the deletions older than [`DELETIONS_KEPT`], so they do not pile up forever
+    fn forget_old_deletions(&mut self, now: u64) {
+        let oldest = now.saturating_sub(DELETIONS_KEPT);
+        self.deleted.retain(|deleted| deleted.deleted >= oldest);
+    }
+}
+
+impl Bookmarks {
+    /// The bookmarks kept in `path`, which is empty to keep none, merged with the copies of it
+    /// made by conflicts when it is synchronized between machines. The copies are then removed.
+    pub fn load(path: &str) -> Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::default());
+        }
+        let path = PathBuf::from(path);
+        let (files, copies) = sync::read_all(&path)?;
+        let mut merged = BookmarksFile::merge(files);
+        merged.forget_old_deletions(sync::now());
+        let bookmarks = Self {
+            path: Some(path),
+            entries: merged.bookmarks,
+            deleted: merged.deleted,
+        };
+        if !copies.is_empty() {
+            bookmarks.write()?;
+            sync::remove_copies(&copies)?;
+        }
+        Ok(bookmarks)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Bookmark> {
+        self.entries.get(index)
+    }
+
+    /// The name and URL of each bookmark, to export them
+    pub fn list(&self) -> Vec<(&str, &Url)> {
+        self.entries
+            .iter()
+            .map(|bookmark| (bookmark.name.as_str(), &bookmark.url))
+            .collect()
+    }
+
+    /// Bookmarks the pages of `pages` that have no bookmark yet, those that have one keeping its
+    /// name. Returns how many were added.
+    pub fn import(&mut self, pages: Vec<(String, Url)>) -> Result<usize> {
+        let now = sync::now();
+        let mut added = 0;
+        for (name, url) in pages {
+            if self.entries.iter().any(|bookmark| bookmark.url == url) {
+                continue;
+            }
+            let mut updated = now;
+            if let Some(index) = self.deleted.iter().position(|deleted| deleted.url == url) {
+                updated = updated.max(self.deleted.remove(index).deleted + 1);
+            }
+            self.entries.push(Bookmark { name, url, updated });
+            added += 1;
+        }
+        if added > 0 {
+            self.save()?;
+        }
+        Ok(added)
+    }
+
+    /// Bookmarks `url`, or renames its bookmark when it already has one. Returns whether it is a
+    /// new one.
+    pub fn add(&mut self, name: String, url: Url) -> Result<bool> {
+        // Later than its deletion, so it is not deleted again when merged
+        let mut updated = sync::now();
+        if let Some(index) = self.deleted.iter().position(|deleted| deleted.url == url) {
+            updated = updated.max(self.deleted.remove(index).deleted + 1);
+        }
+        let added = match self.entries.iter_mut().find(|bookmark| bookmark.url == url) {
+            Some(bookmark) => {
+                bookmark.name = name;
+                bookmark.updated = updated;
+                false
+            }
+            None => {
+                self.entries.push(Bookmark { name, url, updated });
+                true
+            }
+        };
+        self.save()?;
+        Ok(added)
+    }
+
+    pub fn rename(&mut self, index: usize, name: String) -> Result<()> {
+        let Some(bookmark) = self.entries.get_mut(index) else {
+            bail!("No bookmark {index}");
+        };
+        bookmark.name = name;
+        bookmark.updated = sync::now().max(bookmark.updated);
+        self.save()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<Bookmark> {
+        if index >= self.entries.len() {
+            bail!("No bookmark {index}");
+        }
+        let bookmark = self.entries.remove(index);
+        self.deleted.push(Deleted {
+            url: bookmark.url.clone(),
+            deleted: sync::now().max(bookmark.updated),
+        });
+        self.save()?;
+        Ok(bookmark)
+    }
+
+    fn file(&self) -> BookmarksFile {
+        BookmarksFile {
+            bookmarks: self.entries.clone(),
+            deleted: self.deleted.clone(),
+        }
+    }
+
+    /// Writes the bookmarks merged with the changes other machines made to the file since it
+    /// was read
+    fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let (files, copies) = sync::read_all(path)?;
+        let mut merged = BookmarksFile::merge(std::iter::once(self.file()).chain(files));
+        merged.forget_old_deletions(sync::now());
+        self.entries = merged.bookmarks;
+        self.deleted = merged.deleted;
+        self.write()?;
+        sync::remove_copies(&copies)
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            fs::write(path, toml::to_string(&self.file())?)?;
+        }
+        Ok(())
+    }
+
+    /// The about:bookmarks page, with links to open, rename and delete each bookmark and to
+    /// export or import them. The actions are queries of the page, like `about:bookmarks?delete=2`.
+    pub fn page(&self) -> String {
+        let mut page = String::from("# Bookmarks\n");
+        page.push_str(&format!(
+            "=> {BOOKMARKS_URL}?export Export, as OPML to a .opml file or as gemtext links\n"
+        ));
+        page.push_str(&format!(
+            "=> {BOOKMARKS_URL}?import Import from an OPML file or a list of links\n"
+        ));
+        if self.entries.is_empty() {
+            page.push_str("\nNo bookmarks yet. Press b on a page to bookmark it.\n");
+        }
+        for (index, bookmark) in self.entries.iter().enumerate() {
+            page.push_str(&format!("\n## {}\n", bookmark.name));
+            page.push_str(&format!("=> {} Open\n", bookmark.url));
+            page.push_str(&format!("=> {BOOKMARKS_URL}?rename={index} Rename\n"));
+            page.push_str(&format!("=> {BOOKMARKS_URL}?delete={index} Delete\n"));
+        }
+        page
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bookmarks_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("taurus-test-{}-bookmarks.toml", std::process::id()));
+        let path = path.to_string_lossy();
+        let home = Url::parse("gemini://a.com/").unwrap();
+        let other = Url::parse("gemini://b.com/").unwrap();
+        let mut bookmarks = Bookmarks::load(&path).unwrap();
+        assert!(bookmarks.add("A".to_string(), home.clone()).unwrap());
+        assert!(bookmarks.add("B".to_string(), other).unwrap());
+        assert!(!bookmarks.add("Home".to_string(), home.clone()).unwrap());
+        bookmarks.rename(1, "Other".to_string()).unwrap();
+        assert!(bookmarks.remove(2).is_err());
+        bookmarks.remove(1).unwrap();
+        let loaded = Bookmarks::load(&path).unwrap();
+        fs::remove_file(path.as_ref()).unwrap();
+        assert_eq!("Home", loaded.get(0).unwrap().name);
+        assert_eq!(home, loaded.get(0).unwrap().url);
+        assert_eq!(None, loaded.get(1));
+    }
+
+    #[test]
+    fn copies_merged() {
+        let bookmark = |name: &str, url: &str, updated| Bookmark {
+            name: name.to_string(),
+            url: Url::parse(url).unwrap(),
+            updated,
+        };
+        let here = BookmarksFile {
+            bookmarks: vec![
+                bookmark("A", "gemini://a.com/", 10),
+                bookmark("B", "gemini://b.com/", 10),
+            ],
+            deleted: vec![Deleted {
+                url: Url::parse("gemini://c.com/").unwrap(),
+                deleted: 20,
+            }],
+        };
+        // Renamed, deleted and added again on another machine
+        let there = BookmarksFile {
+            bookmarks: vec![
+                bookmark("Renamed", "gemini://a.com/", 30),
+                bookmark("C", "gemini://c.com/", 30),
+            ],
+            deleted: vec![Deleted {
+                url: Url::parse("gemini://b.com/").unwrap(),
+                deleted: 30,
+            }],
+        };
+        let mut merged = BookmarksFile::merge([here, there]);
+        assert_eq!(
+            vec![
+                bookmark("Renamed", "gemini://a.com/", 30),
+                bookmark("C", "gemini://c.com/", 30)
+            ],
+            merged.bookmarks
+        );
+        assert_eq!(1, merged.deleted.len());
+        merged.forget_old_deletions(DELETIONS_KEPT + 30);
+        assert_eq!(1, merged.deleted.len());
+        merged.forget_old_deletions(DELETIONS_KEPT + 31);
+        assert!(merged.deleted.is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use super::trail::format_time;
+use crate::known_hosts::{KnownHost, TrustPolicy};
+
+pub const CERTIFICATES_URL: &str = "about:certificates";
+
+/// What became of the client certificate of the configuration
+pub enum IdentityStatus {
+    None,
+    /// Loaded, with its name
+    Loaded(String),
+    /// Not loaded, with the reason
+    Broken(String),
+}
+
+/// The certificates accepted for each host and the client certificate, with links to manage
+/// them. The actions are queries of the page, like `about:certificates?forget=example.org`.
+pub fn page(
+    hosts: &[(String, KnownHost)],
+    identity: &IdentityStatus,
+    identified: &[String],
+) -> String {
+    let mut page = String::from("# Certificates\n\n## Hosts\n");
+    if hosts.is_empty() {
+        page.push_str("\nNo certificate was accepted yet.\n");
+    }
+    for (host, known) in hosts {
+        page.push_str(&format!("\n### {host}\n"));
+        page.push_str(&format!("Fingerprint: {}\n", known.fingerprint));
+        page.push_str(&format!("Policy: {}\n", known.policy));
+        if let Some(accepted) = known.accepted_at() {
+            page.push_str(&format!("Accepted: {}\n", format_time(accepted)));
+        }
+        if known.policy == TrustPolicy::Pinned {
+            page.push_str(&format!(
+                "=> {CERTIFICATES_URL}?unpin={host} Stop pinning\n"
+            ));
+        } else {
+            page.push_str(&format!(
+                "=> {CERTIFICATES_URL}?pin={host} Pin this certificate\n"
+            ));
+        }
+        page.push_str(&format!(
+            "=> {CERTIFICATES_URL}?copy={host} Copy fingerprint\n"
+        ));
+        page.push_str(&format!(
+            "=> {CERTIFICATES_URL}?forget={host} Forget, to trust the next certificate\n"
+        ));
+    }
+
+    page.push_str("\n## Client certificate\n\n");
+    let identity = match identity {
+        IdentityStatus::Loaded(name) => name,
+        IdentityStatus::None => {
+            page.push_str("None, set cert_file and key_file in Config.toml to use one.\n");
+            page.push_str(&format!("=> {CERTIFICATES_URL}?create Create one\n"));
+            return page;
+        }
+        IdentityStatus::Broken(problem) => {
+            page.push_str(&format!("Could not be loaded, {problem}.\n"));
+            page.push_str(&format!(
+                "=> {CERTIFICATES_URL}?create Create a new one, keeping the old files\n"
+            ));
+            return page;
+        }
+    };
+    page.push_str(&format!("Name: {identity}\n"));
+    if identified.is_empty() {
+        page.push_str("Not sent to any host since taurus was started.\n");
+    } else {
+        page.push_str("Sent since taurus was started to:\n");
+        for host in identified {
+            page.push_str(&format!("* {host}\n"));
+        }
+    }
+    page
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn actions_by_policy() {
+        let known = |policy| KnownHost {
+            fingerprint: "ab:cd".to_string(),
+            policy,
+            accepted: None,
+        };
+        let hosts = [
+            ("a.com".to_string(), known(TrustPolicy::Tofu)),
+            ("b.com".to_string(), known(TrustPolicy::Pinned)),
+        ];
+        let alice = IdentityStatus::Loaded("alice".to_string());
+        let page = page(&hosts, &alice, &["b.com:1965".to_string()]);
+        assert!(page.contains("=> about:certificates?pin=a.com Pin this certificate\n"));
+        assert!(page.contains("=> about:certificates?unpin=b.com Stop pinning\n"));
+        assert!(page.contains("Name: alice\nSent since taurus was started to:\n* b.com:1965\n"));
+    }
+}
@@ -0,0 +1,13 @@
+use std::io::{stdout, Write};
+
+use anyhow::Result;
+use base64::{prelude::BASE64_STANDARD, Engine};
+
+/// Copies text to the system clipboard through the terminal with an OSC 52 sequence, which also
+/// works over SSH.
+pub fn copy(text: &str) -> Result<()> {
+    let mut stdout = stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", BASE64_STANDARD.encode(text))?;
+    stdout.flush()?;
+    Ok(())
+}
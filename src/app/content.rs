@@ -1,27 +1,160 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::Path,
+    sync::Arc,
+};
+
 use anyhow::Result;
+use url::Url;
+
+use super::document::Document;
+use crate::client::{ResponseBody, TempFile};
 
+/// Every how many lines the offset of a line is kept for bodies stored on disk
+const CHECKPOINT_INTERVAL: usize = 1024;
+
+#[derive(Clone)]
 pub struct Content {
     pub mime: String,
     pub body: Body,
+    /// The parsed text of gemtext and plain text bodies kept in memory
+    pub document: Option<Document>,
 }
 
+#[derive(Clone)]
 pub enum Body {
     String(String),
     Bytes(Vec<u8>),
+    File(FileBody),
 }
 
 impl Content {
-    pub fn from_mime_and_bytes(mime: String, bytes: Vec<u8>) -> Result<Self> {
-        if mime.starts_with("text/") {
-            let body = String::from_utf8(bytes)?;
-            return Ok(Self {
+    /// A text body, whose links are resolved against `url`
+    pub fn text(mime: String, text: String, url: Url) -> Self {
+        let document = if mime.starts_with("text/gemini") {
+            Some(Document::gemtext(&text, url))
+        } else if mime.starts_with("text/plain") {
+            Some(Document::plain(&text))
+        } else {
+            None
+        };
+        Self {
+            mime,
+            body: Body::String(text),
+            document,
+        }
+    }
+
+    pub fn from_mime_and_body(mime: String, body: ResponseBody, url: Url) -> Result<Self> {
+        match body {
+            ResponseBody::Bytes(bytes) => Self::from_mime_and_bytes(mime, bytes, url),
+            ResponseBody::File { file, len } => Ok(Self {
                 mime,
-                body: Body::String(body),
-            });
+                body: Body::File(FileBody::new(file, len)?),
+                document: None,
+            }),
+        }
+    }
+
+    pub fn from_mime_and_bytes(mime: String, bytes: Vec<u8>, url: Url) -> Result<Self> {
+        if mime.starts_with("text/") {
+            let text = String::from_utf8(bytes)?;
+            return Ok(Self::text(mime, text, url));
         }
         Ok(Self {
             mime,
             body: Body::Bytes(bytes),
+            document: None,
         })
     }
 }
+
+/// A body too big to be kept in memory. Lines are read from disk as they are displayed.
+#[derive(Clone)]
+pub struct FileBody {
+    file: Arc<TempFile>,
+    len: u64,
+    checkpoints: Vec<u64>,
+}
+
+impl FileBody {
+    fn new(file: Arc<TempFile>, len: u64) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(file.path())?);
+        let mut checkpoints = vec![0];
+        let mut offset = 0;
+        let mut lines = 0;
+        loop {
+            let buffer = reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            for (position, byte) in buffer.iter().enumerate() {
+                if *byte == b'\n' {
+                    lines += 1;
+                    if lines % CHECKPOINT_INTERVAL == 0 {
+                        checkpoints.push(offset + position as u64 + 1);
+                    }
+                }
+            }
+            let read = buffer.len();
+            offset += read as u64;
+            reader.consume(read);
+        }
+        Ok(Self {
+            file,
+            len,
+            checkpoints,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Reads up to `count` lines starting at line `start`.
+    pub fn lines(&self, start: usize, count: usize) -> Result<Vec<String>> {
+        let checkpoint = (start / CHECKPOINT_INTERVAL).min(self.checkpoints.len() - 1);
+        let mut reader = BufReader::new(File::open(self.file.path())?);
+        reader.seek(SeekFrom::Start(self.checkpoints[checkpoint]))?;
+        let mut lines = Vec::with_capacity(count);
+        let mut line = Vec::new();
+        let mut current = checkpoint * CHECKPOINT_INTERVAL;
+        while lines.len() < count {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            if current >= start {
+                let text = String::from_utf8_lossy(&line);
+                lines.push(text.trim_end_matches(['\r', '\n']).to_string());
+            }
+            current += 1;
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn file_body_lines() {
+        let file = TempFile::new();
+        let text: String = (0..3000).map(|n| format!("line {n}\r\n")).collect();
+        std::fs::write(file.path(), &text).unwrap();
+        let body = FileBody::new(Arc::new(file), text.len() as u64).unwrap();
+        assert_eq!(vec!["line 0", "line 1"], body.lines(0, 2).unwrap());
+        assert_eq!(
+            vec!["line 1023", "line 1024", "line 1025"],
+            body.lines(1023, 3).unwrap()
+        );
+        assert_eq!(vec!["line 2999"], body.lines(2999, 10).unwrap());
+        assert!(body.lines(5000, 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use std::{fs, process::Command};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::client::TempFile;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DiffConfig {
+    /// Command comparing two files given as its last arguments, like `diff -u`. Without one the
+    /// pages are compared line by line.
+    pub command: Option<String>,
+}
+
+/// What changed between two versions of a page, with the configured command or line by line.
+pub fn diff(config: &DiffConfig, old: &str, new: &str) -> Result<String> {
+    match &config.command {
+        Some(command) => external(command, old, new),
+        None => Ok(line_diff(old, new)),
+    }
+}
+
+fn external(command: &str, old: &str, new: &str) -> Result<String> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        bail!("Empty diff command");
+    };
+    let (old_file, new_file) = (TempFile::new(), TempFile::new());
+    fs::write(old_file.path(), old)?;
+    fs::write(new_file.path(), new)?;
+    let output = Command::new(program)
+        .args(parts)
+        .arg(old_file.path())
+        .arg(new_file.path())
+        .output()?;
+    // diff and most tools like it exit with 1 when the files differ
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Lines prefixed by `-` when removed, `+` when added and spaces when kept, from the longest
+/// common subsequence of lines.
+pub fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push_str(&format!("  {}\n", old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            diff.push_str(&format!("- {}\n", old[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+ {}\n", new[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lines_added_and_removed() {
+        assert_eq!(
+            "  # Wiki\n- old line\n+ new line\n  end\n+ more\n",
+            line_diff("# Wiki\nold line\nend", "# Wiki\nnew line\nend\nmore\n")
+        );
+    }
+
+    #[test]
+    fn external_command() {
+        let diff = diff(
+            &DiffConfig {
+                command: Some("diff".to_string()),
+            },
+            "a\nb\n",
+            "a\nc\n",
+        )
+        .unwrap();
+        assert_eq!("2c2\n< b\n---\n> c\n", diff);
+    }
+}
@@ -0,0 +1,358 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthStr;
+use url::Url;
+
+use super::{icons::Icons, linkify, url_display};
+use crate::gemtext::{self, GemTextLine, GemTextParser};
+
+/// A page parsed once when it is loaded, for everything that needs to know its lines, links or
+/// headings.
+#[derive(Debug, Default, Clone)]
+pub struct Document {
+    pub lines: Vec<DocLine>,
+    /// Line of the source each of the lines comes from, counting from 1
+    pub source_lines: Vec<usize>,
+    /// The links in the order they are numbered
+    pub links: Vec<Url>,
+    pub headings: Vec<Heading>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DocLine {
+    Text(String),
+    Heading {
+        level: u8,
+        text: String,
+    },
+    Link {
+        index: usize,
+        url: Url,
+        text: String,
+        /// Number of an earlier link of the page to the same URL
+        duplicate_of: Option<usize>,
+    },
+    /// Shown after a bullet, with its wrapped rows indented under the text
+    ListItem(String),
+    /// Quoted `level` times, shown with as many bars before it
+    Quote {
+        level: u8,
+        text: String,
+    },
+    PreFormatted(String),
+}
+
+/// Before list items, as wide as the indent of their wrapped rows and of their continuations
+pub const BULLET: &str = "• ";
+
+/// The bars before a quote, one per level so replies to replies are indented further
+pub fn quote_prefix(level: u8) -> String {
+    "│ ".repeat(level as usize)
+}
+
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// Position in the lines of the document
+    pub line: usize,
+}
+
+/// How the numbers to follow links are written before them
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkNumbers {
+    /// Like `[12]`
+    #[default]
+    Brackets,
+    /// Like `¹²`
+    Superscript,
+    /// Like brackets while the command line is open to type a link number, and not shown otherwise
+    Hidden,
+}
+
+impl LinkNumbers {
+    /// The number of a link with a space after it, right aligned in `digits` columns
+    pub fn prefix(self, index: usize, digits: usize) -> String {
+        match self {
+            LinkNumbers::Brackets => format!("[{index:>digits$}] "),
+            LinkNumbers::Superscript => {
+                let number: String = index
+                    .to_string()
+                    .chars()
+                    .map(|digit| SUPERSCRIPT_DIGITS[digit as usize - '0' as usize])
+                    .collect();
+                format!("{number:>digits$} ")
+            }
+            LinkNumbers::Hidden => String::new(),
+        }
+    }
+}
+
+const SUPERSCRIPT_DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+impl Document {
+    pub fn gemtext(text: &str, url: Url) -> Self {
+        let mut document = Self::default();
+        for (source_line, line) in (1..).zip(GemTextParser::new(text, url)) {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    tracing::warn!("Skipping invalid line: {err}");
+                    continue;
+                }
+            };
+            let line = match line {
+                GemTextLine::Text(text) => DocLine::Text(text.to_string()),
+                GemTextLine::PreFormatted(text) => DocLine::PreFormatted(text.to_string()),
+                GemTextLine::ListItem(text) => DocLine::ListItem(text.to_string()),
+                GemTextLine::Quote { level, text } => DocLine::Quote {
+                    level,
+                    text: text.to_string(),
+                },
+                GemTextLine::Heading { level, text } => {
+                    document.headings.push(Heading {
+                        level,
+                        text: text.to_string(),
+                        line: document.lines.len(),
+                    });
+                    DocLine::Heading {
+                        level,
+                        text: text.to_string(),
+                    }
+                }
+                GemTextLine::Link { url, text } => {
+                    let duplicate_of = document.links.iter().position(|link| *link == url);
+                    document.links.push(url.clone());
+                    // Links without a text are shown with their URL
+                    let text = match text {
+                        "" => url_display::decoded(url.as_str()).into_owned(),
+                        text => text.to_string(),
+                    };
+                    DocLine::Link {
+                        index: document.links.len() - 1,
+                        url,
+                        text,
+                        duplicate_of,
+                    }
+                }
+            };
+            document.lines.push(line);
+            document.source_lines.push(source_line);
+        }
+        document
+    }
+
+    /// Plain text, whose URLs are the links
+    pub fn plain(text: &str) -> Self {
+        Self {
+            lines: text
+                .lines()
+                .map(|line| DocLine::Text(line.to_string()))
+                .collect(),
+            source_lines: (1..=text.lines().count()).collect(),
+            links: linkify::find_urls(text)
+                .into_iter()
+                .map(|(_, _, url)| url)
+                .collect(),
+            headings: Vec::new(),
+        }
+    }
+
+    /// The text of the first level 1 heading, used as the page title.
+    pub fn title(&self) -> Option<&str> {
+        self.headings
+            .iter()
+            .find(|heading| heading.level == 1 && !heading.text.is_empty())
+            .map(|heading| heading.text.as_str())
+    }
+
+    /// Line of the heading a URL fragment points to.
+    pub fn anchor_line(&self, fragment: &str) -> Option<usize> {
+        let fragment = percent_encoding::percent_decode_str(fragment).decode_utf8_lossy();
+        let anchor = gemtext::slug(&fragment);
+        self.headings
+            .iter()
+            .find(|heading| gemtext::slug(&heading.text) == anchor)
+            .map(|heading| heading.line)
+    }
+
+    /// The text of a line as it is displayed, with links numbered so their texts line up
+    pub fn display<'a>(
+        &self,
+        line: &'a DocLine,
+        numbers: LinkNumbers,
+        icons: Icons,
+    ) -> Cow<'a, str> {
+        match line {
+            DocLine::Text(text) | DocLine::PreFormatted(text) => Cow::Borrowed(text),
+            DocLine::Heading { text, .. } => Cow::Borrowed(text),
+            DocLine::ListItem(text) => Cow::Owned(format!("{BULLET}{text}")),
+            DocLine::Quote { level, text } => Cow::Owned(format!("{}{text}", quote_prefix(*level))),
+            DocLine::Link {
+                index, url, text, ..
+            } => {
+                let digits = self.links.len().saturating_sub(1).to_string().len();
+                let icon = icons
+                    .link(url)
+                    .map(|icon| format!("{icon} "))
+                    .unwrap_or_default();
+                Cow::Owned(format!("{}{icon}{text}", numbers.prefix(*index, digits)))
+            }
+        }
+    }
+
+    /// Columns taken by the source line numbers and the bar after them
+    pub fn gutter_width(&self) -> usize {
+        self.source_lines.last().unwrap_or(&0).to_string().len() + 3
+    }
+
+    /// Rows taken by the lines before `line` once wrapped at `width` columns.
+    pub fn rows_before(
+        &self,
+        line: usize,
+        width: usize,
+        numbers: LinkNumbers,
+        icons: Icons,
+    ) -> usize {
+        let width = width.max(1);
+        self.lines
+            .iter()
+            .take(line)
+            .map(|line| match line {
+                // Wrapped list items and quotes are indented under their first row
+                DocLine::ListItem(text) => text
+                    .width()
+                    .div_ceil(width.saturating_sub(BULLET.width()).max(1))
+                    .max(1),
+                DocLine::Quote { level, text } => {
+                    let indent = quote_prefix(*level).width();
+                    text.width()
+                        .div_ceil(width.saturating_sub(indent).max(1))
+                        .max(1)
+                }
+                line => {
+                    let display = self.display(line, numbers, icons);
+                    display.width().div_ceil(width).max(1)
+                }
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(text: &str) -> Document {
+        Document::gemtext(text, Url::parse("gemini://test.com/").unwrap())
+    }
+
+    #[test]
+    fn links_numbered_in_order() {
+        let document = parse("=> /a First\ntext\n=> gemini://b.com/ Second\n=> /%C3%A9t%C3%A9");
+        assert_eq!(
+            vec![
+                "gemini://test.com/a",
+                "gemini://b.com/",
+                "gemini://test.com/%C3%A9t%C3%A9"
+            ],
+            document.links.iter().map(Url::as_str).collect::<Vec<_>>()
+        );
+        let display =
+            |line| document.display(&document.lines[line], LinkNumbers::Brackets, Icons::None);
+        assert_eq!("[1] Second", display(2));
+        assert_eq!("[2] gemini://test.com/été", display(3));
+        let document = parse("=> https://a.org/ Web");
+        let line = &document.lines[0];
+        assert_eq!(
+            "[0] 🌐 Web",
+            document.display(line, LinkNumbers::Brackets, Icons::Emoji)
+        );
+    }
+
+    #[test]
+    fn numbers_aligned() {
+        let document = parse(&"=> /a A\n".repeat(11));
+        let display = |line, numbers| document.display(&document.lines[line], numbers, Icons::None);
+        assert_eq!("[ 9] A", display(9, LinkNumbers::Brackets));
+        assert_eq!("[10] A", display(10, LinkNumbers::Brackets));
+        assert_eq!(" ⁹ A", display(9, LinkNumbers::Superscript));
+        assert_eq!("¹⁰ A", display(10, LinkNumbers::Superscript));
+        assert_eq!("A", display(10, LinkNumbers::Hidden));
+    }
+
+    #[test]
+    fn duplicate_links() {
+        let document = parse("=> /a A\n=> /b B\n=> gemini://TEST.com:1965/a Again");
+        let duplicates: Vec<_> = document
+            .lines
+            .iter()
+            .map(|line| match line {
+                DocLine::Link { duplicate_of, .. } => *duplicate_of,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![None, None, Some(0)], duplicates);
+    }
+
+    #[test]
+    fn source_lines_kept() {
+        // The invalid link is skipped
+        let document = parse("# A\n```\npre\n```\n=> http://[::1 bad\ntext");
+        assert_eq!(vec![1, 2, 3, 4, 6], document.source_lines);
+        assert_eq!(4, document.gutter_width());
+    }
+
+    #[test]
+    fn title_skips_pre_formatted_blocks() {
+        let document = parse("```\n# not a title\n```\n## Sub\n# Real title\n# Other");
+        assert_eq!(Some("Real title"), document.title());
+        assert_eq!(None, parse("Just text").title());
+    }
+
+    #[test]
+    fn heading_anchors() {
+        let document = parse("# Title\n```\n## Notes\n```\ntext\n## Notes\n");
+        assert_eq!(Some(5), document.anchor_line("notes"));
+        assert_eq!(Some(0), document.anchor_line("Title"));
+        assert_eq!(None, document.anchor_line("missing"));
+    }
+
+    #[test]
+    fn list_items_bulleted() {
+        let document = parse("* First\n*not an item");
+        let display =
+            |line| document.display(&document.lines[line], LinkNumbers::Brackets, Icons::None);
+        assert_eq!("• First", display(0));
+        assert_eq!("*not an item", display(1));
+    }
+
+    #[test]
+    fn quotes_indented_by_level() {
+        let document = parse("> One\n>> Two two");
+        let display =
+            |line| document.display(&document.lines[line], LinkNumbers::Brackets, Icons::None);
+        assert_eq!("│ One", display(0));
+        assert_eq!("│ │ Two two", display(1));
+        // The second quote is wrapped in 3 columns left of its bars
+        assert_eq!(
+            4,
+            document.rows_before(2, 7, LinkNumbers::Brackets, Icons::None)
+        );
+    }
+
+    #[test]
+    fn wrapped_rows() {
+        let document = parse("0123456789\n\n=> /a link\n# Heading");
+        assert_eq!(
+            5,
+            document.rows_before(3, 5, LinkNumbers::Brackets, Icons::None)
+        );
+        assert_eq!(
+            3,
+            document.rows_before(3, 10, LinkNumbers::Brackets, Icons::None)
+        );
+    }
+}
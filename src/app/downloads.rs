@@ -0,0 +1,554 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use super::{
+    content::{Body, Content},
+    icons::Icons,
+};
+use crate::{
+    client::{Client, GeminiResponse, TruncatedBody},
+    logging,
+};
+
+pub const DOWNLOADS_URL: &str = "about:downloads";
+
+#[derive(Default)]
+pub struct Downloads {
+    entries: Vec<Arc<Download>>,
+    handlers: HashMap<String, MimeHandler>,
+    /// Mime patterns the user already agreed to open automatically
+    confirmed: HashSet<String>,
+    /// Where pages are saved by default, the working directory when missing. It is created when
+    /// something is saved to it.
+    dir: Option<PathBuf>,
+}
+
+/// How to open downloads of a mime type, like `image/png`, or of a whole `image/*` family
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct MimeHandler {
+    /// Command to open the file with, the default application is used when missing
+    pub command: Option<String>,
+    /// Open the file as soon as its download finishes
+    pub auto_open: bool,
+}
+
+pub struct Download {
+    pub url: Url,
+    pub path: PathBuf,
+    state: Mutex<DownloadState>,
+    cancelled: AtomicBool,
+    /// Whether the user was already told that this download ended
+    notified: AtomicBool,
+}
+
+#[derive(Clone, Debug)]
+pub enum DownloadState {
+    InProgress {
+        received: u64,
+    },
+    Finished {
+        size: u64,
+        mime: String,
+        /// Of the saved file, to compare with the one a capsule publishes
+        sha256: String,
+    },
+    Failed(String),
+    /// The body was cut short, what was received is kept next to where the file goes
+    Partial {
+        received: u64,
+        error: String,
+    },
+    Cancelled,
+}
+
+impl Downloads {
+    pub fn new(handlers: HashMap<String, MimeHandler>, dir: Option<PathBuf>) -> Self {
+        Self {
+            handlers,
+            dir,
+            ..Default::default()
+        }
+    }
+
+    /// Path to suggest when saving `url`, in the downloads directory, numbered like `file(1).ext`
+    /// when the file already exists
+    pub fn suggested_path(&self, url: &Url, mime: &str) -> String {
+        let path = self.target(&suggested_file_name(url, mime));
+        unused_path(path).to_string_lossy().into_owned()
+    }
+
+    /// The file a path typed when saving points to, file names alone being in the downloads
+    /// directory
+    pub fn target(&self, text: &str) -> PathBuf {
+        let path = PathBuf::from(text);
+        match &self.dir {
+            Some(dir) if parent_dir(&path) == Path::new(".") && !text.starts_with('.') => {
+                dir.join(path)
+            }
+            _ => path,
+        }
+    }
+
+    /// Writes a file made by taurus to the downloads directory, under a free name
+    pub fn write(&self, name: &str, contents: &str) -> Result<PathBuf> {
+        let path = unused_path(self.target(name));
+        fs::create_dir_all(parent_dir(&path))?;
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+
+    /// The handler for a mime type and the pattern it was configured with
+    pub fn handler(&self, mime: &str) -> Option<(&str, &MimeHandler)> {
+        let essence = mime.split(';').next().unwrap_or_default().trim();
+        let family = format!("{}/*", essence.split('/').next().unwrap_or_default());
+        self.handlers
+            .get_key_value(essence)
+            .or_else(|| self.handlers.get_key_value(&family))
+            .map(|(pattern, handler)| (pattern.as_str(), handler))
+    }
+
+    pub fn is_confirmed(&self, pattern: &str) -> bool {
+        self.confirmed.contains(pattern)
+    }
+
+    pub fn confirm(&mut self, pattern: String) {
+        self.confirmed.insert(pattern);
+    }
+
+    /// Opens a finished download with the handler configured for its type.
+    pub fn open(&self, download: &Download) -> Result<()> {
+        let command = match download.state() {
+            DownloadState::Finished { mime, .. } => self
+                .handler(&mime)
+                .and_then(|(_, handler)| handler.command.clone()),
+            _ => None,
+        };
+        match command {
+            Some(command) => open_with(&command, &download.path),
+            None => open_external(&download.path),
+        }
+    }
+
+    /// Saves an already loaded page, so no request is needed.
+    pub fn save_content(
+        &mut self,
+        url: Url,
+        path: PathBuf,
+        content: &Content,
+    ) -> Result<Arc<Download>> {
+        fs::create_dir_all(parent_dir(&path))?;
+        let size = match &content.body {
+            Body::String(text) => {
+                fs::write(&path, text)?;
+                text.len() as u64
+            }
+            Body::Bytes(bytes) => {
+                fs::write(&path, bytes)?;
+                bytes.len() as u64
+            }
+            Body::File(file) => fs::copy(file.path(), &path)?,
+        };
+        let mime = content.mime.clone();
+        let sha256 = sha256(&path)?;
+        let download = Arc::new(Download::new(
+            url,
+            path,
+            DownloadState::Finished { size, mime, sha256 },
+        ));
+        download.notified.store(true, Ordering::Relaxed);
+        self.entries.push(download.clone());
+        Ok(download)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Arc<Download>> {
+        self.entries.get(index)
+    }
+
+    /// Requests the download again in the background.
+    pub fn retry(&mut self, index: usize, client: Client) -> Result<()> {
+        let Some(download) = self.entries.get(index) else {
+            bail!("No download {index}");
+        };
+        if matches!(download.state(), DownloadState::InProgress { .. }) {
+            bail!("{} is still downloading", download.file_name());
+        }
+        let download = Arc::new(Download::new(
+            download.url.clone(),
+            download.path.clone(),
+            DownloadState::InProgress { received: 0 },
+        ));
+        self.entries[index] = download.clone();
+        thread::spawn(move || download.fetch(&client));
+        Ok(())
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|download| matches!(download.state(), DownloadState::InProgress { .. }))
+    }
+
+    /// Downloads that ended since the last call, to tell the user about them.
+    pub fn take_ended(&self) -> Vec<Arc<Download>> {
+        self.entries
+            .iter()
+            .filter(|download| {
+                !matches!(download.state(), DownloadState::InProgress { .. })
+                    && !download.notified.swap(true, Ordering::Relaxed)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The about:downloads page, with one link per action.
+    pub fn page(&self, icons: Icons) -> String {
+        let mut page = String::from("# Downloads\n");
+        if self.entries.is_empty() {
+            page.push_str("\nNothing was downloaded yet. Press s on a page to save it.\n");
+        }
+        for (index, download) in self.entries.iter().enumerate().rev() {
+            let state = download.state();
+            let partial = match state {
+                DownloadState::Partial { .. } => " (partial)",
+                _ => "",
+            };
+            let icon = icons
+                .download(&state)
+                .map(|icon| format!("{icon} "))
+                .unwrap_or_default();
+            page.push_str(&format!("\n## {icon}{}{partial}\n", download.file_name()));
+            page.push_str(&format!("{}\n", download.url));
+            page.push_str(&format!("{}\n", download.saved_path().display()));
+            page.push_str(&format!("{}\n", state.describe()));
+            if let DownloadState::Finished { sha256, .. } = &state {
+                page.push_str(&format!("SHA-256 {sha256}\n"));
+            }
+            if let DownloadState::InProgress { .. } = state {
+                page.push_str(&format!("=> {DOWNLOADS_URL}?cancel={index} Cancel\n"));
+                continue;
+            }
+            if let DownloadState::Finished { .. } = state {
+                page.push_str(&format!("=> {DOWNLOADS_URL}?open={index} Open file\n"));
+                page.push_str(&format!(
+                    "=> {DOWNLOADS_URL}?checksum={index} Copy SHA-256\n"
+                ));
+            }
+            page.push_str(&format!("=> {DOWNLOADS_URL}?folder={index} Open folder\n"));
+            page.push_str(&format!("=> {DOWNLOADS_URL}?copy={index} Copy path\n"));
+            page.push_str(&format!("=> {DOWNLOADS_URL}?retry={index} Retry\n"));
+        }
+        page
+    }
+}
+
+impl Download {
+    fn new(url: Url, path: PathBuf, state: DownloadState) -> Self {
+        Self {
+            url,
+            path,
+            state: Mutex::new(state),
+            cancelled: AtomicBool::new(false),
+            notified: AtomicBool::new(false),
+        }
+    }
+
+    pub fn state(&self) -> DownloadState {
+        self.state.lock().expect("Poisoned download state").clone()
+    }
+
+    fn set_state(&self, state: DownloadState) {
+        *self.state.lock().expect("Poisoned download state") = state;
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    /// The folder the file was saved to
+    pub fn folder(&self) -> &Path {
+        parent_dir(&self.path)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Where what was received of a body cut short is kept, `song.ogg.part` for `song.ogg`
+    pub fn partial_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".part");
+        path.into()
+    }
+
+    /// The file on disk, the partial one when the download was interrupted
+    pub fn saved_path(&self) -> PathBuf {
+        match self.state() {
+            DownloadState::Partial { .. } => self.partial_path(),
+            _ => self.path.clone(),
+        }
+    }
+
+    fn fetch(&self, client: &Client) {
+        let state = match self.request(client) {
+            Ok((size, mime, sha256)) => {
+                let _ = fs::remove_file(self.partial_path());
+                DownloadState::Finished { size, mime, sha256 }
+            }
+            Err(_) if self.cancelled.load(Ordering::Relaxed) => DownloadState::Cancelled,
+            Err(err) => match err.downcast::<TruncatedBody>() {
+                Ok(truncated) if !truncated.body.is_empty() => self.keep_partial(&truncated),
+                Ok(truncated) => DownloadState::Failed(truncated.to_string()),
+                Err(err) => DownloadState::Failed(err.to_string()),
+            },
+        };
+        tracing::info!("Download of {} ended: {state:?}", logging::url(&self.url));
+        self.set_state(state);
+    }
+
+    fn request(&self, client: &Client) -> Result<(u64, String, String)> {
+        let response = client.request(self.url.clone(), &mut |progress| {
+            if self.cancelled.load(Ordering::Relaxed) {
+                bail!("Cancelled");
+            }
+            self.set_state(DownloadState::InProgress {
+                received: progress.received,
+            });
+            Ok(())
+        })?;
+        let GeminiResponse::Success { body, mime, .. } = response else {
+            bail!("The server did not send a file");
+        };
+        fs::create_dir_all(self.folder())?;
+        let size = body.save(&self.path)?;
+        Ok((size, mime, sha256(&self.path)?))
+    }
+
+    /// Keeps what was received of a body cut short, unless an earlier try received more. As
+    /// Gemini cannot ask for the rest of a file, the next try starts over and the file is only
+    /// written once it succeeds.
+    fn keep_partial(&self, truncated: &TruncatedBody) -> DownloadState {
+        let path = self.partial_path();
+        let kept = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+        let received = truncated.body.len();
+        if received > kept {
+            let saved = fs::create_dir_all(self.folder()).map_err(anyhow::Error::from);
+            if let Err(err) = saved.and_then(|_| truncated.body.save(&path)) {
+                return DownloadState::Failed(format!("{truncated}, not kept: {err}"));
+            }
+        }
+        DownloadState::Partial {
+            received: received.max(kept),
+            error: truncated.source.to_string(),
+        }
+    }
+}
+
+impl DownloadState {
+    pub fn describe(&self) -> String {
+        match self {
+            DownloadState::InProgress { received } => {
+                format!("Downloading, {} received", super::format_size(*received))
+            }
+            DownloadState::Finished { size, .. } => {
+                format!("Finished, {}", super::format_size(*size))
+            }
+            DownloadState::Failed(err) => format!("Failed: {err}"),
+            DownloadState::Partial { received, error } => format!(
+                "Interrupted: {error}, the {} received are kept until a retry succeeds",
+                super::format_size(*received)
+            ),
+            DownloadState::Cancelled => "Cancelled".to_string(),
+        }
+    }
+}
+
+/// Opens a file, a folder or a link with the desktop's default application.
+pub fn open_external(path: impl AsRef<OsStr>) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Opens a file or a link with a command, like `feh --scale-down`.
+pub fn open_with(command: &str, path: impl AsRef<OsStr>) -> Result<()> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        bail!("Empty handler command");
+    };
+    Command::new(program)
+        .args(parts)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// SHA-256 of a file, in hexadecimal like `sha256sum` prints it
+fn sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// The directory of a file, `.` for a file name alone
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// `path` when there is no file there yet, otherwise the first of `name(1).ext`, `name(2).ext`…
+/// that is free
+fn unused_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|number| path.with_file_name(format!("{stem}({number}){extension}")))
+        .find(|path| !path.exists())
+        .expect("There is always a free number")
+}
+
+/// File name to suggest when saving `url`.
+fn suggested_file_name(url: &Url, mime: &str) -> String {
+    let segment = url
+        .path_segments()
+        .and_then(|segments| segments.rev().find(|segment| !segment.is_empty()))
+        .map(|segment| segment.to_string());
+    match segment {
+        Some(segment) => segment,
+        None if mime.starts_with("text/gemini") => "index.gmi".to_string(),
+        None => "download".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::ResponseBody;
+
+    #[test]
+    fn handler_lookup() {
+        let handlers = HashMap::from([
+            ("image/*".to_string(), MimeHandler::default()),
+            ("image/gif".to_string(), MimeHandler::default()),
+        ]);
+        let downloads = Downloads::new(handlers, None);
+        assert_eq!("image/*", downloads.handler("image/png").unwrap().0);
+        assert_eq!("image/gif", downloads.handler("image/gif; x=y").unwrap().0);
+        assert!(downloads.handler("application/pdf").is_none());
+    }
+
+    #[test]
+    fn file_names() {
+        let url = Url::parse("gemini://test.com/files/song.ogg").unwrap();
+        assert_eq!("song.ogg", suggested_file_name(&url, "audio/ogg"));
+        let url = Url::parse("gemini://test.com/docs/").unwrap();
+        assert_eq!("docs", suggested_file_name(&url, "text/gemini"));
+        let url = Url::parse("gemini://test.com/").unwrap();
+        assert_eq!("index.gmi", suggested_file_name(&url, "text/gemini"));
+    }
+
+    #[test]
+    fn existing_files_not_suggested() {
+        let dir =
+            std::env::temp_dir().join(format!("taurus-test-{}-downloads", std::process::id()));
+        let downloads = Downloads::new(HashMap::new(), Some(dir.clone()));
+        let url = Url::parse("gemini://test.com/song.ogg").unwrap();
+        let suggested = |downloads: &Downloads| downloads.suggested_path(&url, "audio/ogg");
+        assert_eq!(
+            dir.join("song.ogg").to_string_lossy(),
+            suggested(&downloads)
+        );
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("song.ogg"), "").unwrap();
+        fs::write(dir.join("song(1).ogg"), "").unwrap();
+        let result = suggested(&downloads);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(dir.join("song(2).ogg").to_string_lossy(), result);
+        assert_eq!(dir.join("a.gmi"), downloads.target("a.gmi"));
+        assert_eq!(PathBuf::from("b/a.gmi"), downloads.target("b/a.gmi"));
+        assert_eq!(PathBuf::from("./a.gmi"), downloads.target("./a.gmi"));
+    }
+
+    #[test]
+    fn largest_partial_body_kept() {
+        let dir = std::env::temp_dir().join(format!("taurus-test-{}-partial", std::process::id()));
+        let url = Url::parse("gemini://test.com/big.iso").unwrap();
+        let download = Download::new(
+            url,
+            dir.join("big.iso"),
+            DownloadState::InProgress { received: 0 },
+        );
+        let truncated = |bytes: &[u8]| TruncatedBody {
+            body: ResponseBody::Bytes(bytes.to_vec()),
+            source: io::ErrorKind::UnexpectedEof.into(),
+        };
+        download.keep_partial(&truncated(b"abcd"));
+        let state = download.keep_partial(&truncated(b"ab"));
+        let kept = fs::read(dir.join("big.iso.part")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(b"abcd", kept.as_slice());
+        assert!(matches!(state, DownloadState::Partial { received: 4, .. }));
+        assert!(!dir.join("big.iso").exists());
+    }
+
+    #[test]
+    fn checksum_of_saved_files() {
+        let path = std::env::temp_dir().join(format!("taurus-test-{}-abc", std::process::id()));
+        fs::write(&path, "abc").unwrap();
+        let checksum = sha256(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            checksum
+        );
+    }
+}
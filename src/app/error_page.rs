@@ -0,0 +1,100 @@
+use url::Url;
+
+use crate::client::{GeminiResponse, NoResponse};
+
+/// Page shown instead of a notification when the host of `url` could not be found, as there is
+/// more to try than with other errors.
+pub fn host_not_found(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    format!(
+        "# Host not found\n\n\
+         The address of {host} could not be found.\n\n\
+         * Check the spelling of {url}\n\
+         * Check that you are connected to the network\n\
+         * If {host} is only reachable through a proxy or a tunnel, map it to a local address in \
+         client.connect_overrides of the configuration\n\
+         * No copy of this page was cached, pages visited before are shown from the cache when \
+         their host cannot be reached\n\n\
+         Press u to try again.\n"
+    )
+}
+
+/// Page shown when the server of `url` closed the connection without a response, as this is
+/// more often a hiccup of the server than a problem with the page
+pub fn no_response(url: &Url, err: &NoResponse) -> String {
+    let host = url.host_str().unwrap_or_default();
+    format!(
+        "# No response\n\n\
+         {err}.\n\n\
+         * {host} may be restarting or overloaded, try again in a moment\n\
+         * Some servers close the connection of requests they cannot parse, check {url}\n\
+         * Some servers only answer clients sending a certificate, create one with \
+         :new-identity NAME\n\n\
+         Press u to try again.\n"
+    )
+}
+
+/// Page shown for the failure statuses, 4x to 6x, with the message of the server and what can
+/// be done about it. Other responses have no page.
+pub fn failure(url: &Url, response: &GeminiResponse) -> Option<String> {
+    let status = response.status_code();
+    let (title, hint) = match status {
+        40 => ("Temporary failure", "Try again in a moment"),
+        41 => (
+            "Server unavailable",
+            "The server may be overloaded or under maintenance, try again in a moment",
+        ),
+        42 => (
+            "CGI error",
+            "The program making this page failed, its author may want to know",
+        ),
+        43 => (
+            "Proxy error",
+            "The server could not get the page from the host it proxies",
+        ),
+        44 => (
+            "Slow down",
+            "Too many requests were sent, wait the seconds the server asks for",
+        ),
+        50 => ("Permanent failure", "Trying again will fail the same way"),
+        51 => (
+            "Not found",
+            "There is no page at this address, check its spelling",
+        ),
+        52 => ("Gone", "This page was removed and will not come back"),
+        53 => (
+            "Proxy request refused",
+            "The server does not serve pages of other hosts",
+        ),
+        59 => ("Bad request", "The server could not understand the request"),
+        60 => (
+            "Certificate required",
+            "Choose an identity with C, or create one with :new-identity NAME",
+        ),
+        61 => (
+            "Certificate not authorized",
+            "The certificate sent is not allowed to see this page, choose another identity with C",
+        ),
+        62 => (
+            "Certificate not valid",
+            "The server rejected the certificate sent, it may have expired",
+        ),
+        _ => return None,
+    };
+    let message = match response.meta().trim() {
+        "" => String::new(),
+        message => format!("> {message}\n\n"),
+    };
+    let retry = if status < 50 || status == 60 {
+        "Press u to try again, < to go back.\n"
+    } else {
+        "Press < to go back.\n"
+    };
+    Some(format!(
+        "# {status} {title}\n\n\
+         {url}\n\n\
+         {message}\
+         * {hint}\n\n\
+         {retry}"
+    ))
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use super::downloads::{open_external, open_with};
+
+/// Schemes handed off even without a command configured for them
+const HANDED_OFF: [&str; 1] = ["magnet"];
+
+/// Opens the links of schemes taurus does not load, like `magnet:` for torrents, with other
+/// programs
+#[derive(Default)]
+pub struct External {
+    /// Command by scheme, the link being added after it
+    commands: HashMap<String, String>,
+}
+
+impl External {
+    pub fn new(commands: HashMap<String, String>) -> Self {
+        Self { commands }
+    }
+
+    /// Whether links like `url` are opened by another program
+    pub fn handles(&self, url: &Url) -> bool {
+        HANDED_OFF.contains(&url.scheme()) || self.commands.contains_key(url.scheme())
+    }
+
+    fn command(&self, url: &Url) -> Option<&str> {
+        self.commands
+            .get(url.scheme())
+            .map(String::as_str)
+            .filter(|command| !command.trim().is_empty())
+    }
+
+    /// The program `url` is opened with, to ask first
+    pub fn program(&self, url: &Url) -> &str {
+        self.command(url).unwrap_or("the default application")
+    }
+
+    pub fn open(&self, url: &Url) -> Result<()> {
+        match self.command(url) {
+            Some(command) => open_with(command, url.as_str()),
+            None => open_external(url.as_str()),
+        }
+    }
+}
+
+/// The addresses a `mailto:` link writes to, without its subject or body
+pub fn mail_address(url: &Url) -> String {
+    percent_decode_str(url.path())
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn magnet_links_handed_off() {
+        let external = External::new(HashMap::from([
+            ("magnet".to_string(), "transmission-remote -a".to_string()),
+            ("ssh".to_string(), String::new()),
+        ]));
+        let url = |url| Url::parse(url).unwrap();
+        let magnet = url("magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a");
+        assert!(external.handles(&magnet));
+        assert_eq!("transmission-remote -a", external.program(&magnet));
+        assert!(external.handles(&url("ssh://a.com")));
+        assert_eq!(
+            "the default application",
+            external.program(&url("ssh://a.com"))
+        );
+        assert!(!external.handles(&url("gemini://a.com/")));
+        assert!(External::default().handles(&magnet));
+    }
+
+    #[test]
+    fn addresses_of_mail_links() {
+        let url = Url::parse("mailto:someone%40example.org?subject=Hello").unwrap();
+        assert_eq!("someone@example.org", mail_address(&url));
+        assert!(!External::default().handles(&url));
+    }
+}
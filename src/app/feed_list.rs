@@ -0,0 +1,215 @@
+use std::path::Path;
+
+use url::Url;
+
+use crate::gemtext::{GemTextLine, GemTextParser};
+
+/// How a list of pages to follow is written, to move it between taurus, Lagrange and
+/// aggregators like CAPCOM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `<outline text="Name" xmlUrl="gemini://…"/>` elements, as feed readers exchange them
+    Opml,
+    /// `=> gemini://… Name` links, bare URLs being read as well
+    Gemtext,
+}
+
+impl Format {
+    /// OPML for `.opml` and `.xml` files, gemtext for any other
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension)
+                if extension.eq_ignore_ascii_case("opml")
+                    || extension.eq_ignore_ascii_case("xml") =>
+            {
+                Format::Opml
+            }
+            _ => Format::Gemtext,
+        }
+    }
+}
+
+pub fn write(format: Format, entries: &[(&str, &Url)]) -> String {
+    match format {
+        Format::Opml => {
+            let mut opml = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <opml version=\"2.0\">\n  <head>\n    <title>taurus bookmarks</title>\n  </head>\n  \
+                 <body>\n",
+            );
+            for (name, url) in entries {
+                let name = escape(name);
+                opml.push_str(&format!(
+                    "    <outline text=\"{name}\" title=\"{name}\" xmlUrl=\"{}\"/>\n",
+                    escape(url.as_str())
+                ));
+            }
+            opml.push_str("  </body>\n</opml>\n");
+            opml
+        }
+        Format::Gemtext => {
+            let mut gemtext = String::from("# Bookmarks\n\n");
+            for (name, url) in entries {
+                gemtext.push_str(&format!("=> {url} {name}\n"));
+            }
+            gemtext
+        }
+    }
+}
+
+/// The names and URLs of the list, those without an absolute URL being left out. Pages without
+/// a name are named after their URL.
+pub fn read(format: Format, text: &str) -> Vec<(String, Url)> {
+    let entries: Vec<(String, Url)> = match format {
+        Format::Opml => text
+            .split("<outline")
+            .skip(1)
+            .filter_map(|element| {
+                let attributes = element.split('>').next().unwrap_or_default();
+                let url = ["xmlUrl", "url", "htmlUrl"]
+                    .into_iter()
+                    .find_map(|name| attribute(attributes, name))?;
+                let url = Url::parse(url.trim()).ok()?;
+                let name = attribute(attributes, "text")
+                    .or_else(|| attribute(attributes, "title"))
+                    .unwrap_or_default();
+                Some((name, url))
+            })
+            .collect(),
+        Format::Gemtext => {
+            // Relative links lead nowhere from a file, they fail to resolve
+            let base = Url::parse("about:blank").expect("We know that this is a valid url");
+            GemTextParser::new(text, base)
+                .filter_map(|line| match line.ok()? {
+                    GemTextLine::Link { url, text } => Some((text.trim().to_string(), url)),
+                    GemTextLine::Text(line) => Url::parse(line.trim())
+                        .ok()
+                        .filter(|url| url.has_host())
+                        .map(|url| (String::new(), url)),
+                    _ => None,
+                })
+                .collect()
+        }
+    };
+    entries
+        .into_iter()
+        .map(|(name, url)| match name.is_empty() {
+            true => (url.to_string(), url),
+            false => (name, url),
+        })
+        .collect()
+}
+
+/// The unescaped value of the attribute `name` of an element, quoted with `"` or `'`
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while let Some(position) = rest.find(name) {
+        let before = rest[..position].chars().next_back();
+        rest = &rest[position + name.len()..];
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let value = &value[1..];
+        let end = value.find(quote)?;
+        return Some(unescape(&value[..end]));
+    }
+    None
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            // Left as it is
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lists_written_and_read() {
+        let url = Url::parse("gemini://a.com/feed.gmi?a&b").unwrap();
+        let entries = [("Tom & Jerry's \"log\"", &url)];
+        for format in [Format::Opml, Format::Gemtext] {
+            let text = write(format, &entries);
+            assert_eq!(
+                vec![("Tom & Jerry's \"log\"".to_string(), url.clone())],
+                read(format, &text)
+            );
+        }
+        let opml = "<opml><body><outline type='rss' title='B&#233;b&#xe9;' \
+                    xmlUrl='gemini://b.com/'/><outline text=\"Group\">\
+                    <outline htmlUrl=\"gemini://c.com/\"/></outline></body></opml>";
+        let read_opml: Vec<_> = read(Format::Opml, opml)
+            .into_iter()
+            .map(|(name, url)| (name, url.to_string()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("Bébé".to_string(), "gemini://b.com/".to_string()),
+                ("gemini://c.com/".to_string(), "gemini://c.com/".to_string()),
+            ],
+            read_opml
+        );
+        // The feed list of CAPCOM has one URL per line
+        let capcom = "gemini://d.com/atom.xml\nnot a url\n=> relative.gmi\n";
+        assert_eq!(
+            vec![(
+                "gemini://d.com/atom.xml".to_string(),
+                Url::parse("gemini://d.com/atom.xml").unwrap()
+            )],
+            read(Format::Gemtext, capcom)
+        );
+        assert_eq!(Format::Opml, Format::of(Path::new("feeds.OPML")));
+        assert_eq!(Format::Gemtext, Format::of(Path::new("feeds.gmi")));
+    }
+}
@@ -1,5 +1,6 @@
 use url::Url;
 
+#[derive(Clone)]
 pub struct GemspaceNav {
     gemspaces: Vec<Url>,
     position: usize,
@@ -22,6 +23,11 @@ impl GemspaceNav {
         self.position += 1;
     }
 
+    /// Replaces the current URL without touching the rest of the history
+    pub fn replace(&mut self, url: Url) {
+        self.gemspaces[self.position] = url;
+    }
+
     pub fn current(&self) -> Url {
         self.gemspaces
             .get(self.position)
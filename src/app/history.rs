@@ -0,0 +1,279 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{trail, url_display};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Where the visited pages are kept across runs, empty to keep none
+    pub file: String,
+    /// Visits older than this are forgotten when taurus starts, 0 keeps them forever
+    pub days: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            file: "history.toml".to_string(),
+            days: 90,
+        }
+    }
+}
+
+/// Every page visited, with when it was, added to a file as they are visited. The queries are
+/// left out, they hold the answers to prompts, passwords among them.
+#[derive(Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    /// Oldest first
+    visits: Vec<Visit>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Visit {
+    pub url: Url,
+    pub title: Option<String>,
+    /// Seconds since the Unix epoch
+    time: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct HistoryFile {
+    #[serde(default)]
+    visits: Vec<Visit>,
+}
+
+impl Visit {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.time)
+    }
+}
+
+impl History {
+    /// The visits kept in the file of `config`, without those older than its retention, which
+    /// are removed from the file.
+    pub fn load(config: &HistoryConfig, now: SystemTime) -> Result<Self> {
+        if config.file.is_empty() {
+            return Ok(Self::default());
+        }
+        let path = PathBuf::from(&config.file);
+        let file: HistoryFile = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HistoryFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut history = Self {
+            path: Some(path),
+            visits: file.visits,
+        };
+        // Kept by the versions that recorded them
+        let mut changed = false;
+        for visit in &mut history.visits {
+            if visit.url.query().is_some() {
+                visit.url.set_query(None);
+                changed = true;
+            }
+        }
+        if config.days > 0 {
+            let oldest = seconds(now).saturating_sub(config.days * 24 * 60 * 60);
+            let count = history.visits.len();
+            history.visits.retain(|visit| visit.time >= oldest);
+            changed |= history.visits.len() < count;
+        }
+        if changed {
+            history.save()?;
+        }
+        Ok(history)
+    }
+
+    /// Adds a visit, appended to the file so it is never written whole while browsing
+    pub fn record(&mut self, mut url: Url, title: Option<String>, time: SystemTime) -> Result<()> {
+        url.set_query(None);
+        let visit = Visit {
+            url,
+            title,
+            time: seconds(time),
+        };
+        if let Some(path) = &self.path {
+            let file = HistoryFile {
+                visits: vec![visit.clone()],
+            };
+            let mut writer = OpenOptions::new().create(true).append(true).open(path)?;
+            writer.write_all(format!("\n{}", toml::to_string(&file)?).as_bytes())?;
+        }
+        self.visits.push(visit);
+        Ok(())
+    }
+
+    /// The last visit of each page whose title or URL matches `query`, most recent first
+    pub fn search(&self, query: &str) -> Vec<&Visit> {
+        let mut seen = HashSet::new();
+        self.visits
+            .iter()
+            .rev()
+            .filter(|visit| seen.insert(&visit.url))
+            .filter(|visit| {
+                fuzzy_match(visit.url.as_str(), query)
+                    || visit
+                        .title
+                        .as_deref()
+                        .is_some_and(|title| fuzzy_match(title, query))
+            })
+            .collect()
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        // An empty `visits = []` could not be followed by the visits appended later
+        let contents = if self.visits.is_empty() {
+            String::new()
+        } else {
+            let file = HistoryFile {
+                visits: self.visits.clone(),
+            };
+            toml::to_string(&file)?
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Whether the characters of `query` are all in `text`, in the same order, ignoring case
+fn fuzzy_match(text: &str, query: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|wanted| text.any(|c| c == wanted))
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The history, narrowed down by what is typed, to open a page visited before.
+#[derive(Default)]
+pub struct HistoryView {
+    pub filter: String,
+    /// Position of the highlighted visit in the matching ones
+    pub selected: usize,
+}
+
+impl HistoryView {
+    /// Moves the highlight by `step` visits, staying in the matching ones.
+    pub fn move_selection(&mut self, step: isize, matching: usize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(step)
+            .min(matching.saturating_sub(1));
+    }
+
+    /// The page to open
+    pub fn selected_url(&self, history: &History) -> Option<Url> {
+        history
+            .search(&self.filter)
+            .get(self.selected)
+            .map(|visit| visit.url.clone())
+    }
+
+    pub fn render(&self, history: &History, area: Rect, buf: &mut Buffer) {
+        let matching = history.search(&self.filter);
+        let lines: Vec<Line> = if matching.is_empty() {
+            vec![Line::raw("No visit matches")]
+        } else {
+            matching
+                .iter()
+                .enumerate()
+                .map(|(position, visit)| {
+                    let title = match &visit.title {
+                        Some(title) => title.clone(),
+                        None => url_display::decoded(visit.url.as_str()).into_owned(),
+                    };
+                    let line = Line::raw(format!(
+                        "{}  {title}  {}",
+                        trail::format_time(visit.time()),
+                        url_display::shorten(visit.url.as_str(), 40)
+                    ));
+                    if position == self.selected {
+                        line.style(Style::new().reversed())
+                    } else {
+                        line
+                    }
+                })
+                .collect()
+        };
+        // Keeps the highlighted visit visible
+        let height = area.height.saturating_sub(2) as usize;
+        let scroll = (self.selected + 1).saturating_sub(height) as u16;
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .scroll((scroll, 0))
+            .block(
+                Block::bordered()
+                    .title_top(format!("History ({})", matching.len()))
+                    .title_bottom("Type to search, Enter to open"),
+            )
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn history_kept_and_searched() {
+        let path =
+            std::env::temp_dir().join(format!("taurus-test-{}-history.toml", std::process::id()));
+        let config = HistoryConfig {
+            file: path.to_string_lossy().into_owned(),
+            days: 1,
+        };
+        let day = UNIX_EPOCH + Duration::from_secs(1_709_197_200);
+        let home = Url::parse("gemini://a.com/").unwrap();
+        let notes = Url::parse("gemini://b.org/notes.gmi").unwrap();
+        let mut login = notes.clone();
+        login.set_query(Some("password"));
+        let mut history = History::load(&config, day).unwrap();
+        let old_title = Some("Old".to_string());
+        history.record(home.clone(), old_title, day).unwrap();
+        let later = day + Duration::from_secs(2 * 24 * 60 * 60);
+        let title = Some("Gemini notes".to_string());
+        history.record(login, title, later).unwrap();
+        history.record(home.clone(), None, later).unwrap();
+        // The first visit is forgotten, it is older than a day
+        let history = History::load(&config, later).unwrap();
+        let kept = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!kept.contains("password"));
+        assert_eq!(2, history.visits.len());
+        let urls = |visits: Vec<&Visit>| -> Vec<Url> {
+            visits.into_iter().map(|visit| visit.url.clone()).collect()
+        };
+        assert_eq!(vec![home.clone(), notes.clone()], urls(history.search("")));
+        assert_eq!(vec![notes], urls(history.search("GMNT")));
+        assert_eq!(vec![home], urls(history.search("a.com")));
+        assert!(history.search("notesx").is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::downloads::DownloadState;
+use crate::known_hosts::TrustPolicy;
+
+/// How links to other schemes than Gemini, downloads and the trust of hosts are marked. Taurus
+/// cannot tell which glyphs the font of the terminal has, so the set is chosen in the
+/// configuration.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Icons {
+    #[default]
+    None,
+    /// Emoji, which most fonts have
+    Emoji,
+    /// Glyphs of fonts patched by Nerd Fonts
+    NerdFont,
+    /// Short tags like `[www]`, for minimal terminals
+    Ascii,
+}
+
+impl Icons {
+    /// Icon of a link, by its scheme. Links to Gemini pages, most of them, have none.
+    pub fn link(self, url: &Url) -> Option<&'static str> {
+        let (emoji, nerd_font, ascii) = match url.scheme() {
+            "gemini" => return None,
+            "http" | "https" => ("🌐", "\u{f0ac}", "[www]"),
+            "gopher" => ("🐹", "\u{f120}", "[gopher]"),
+            "mailto" => ("📧", "\u{f0e0}", "[mail]"),
+            "file" => ("📄", "\u{f15b}", "[file]"),
+            "finger" => ("👉", "\u{f0a4}", "[finger]"),
+            "misfin" => ("📨", "\u{f1d8}", "[misfin]"),
+            "magnet" => ("🧲", "\u{f076}", "[magnet]"),
+            "data" => ("📦", "\u{f187}", "[data]"),
+            "about" => ("📘", "\u{f05a}", "[about]"),
+            _ => ("🔗", "\u{f0c1}", "[link]"),
+        };
+        self.pick(emoji, nerd_font, ascii)
+    }
+
+    pub fn download(self, state: &DownloadState) -> Option<&'static str> {
+        let (emoji, nerd_font, ascii) = match state {
+            DownloadState::InProgress { .. } => ("⏳", "\u{f254}", "[..]"),
+            DownloadState::Finished { .. } => ("✅", "\u{f00c}", "[ok]"),
+            DownloadState::Partial { .. } => ("🧩", "\u{f12e}", "[part]"),
+            DownloadState::Failed(_) => ("❌", "\u{f00d}", "[failed]"),
+            DownloadState::Cancelled => ("🚫", "\u{f05e}", "[cancelled]"),
+        };
+        self.pick(emoji, nerd_font, ascii)
+    }
+
+    /// Icon of how the certificate of a host is trusted. The ASCII set has none, as the name of
+    /// the policy says it already.
+    pub fn trust(self, policy: TrustPolicy) -> Option<&'static str> {
+        let (emoji, nerd_font) = match policy {
+            TrustPolicy::Tofu => ("🔑", "\u{f084}"),
+            TrustPolicy::AlwaysTrust => ("🔓", "\u{f09c}"),
+            TrustPolicy::Pinned => ("📌", "\u{f08d}"),
+        };
+        match self {
+            Icons::Ascii => None,
+            icons => icons.pick(emoji, nerd_font, ""),
+        }
+    }
+
+    fn pick(
+        self,
+        emoji: &'static str,
+        nerd_font: &'static str,
+        ascii: &'static str,
+    ) -> Option<&'static str> {
+        match self {
+            Icons::None => None,
+            Icons::Emoji => Some(emoji),
+            Icons::NerdFont => Some(nerd_font),
+            Icons::Ascii => Some(ascii),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn links_marked_by_scheme() {
+        let web = Url::parse("https://example.org/").unwrap();
+        let capsule = Url::parse("gemini://example.org/").unwrap();
+        assert_eq!(Some("🌐"), Icons::Emoji.link(&web));
+        assert_eq!(Some("[www]"), Icons::Ascii.link(&web));
+        assert_eq!(None, Icons::None.link(&web));
+        assert_eq!(None, Icons::Emoji.link(&capsule));
+        assert_eq!(None, Icons::Ascii.trust(TrustPolicy::Pinned));
+        assert_eq!(Some("📌"), Icons::Emoji.trust(TrustPolicy::Pinned));
+    }
+}
@@ -0,0 +1,342 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+use serde::{Deserialize, Serialize};
+use url::{form_urlencoded, Url};
+
+use crate::{
+    client::{Certificates, Identity},
+    secrets::Passphrase,
+};
+
+pub const IDENTITIES_URL: &str = "about:identities";
+/// Lists the identities and the prefixes they are used for, in the identities directory
+const IDENTITIES_FILE: &str = "identities.toml";
+
+/// Client certificates each used for the pages under one of their prefixes, on the same host, in
+/// place of the one of the configuration. They are kept in a directory, saved every time they
+/// change.
+#[derive(Default)]
+pub struct Identities {
+    dir: Option<PathBuf>,
+    entries: Vec<IdentityEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IdentityEntry {
+    pub name: String,
+    /// Beginnings of the URLs the identity is sent to
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct IdentitiesFile {
+    #[serde(default)]
+    identities: Vec<IdentityEntry>,
+}
+
+impl Identities {
+    /// The identities kept in `dir`, which is empty to keep none.
+    pub fn load(dir: &str) -> Result<Self> {
+        if dir.is_empty() {
+            return Ok(Self::default());
+        }
+        let dir = PathBuf::from(dir);
+        let file: IdentitiesFile = match fs::read_to_string(dir.join(IDENTITIES_FILE)) {
+            Ok(contents) => toml::from_str(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => IdentitiesFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            dir: Some(dir),
+            entries: file.identities,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&IdentityEntry> {
+        self.entries.get(index)
+    }
+
+    /// The files of the identity named `name`
+    fn certificates(&self, name: &str) -> Option<Certificates> {
+        let dir = self.dir.as_ref()?;
+        Some(Certificates {
+            cert_file: dir
+                .join(format!("{name}.pem"))
+                .to_string_lossy()
+                .into_owned(),
+            key_file: dir
+                .join(format!("{name}.key"))
+                .to_string_lossy()
+                .into_owned(),
+        })
+    }
+
+    /// The files of the private keys, encrypted with the passphrase when there is one
+    pub fn key_files(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .filter_map(|entry| self.certificates(&entry.name))
+            .map(|files| PathBuf::from(files.key_file))
+            .collect()
+    }
+
+    /// Creates a self signed certificate named `name`, its key encrypted with the passphrase if
+    /// there is one.
+    pub fn create(&mut self, name: &str, passphrase: Option<&Passphrase>) -> Result<()> {
+        if name.is_empty() {
+            bail!("The identity needs a name");
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("Identity names only have letters, digits, - and _");
+        }
+        if self.entries.iter().any(|entry| entry.name == name) {
+            bail!("There is already an identity named {name}");
+        }
+        let (Some(dir), Some(files)) = (&self.dir, self.certificates(name)) else {
+            bail!("Set identities_dir to keep identities");
+        };
+        fs::create_dir_all(dir)?;
+        Identity::create(name, &files, passphrase)?;
+        self.entries.push(IdentityEntry {
+            name: name.to_string(),
+            prefixes: Vec::new(),
+        });
+        self.save()
+    }
+
+    /// Sends the identity named `name` to the URLs starting with `prefix`, or none of them
+    pub fn use_for(&mut self, name: Option<&str>, prefix: &str) -> Result<()> {
+        if let Some(name) = name {
+            if !self.entries.iter().any(|entry| entry.name == name) {
+                bail!("No identity named {name}");
+            }
+        }
+        for entry in &mut self.entries {
+            entry.prefixes.retain(|used| used != prefix);
+            if Some(entry.name.as_str()) == name {
+                entry.prefixes.push(prefix.to_string());
+            }
+        }
+        self.save()
+    }
+
+    /// Deletes the identity named `name` with its files, which cannot be undone
+    pub fn delete(&mut self, name: &str) -> Result<()> {
+        let Some(index) = self.entries.iter().position(|entry| entry.name == name) else {
+            bail!("No identity named {name}");
+        };
+        self.entries.remove(index);
+        self.save()?;
+        if let Some(files) = self.certificates(name) {
+            for file in [files.cert_file, files.key_file] {
+                fs::remove_file(file)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The identities with their prefixes, each loaded once. Those that cannot be loaded are
+    /// left out and returned with the reason.
+    pub fn loaded(
+        &self,
+        passphrase: Option<&Passphrase>,
+    ) -> (Vec<(String, Identity)>, Vec<String>) {
+        let mut identities = Vec::new();
+        let mut problems = Vec::new();
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| !entry.prefixes.is_empty())
+        {
+            let identity = self
+                .certificates(&entry.name)
+                .ok_or(anyhow!("identities_dir is not set"))
+                .and_then(|files| Identity::load(&files, passphrase));
+            match identity {
+                Ok(identity) => identities.extend(
+                    entry
+                        .prefixes
+                        .iter()
+                        .map(|prefix| (prefix.clone(), identity.clone())),
+                ),
+                Err(err) => problems.push(format!("{}: {err}", entry.name)),
+            }
+        }
+        (identities, problems)
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = &self.dir {
+            let file = IdentitiesFile {
+                identities: self.entries.clone(),
+            };
+            fs::create_dir_all(dir)?;
+            fs::write(dir.join(IDENTITIES_FILE), toml::to_string(&file)?)?;
+        }
+        Ok(())
+    }
+
+    /// The about:identities page, with the pages each identity is used for and links to stop
+    /// using it there or to delete it. The actions are queries of the page, like
+    /// `about:identities?delete=alice`.
+    pub fn page(&self) -> String {
+        let mut page = String::from("# Identities\n\n");
+        page.push_str(
+            "Client certificates sent to the pages under one of their prefixes, on the same \
+             host. Press C on a page to choose the one it gets.\n",
+        );
+        page.push_str(&format!("=> {IDENTITIES_URL}?create Create one\n"));
+        for entry in &self.entries {
+            page.push_str(&format!("\n## {}\n", entry.name));
+            if entry.prefixes.is_empty() {
+                page.push_str("Not used for any page.\n");
+            }
+            for prefix in &entry.prefixes {
+                let encoded: String = form_urlencoded::byte_serialize(prefix.as_bytes()).collect();
+                page.push_str(&format!(
+                    "=> {IDENTITIES_URL}?forget={encoded} Stop using it for {prefix}\n"
+                ));
+            }
+            page.push_str(&format!(
+                "=> {IDENTITIES_URL}?delete={} Delete\n",
+                entry.name
+            ));
+        }
+        page
+    }
+}
+
+/// The prefix an identity is chosen for from a page, its URL without the query and the fragment
+pub fn prefix(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.into()
+}
+
+/// A list of the identities to choose the one sent to a page, with no identity first.
+pub struct IdentityMenu {
+    pub prefix: String,
+    /// Position of the highlighted choice, 0 being no identity
+    pub selected: usize,
+}
+
+impl IdentityMenu {
+    pub fn new(url: &Url) -> Self {
+        Self {
+            prefix: prefix(url),
+            selected: 0,
+        }
+    }
+
+    /// Moves the highlight by `step` choices.
+    pub fn move_selection(&mut self, step: isize, identities: &Identities) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(step)
+            .min(identities.entries.len());
+    }
+
+    /// The name of the identity chosen, `None` for no identity
+    pub fn chosen<'a>(&self, identities: &'a Identities) -> Option<&'a str> {
+        let index = self.selected.checked_sub(1)?;
+        identities.get(index).map(|entry| entry.name.as_str())
+    }
+
+    pub fn render(
+        &self,
+        identities: &Identities,
+        current: Option<&str>,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let choices = std::iter::once("No identity")
+            .chain(identities.entries.iter().map(|entry| entry.name.as_str()));
+        let lines: Vec<Line> = choices
+            .enumerate()
+            .map(|(position, name)| {
+                let in_use = match position {
+                    0 => current.is_none(),
+                    _ => current == Some(name),
+                };
+                let marker = if in_use { "*" } else { " " };
+                let line = Line::raw(format!("{marker} {name}"));
+                if position == self.selected {
+                    line.style(Style::new().reversed())
+                } else {
+                    line
+                }
+            })
+            .collect();
+        // Keeps the highlighted choice visible
+        let height = area.height.saturating_sub(2) as usize;
+        let scroll = (self.selected + 1).saturating_sub(height) as u16;
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .scroll((scroll, 0))
+            .block(
+                Block::bordered()
+                    .title_top(format!("Identity for {}", self.prefix))
+                    .title_bottom("Enter to use, n for a new one"),
+            )
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identities_used_by_prefix() {
+        let dir =
+            std::env::temp_dir().join(format!("taurus-test-{}-identities", std::process::id()));
+        let mut identities = Identities::load(&dir.to_string_lossy()).unwrap();
+        identities.create("alice", None).unwrap();
+        identities.create("bob", None).unwrap();
+        assert!(identities.create("alice", None).is_err());
+        assert!(identities.create("../alice", None).is_err());
+        identities
+            .use_for(Some("alice"), "gemini://a.com/")
+            .unwrap();
+        identities.use_for(Some("bob"), "gemini://b.com/").unwrap();
+        // A prefix goes to a single identity
+        identities.use_for(Some("bob"), "gemini://a.com/").unwrap();
+        identities.use_for(None, "gemini://b.com/").unwrap();
+        identities.delete("alice").unwrap();
+        let loaded = Identities::load(&dir.to_string_lossy()).unwrap();
+        let (scoped, problems) = loaded.loaded(None);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(problems.is_empty());
+        assert_eq!(
+            vec!["gemini://a.com/"],
+            scoped
+                .iter()
+                .map(|(prefix, _)| prefix.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(&IdentityEntry {
+                name: "bob".to_string(),
+                prefixes: vec!["gemini://a.com/".to_string()],
+            }),
+            loaded.get(0)
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::Result;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use url::Url;
+
+use crate::secrets::{self, Passphrase, SecretKey};
+
+/// Answers typed to the prompts of pages, to fill them in again the next time, for capsules
+/// asking for the same token again and again. They are kept per client certificate, so a
+/// capsule only gets back what was typed while it saw the same one. They are encrypted with a key
+/// kept next to them, itself encrypted with the passphrase when there is one. Without a
+/// passphrase the key is in the clear, so anyone who can read the answers can read it too: the
+/// answers are then only kept out of sight, not protected.
+#[derive(Default)]
+pub struct RememberedInputs {
+    /// Where they are kept with their key, nothing is remembered without one
+    store: Option<(PathBuf, SecretKey)>,
+    /// Answers by certificate and page
+    answers: HashMap<String, String>,
+}
+
+impl RememberedInputs {
+    /// The answers kept in `path`, which is empty to remember nothing
+    pub fn load(path: &str, passphrase: Option<&Passphrase>) -> Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::default());
+        }
+        let path = PathBuf::from(path);
+        let key = SecretKey::load_or_create(&secrets::key_path(&path), passphrase)?;
+        let answers = match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let plain = key.open(&BASE64_STANDARD.decode(contents.trim())?)?;
+                toml::from_str(&String::from_utf8(plain)?)?
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            store: Some((path, key)),
+            answers,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// The last answer to the prompt of `url`, typed with the certificate `identity`
+    pub fn get(&self, identity: Option<&str>, url: &Url) -> Option<&str> {
+        self.answers.get(&entry(identity, url)).map(String::as_str)
+    }
+
+    /// Remembers an answer and saves it right away
+    pub fn set(&mut self, identity: Option<&str>, url: &Url, answer: &str) -> Result<()> {
+        if self.store.is_none() {
+            return Ok(());
+        }
+        self.answers
+            .insert(entry(identity, url), answer.to_string());
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some((path, key)) = &self.store else {
+            return Ok(());
+        };
+        let sealed = key.seal(toml::to_string(&self.answers)?.as_bytes())?;
+        secrets::write_private(path, BASE64_STANDARD.encode(sealed).as_bytes())
+    }
+}
+
+/// The page asking, without the answer it was sent last time
+fn entry(identity: Option<&str>, url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    format!("{} {url}", identity.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn answers_kept_per_certificate() {
+        let path = std::env::temp_dir().join(format!("taurus-test-{}-inputs", std::process::id()));
+        let path = path.to_string_lossy();
+        let url = Url::parse("gemini://a.com/game?old").unwrap();
+        let mut inputs = RememberedInputs::load(&path, None).unwrap();
+        inputs.set(Some("alice"), &url, "token").unwrap();
+        let loaded = RememberedInputs::load(&path, None).unwrap();
+        let contents = fs::read_to_string(path.as_ref()).unwrap();
+        fs::remove_file(path.as_ref()).unwrap();
+        fs::remove_file(secrets::key_path(path.as_ref().as_ref())).unwrap();
+        assert!(!contents.contains("token"));
+        let page = Url::parse("gemini://a.com/game").unwrap();
+        assert_eq!(Some("token"), loaded.get(Some("alice"), &page));
+        assert_eq!(None, loaded.get(None, &page));
+        assert!(!RememberedInputs::load("", None).unwrap().enabled());
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeSet;
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+use url::Url;
+
+use super::document::{DocLine, Document};
+
+/// The order links are listed in
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LinkOrder {
+    #[default]
+    Page,
+    Text,
+    Url,
+    Scheme,
+}
+
+impl LinkOrder {
+    fn next(self) -> Self {
+        match self {
+            LinkOrder::Page => LinkOrder::Text,
+            LinkOrder::Text => LinkOrder::Url,
+            LinkOrder::Url => LinkOrder::Scheme,
+            LinkOrder::Scheme => LinkOrder::Page,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            LinkOrder::Page => "in page order",
+            LinkOrder::Text => "by text",
+            LinkOrder::Url => "by URL",
+            LinkOrder::Scheme => "by scheme",
+        }
+    }
+}
+
+struct LinkEntry {
+    index: usize,
+    url: Url,
+    text: String,
+}
+
+/// Every link of a page, narrowed down by what is typed, to open several of them at once or
+/// export them.
+pub struct LinkList {
+    links: Vec<LinkEntry>,
+    pub filter: String,
+    /// Position of the highlighted link in the matching ones
+    selected: usize,
+    order: LinkOrder,
+    /// Numbers of the links marked to be opened or exported
+    marked: BTreeSet<usize>,
+}
+
+impl LinkList {
+    pub fn new(document: &Document) -> Self {
+        let links = document
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                DocLine::Link {
+                    index, url, text, ..
+                } => Some(LinkEntry {
+                    index: *index,
+                    url: url.clone(),
+                    text: text.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+        Self {
+            links,
+            filter: String::new(),
+            selected: 0,
+            order: LinkOrder::default(),
+            marked: BTreeSet::new(),
+        }
+    }
+
+    /// The links whose text or URL contain the filter, ignoring case, in the chosen order
+    fn matching(&self) -> Vec<&LinkEntry> {
+        let filter = self.filter.to_lowercase();
+        let mut links: Vec<&LinkEntry> = self
+            .links
+            .iter()
+            .filter(|link| {
+                link.text.to_lowercase().contains(&filter)
+                    || link.url.as_str().to_lowercase().contains(&filter)
+            })
+            .collect();
+        match self.order {
+            LinkOrder::Page => {}
+            LinkOrder::Text => links.sort_by_key(|link| link.text.to_lowercase()),
+            LinkOrder::Url => links.sort_by_key(|link| link.url.as_str()),
+            LinkOrder::Scheme => links.sort_by_key(|link| link.url.scheme()),
+        }
+        links
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        self.filter.push(c);
+        self.selected = 0;
+    }
+
+    pub fn erase_char(&mut self) {
+        self.filter.pop();
+        self.selected = 0;
+    }
+
+    pub fn cycle_order(&mut self) {
+        self.order = self.order.next();
+        self.selected = 0;
+    }
+
+    /// Moves the highlight by `step` links, staying in the matching ones.
+    pub fn move_selection(&mut self, step: isize) {
+        let matching = self.matching().len();
+        self.selected = self
+            .selected
+            .saturating_add_signed(step)
+            .min(matching.saturating_sub(1));
+    }
+
+    /// Marks the highlighted link, or unmarks it
+    pub fn toggle_mark(&mut self) {
+        let Some(index) = self.matching().get(self.selected).map(|link| link.index) else {
+            return;
+        };
+        if !self.marked.remove(&index) {
+            self.marked.insert(index);
+        }
+    }
+
+    /// The marked links in page order, or the highlighted one when none is marked
+    pub fn chosen(&self) -> Vec<Url> {
+        if self.marked.is_empty() {
+            return self
+                .matching()
+                .get(self.selected)
+                .map(|link| vec![link.url.clone()])
+                .unwrap_or_default();
+        }
+        self.links
+            .iter()
+            .filter(|link| self.marked.contains(&link.index))
+            .map(|link| link.url.clone())
+            .collect()
+    }
+
+    /// The marked links as gemtext, or all the links shown when none is marked
+    pub fn export(&self) -> (usize, String) {
+        let links: Vec<&LinkEntry> = match self.marked.is_empty() {
+            true => self.matching(),
+            false => self
+                .links
+                .iter()
+                .filter(|link| self.marked.contains(&link.index))
+                .collect(),
+        };
+        let gemtext = links
+            .iter()
+            .map(|link| format!("=> {} {}\n", link.url, link.text))
+            .collect();
+        (links.len(), gemtext)
+    }
+
+    pub fn render(&self, area: Rect, buf: &mut Buffer) {
+        let matching = self.matching();
+        let digits = self.links.len().saturating_sub(1).to_string().len();
+        let lines: Vec<Line> = if matching.is_empty() {
+            vec![Line::raw("No link matches")]
+        } else {
+            matching
+                .iter()
+                .enumerate()
+                .map(|(position, link)| {
+                    let marker = if self.marked.contains(&link.index) {
+                        "+"
+                    } else {
+                        " "
+                    };
+                    let line = Line::from(vec![
+                        Span::raw(format!(
+                            "{marker}{:>digits$} {:<7} {}  ",
+                            link.index,
+                            link.url.scheme(),
+                            link.text
+                        )),
+                        Span::raw(link.url.as_str()).dim(),
+                    ]);
+                    let mut style = Style::new();
+                    if self.marked.contains(&link.index) {
+                        style = style.bold();
+                    }
+                    if position == self.selected {
+                        style = style.reversed();
+                    }
+                    line.style(style)
+                })
+                .collect()
+        };
+        // Keeps the highlighted link visible
+        let height = area.height.saturating_sub(2) as usize;
+        let scroll = (self.selected + 1).saturating_sub(height) as u16;
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .scroll((scroll, 0))
+            .block(
+                Block::bordered()
+                    .title_top(format!(
+                        "Links ({}, {}, {} marked)",
+                        self.links.len(),
+                        self.order.label(),
+                        self.marked.len()
+                    ))
+                    .title_bottom(
+                        "Type to filter, Space to mark, Tab to sort, Enter to open, \
+                         Ctrl-s to export",
+                    ),
+            )
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn marked_links_opened_in_page_order() {
+        let url = Url::parse("gemini://test.com/").unwrap();
+        let document = Document::gemtext(
+            "=> /b Beta\n=> /a Alpha\ntext\n=> https://c.org/ Gamma",
+            url,
+        );
+        let mut list = LinkList::new(&document);
+        list.cycle_order();
+        assert_eq!(LinkOrder::Text, list.order);
+        list.toggle_mark();
+        list.move_selection(2);
+        list.toggle_mark();
+        let chosen: Vec<String> = list.chosen().iter().map(Url::to_string).collect();
+        assert_eq!(vec!["gemini://test.com/a", "https://c.org/"], chosen);
+        list.type_char('b');
+        assert_eq!(
+            (
+                2,
+                "=> gemini://test.com/a Alpha\n=> https://c.org/ Gamma\n".to_string()
+            ),
+            list.export()
+        );
+        // Without marks, the highlighted link is opened
+        list.marked.clear();
+        list.erase_char();
+        list.type_char('/');
+        list.type_char('b');
+        let chosen: Vec<String> = list.chosen().iter().map(Url::to_string).collect();
+        assert_eq!(vec!["gemini://test.com/b"], chosen);
+    }
+}
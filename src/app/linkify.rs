@@ -0,0 +1,53 @@
+use url::Url;
+
+const SCHEMES: [&str; 3] = ["gemini://", "http://", "https://"];
+
+/// The URLs written in plain text, with where they start and end. Punctuation right after a URL
+/// is taken as part of the sentence, not of the URL.
+pub fn find_urls(text: &str) -> Vec<(usize, usize, Url)> {
+    let mut urls = Vec::new();
+    let mut position = 0;
+    while let Some(start) = SCHEMES
+        .iter()
+        .filter_map(|scheme| text[position..].find(scheme))
+        .min()
+        .map(|start| position + start)
+    {
+        let length = text[start..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+            .unwrap_or(text.len() - start);
+        let candidate = text[start..start + length]
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '\'']);
+        let end = start + candidate.len();
+        if let Ok(url) = Url::parse(candidate) {
+            if url.has_host() {
+                urls.push((start, end, url));
+            }
+        }
+        position = end.max(start + 1);
+    }
+    urls
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn urls_in_text() {
+        let text =
+            "See gemini://a.com/page. Or (https://b.org/x?y=1), not gemini:// alone\nhttp://c.net";
+        let urls: Vec<(&str, String)> = find_urls(text)
+            .into_iter()
+            .map(|(start, end, url)| (&text[start..end], url.to_string()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("gemini://a.com/page", "gemini://a.com/page".to_string()),
+                ("https://b.org/x?y=1", "https://b.org/x?y=1".to_string()),
+                ("http://c.net", "http://c.net/".to_string()),
+            ],
+            urls
+        );
+    }
+}
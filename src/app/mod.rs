@@ -1,37 +1,245 @@
-use std::time::Duration;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::stdout,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use crossterm::{
-    event::{self, Event, KeyCode},
-    terminal,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::SetTitle,
 };
+use percent_encoding::percent_decode_str;
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Layout, Rect},
-    style::{Color, Style, Stylize},
-    text::Line,
-    widgets::{Block, Paragraph, Widget, Wrap},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Paragraph, Widget, Wrap},
     DefaultTerminal, Frame,
 };
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
 use url::Url;
 
 use crate::{
-    client::{Certificates, Client, GeminiResponse},
-    gemtext::{GemTextLine, GemTextParser},
-    Config,
+    client::{
+        Certificates, Client, GeminiResponse, HostNotFound, Identity, NoResponse,
+        MAX_REQUEST_URL_SIZE,
+    },
+    diagnostics::{self, DIAGNOSTICS_URL},
+    known_hosts::{CertificateMismatch, KnownHosts, TrustPolicy},
+    logging,
+    schemes::Schemes,
+    secrets::Passphrase,
+    Config, CONFIG_FILE,
 };
+use auto_scroll::AutoScroll;
+use bookmarks::{Bookmarks, BOOKMARKS_URL};
+use certificates::{IdentityStatus, CERTIFICATES_URL};
 use content::{Body, Content};
-use gemspace_nav::GemspaceNav;
+use diff::DiffConfig;
+use document::{DocLine, Document, LinkNumbers};
+use downloads::{Download, DownloadState, Downloads, DOWNLOADS_URL};
+use external::External;
+use history::{History, HistoryView};
+use icons::Icons;
+use identities::{Identities, IdentityMenu, IDENTITIES_URL};
+use inputs::RememberedInputs;
+use link_list::LinkList;
+use notifications::{Level, Notifications};
+use overview::TabOverview;
+use page_cache::PageCache;
+use page_load::{LoadEvent, PageLoad};
+use pool::WorkerPool;
+use positions::ReadingPositions;
+use settings::{SETTINGS, SETTINGS_URL};
+use speech::{Reader, SpeechConfig};
+use status_bar::Segment;
+use tabs::{NewTabPage, Session, SessionTab, Siblings, Tab};
+use theme::Theme;
+use trail::{Trail, TRAIL_URL};
+use translate::TranslationConfig;
 
+mod auto_scroll;
+mod bidi;
+mod bookmarks;
+mod certificates;
+mod clipboard;
 mod content;
+pub mod diff;
+mod document;
+pub mod downloads;
+mod error_page;
+mod external;
+mod feed_list;
 mod gemspace_nav;
+pub mod history;
+mod icons;
+pub mod identities;
+mod inputs;
+mod link_list;
+mod linkify;
+mod notifications;
+mod overview;
+pub mod page_cache;
+mod page_load;
+mod pool;
+mod positions;
+mod settings;
+pub mod speech;
+mod status_bar;
+mod sync;
+pub mod tabs;
+pub mod theme;
+pub mod trail;
+pub mod translate;
+pub mod url_bar;
+mod url_display;
+mod wrap;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_FRAME: Duration = Duration::from_millis(80);
+/// How often the configuration file is checked for changes
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// Input is only sent after a confirmation to hosts whose certificate was accepted more recently
+const RECENT_CERTIFICATE: Duration = Duration::from_secs(24 * 60 * 60);
+/// How many closed tabs can be reopened
+const CLOSED_TABS_CAPACITY: usize = 10;
+/// Frames drawn to scroll a page up or down with smooth scrolling, one per frame budget
+const SCROLL_FRAMES: u16 = 5;
 
 pub struct App {
-    gemspaces_nav: GemspaceNav,
+    tabs: Vec<Tab>,
+    current_tab: usize,
+    /// Tab shown next to the current one when the view is split
+    split: Option<u64>,
+    /// The last closed tabs, most recent first
+    closed_tabs: VecDeque<Tab>,
     client: Client,
-    content: Option<Content>,
-    scroll: (u16, u16),
+    schemes: Schemes,
+    pool: WorkerPool,
+    /// The page of the current tab being fetched while loading
+    page_load: Option<PageLoad>,
+    session_file: PathBuf,
+    home_url: Url,
+    new_tab: NewTabPage,
+    positions: ReadingPositions,
+    /// Answers typed to prompts, filled in again when the same page asks
+    inputs: RememberedInputs,
+    bookmarks: Bookmarks,
+    /// Client certificates used for some pages only
+    identities: Identities,
+    /// The pages visited, kept across runs
+    history: History,
+    known_hosts: Arc<KnownHosts>,
     status: AppStatus,
+    loading_started: Instant,
+    received: u64,
+    command_error: Option<String>,
+    /// The about: URL typed or followed from a page of taurus, whose actions may run
+    trusted_about_url: Option<Url>,
+    notifications: Notifications,
+    show_notifications: bool,
+    /// Shows the full URL and other details of the current page
+    show_page_info: bool,
+    downloads: Downloads,
+    external: External,
+    confirmations: VecDeque<Confirmation>,
+    search_url: Url,
+    translation: TranslationConfig,
+    cache: PageCache,
+    diff: DiffConfig,
+    trail: Trail,
+    translated_sender: Sender<(u64, Result<Content>)>,
+    /// Translated pages, sent by the threads translating them
+    translated: Receiver<(u64, Result<Content>)>,
+    speech: SpeechConfig,
+    /// Reads the page aloud
+    reader: Option<Reader>,
+    theme: Theme,
+    tick_rate: Duration,
+    frame_budget: Duration,
+    status_segments: Vec<Segment>,
+    mark_duplicate_links: bool,
+    link_numbers: LinkNumbers,
+    break_long_words: bool,
+    list_continuations: bool,
+    smooth_scrolling: bool,
+    line_numbers: bool,
+    icons: Icons,
+    scroll_animation: Option<ScrollAnimation>,
+    auto_scroll_interval: Duration,
+    auto_scroll: Option<AutoScroll>,
+    /// When the configuration file was last modified, to reload it when it changes
+    config_modified: Option<SystemTime>,
+    config_checked: Instant,
+    /// The settings of the configuration file that were left out, shown on about:diagnostics
+    config_problems: Vec<String>,
+    /// Why the client certificate of the configuration could not be loaded
+    identity_problem: Option<String>,
+    /// Where the client certificate is, or is created
+    identity_files: Option<Certificates>,
+    /// Encrypts the keys of the certificates created
+    passphrase: Option<Passphrase>,
+    /// Size of the terminal when it was last drawn
+    area: Rect,
+    window_title: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct UiConfig {
+    /// How often the screen is redrawn when nothing happens, for animations like the spinner
+    pub tick_rate_ms: u64,
+    /// Minimum time between two redraws caused by input
+    pub frame_budget_ms: u64,
+    pub status_segments: Vec<Segment>,
+    /// Follows links repeating an earlier one of the page with the number of the first
+    pub mark_duplicate_links: bool,
+    pub link_numbers: LinkNumbers,
+    /// Breaks words longer than a line, like pasted URLs, anywhere instead of on a line of their
+    /// own. Preformatted text is never wrapped this way.
+    pub break_long_words: bool,
+    /// Shows indented lines after a list item as the continuation of its text, as some
+    /// capsules write long items, although gemtext has no such lines
+    pub list_continuations: bool,
+    /// Scrolls a page up or down in a few frames, which is easier to follow than a jump but
+    /// sends more to the terminal
+    pub smooth_scrolling: bool,
+    /// Shows left of Gemini pages the line of the source each line comes from
+    pub line_numbers: bool,
+    /// Marks links to other schemes than Gemini, downloads and the trust of hosts
+    pub icons: Icons,
+    /// Time between two lines scrolled in auto-scroll, at first
+    pub auto_scroll_ms: u64,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate_ms: 300,
+            frame_budget_ms: 16,
+            status_segments: vec![Segment::Mode, Segment::Identity],
+            mark_duplicate_links: false,
+            link_numbers: LinkNumbers::default(),
+            break_long_words: true,
+            list_continuations: false,
+            smooth_scrolling: false,
+            line_numbers: false,
+            icons: Icons::default(),
+            auto_scroll_ms: 1500,
+        }
+    }
 }
 
 enum AppStatus {
@@ -39,6 +247,44 @@ enum AppStatus {
     Typing(String),
     Loading,
     Input(String),
+    Saving(String),
+    Confirm(Confirmation),
+    Overview(TabOverview),
+    Links(LinkList),
+    History(HistoryView),
+    Identities(IdentityMenu),
+}
+
+/// A page up or down being drawn in a few frames
+struct ScrollAnimation {
+    /// The page scrolled, the animation stops if another one is shown
+    url: Url,
+    target: u16,
+    frames_left: u16,
+}
+
+/// A question answered with y or n before doing something
+struct Confirmation {
+    prompt: String,
+    action: ConfirmAction,
+}
+
+enum ConfirmAction {
+    OpenDownload {
+        download: Arc<Download>,
+        pattern: String,
+    },
+    TrustCertificate(CertificateMismatch),
+    /// Requests the URL answering a prompt
+    SendInput(Url),
+    /// Saves the page over a file that exists
+    ReplaceFile(PathBuf),
+    /// Opens a link with another program
+    OpenExternal(Url),
+    /// Sends the Misfin message in the query of the URL
+    SendMessage(Url),
+    /// Deletes the identity of that name with its certificate and key
+    DeleteIdentity(String),
 }
 
 impl AppStatus {
@@ -48,6 +294,12 @@ impl AppStatus {
             AppStatus::Typing(_) => "Typing",
             AppStatus::Loading => "Loading",
             AppStatus::Input(_) => "Input",
+            AppStatus::Saving(_) => "Saving",
+            AppStatus::Confirm(_) => "Confirm",
+            AppStatus::Overview(_) => "Tabs",
+            AppStatus::Links(_) => "Links",
+            AppStatus::History(_) => "History",
+            AppStatus::Identities(_) => "Identity",
         }
     }
 }
@@ -57,262 +309,3533 @@ impl Widget for &App {
     where
         Self: Sized,
     {
-        let layout = Layout::vertical([Constraint::Percentage(100), Constraint::Min(1)]);
-        let [browser, command] = layout.areas(area);
-        let url = self.gemspaces_nav.current();
-        let title = Line::from(url.as_str()).bold();
-        let main_block = Block::bordered().title_top(title);
-        match &self.content {
-            None => {
-                Paragraph::new("No content")
-                    .wrap(Wrap { trim: true })
-                    .block(main_block)
-                    .render(browser, buf);
-            }
-            Some(content) => match &content.body {
-                Body::Bytes(_) => {
-                    Paragraph::new("Format not supported!")
-                        .wrap(Wrap { trim: true })
-                        .block(main_block)
-                        .render(browser, buf);
-                }
-                Body::String(body) => {
-                    if content.mime.starts_with("text/gemini") {
-                        let parser = GemTextParser::new(body, self.gemspaces_nav.current());
-                        let mut n_links = 0;
-                        let mut lines = Vec::new();
-                        for line in parser {
-                            let Ok(line) = line else {
-                                dbg!(line.expect_err("Should be an error"));
-                                continue;
-                            };
-                            match line {
-                                GemTextLine::Text(text) => {
-                                    lines.push(Line::raw(text).left_aligned());
-                                }
-                                GemTextLine::PreFormatted(text) => {
-                                    lines.push(
-                                        Line::raw(text)
-                                            .left_aligned()
-                                            .style(Style::new().bg(Color::Gray)),
-                                    );
-                                }
-                                GemTextLine::Link { url, text } => {
-                                    let color = if url.scheme() == "gemini" {
-                                        Color::Blue
-                                    } else {
-                                        Color::Red
-                                    };
-                                    lines.push(Line::styled(
-                                        format!("[{n_links}] {text}"),
-                                        Style::new().fg(color),
-                                    ));
-                                    n_links += 1;
-                                }
-                            }
-                        }
-
-                        Paragraph::new(lines)
-                            .wrap(Wrap { trim: true })
-                            .block(main_block)
-                            .scroll(self.scroll)
-                            .render(browser, buf);
+        let tab_bar_height = if self.tabs.len() > 1 { 1 } else { 0 };
+        let layout = Layout::vertical([
+            Constraint::Length(tab_bar_height),
+            Constraint::Percentage(100),
+            Constraint::Min(1),
+        ]);
+        let [tab_bar, browser, command] = layout.areas(area);
+        if tab_bar_height > 0 {
+            self.render_tab_bar(tab_bar, buf);
+        }
+        match self.split_tab() {
+            Some(other) => {
+                let [left, right] =
+                    Layout::horizontal([Constraint::Percentage(50); 2]).areas(browser);
+                let (left_tab, right_tab) = if other < self.current_tab {
+                    (other, self.current_tab)
+                } else {
+                    (self.current_tab, other)
+                };
+                for (index, area) in [(left_tab, left), (right_tab, right)] {
+                    let border = if index == self.current_tab {
+                        BorderType::Thick
                     } else {
-                        Paragraph::new(body.as_str())
-                            .wrap(Wrap { trim: true })
-                            .block(main_block)
-                            .scroll(self.scroll)
-                            .render(browser, buf);
-                    }
+                        BorderType::Plain
+                    };
+                    self.render_tab(&self.tabs[index], area, buf, border);
                 }
-            },
+            }
+            None => self.render_tab(self.tab(), browser, buf, BorderType::Plain),
         }
-        let layout = Layout::horizontal([Constraint::Min(2), Constraint::Length(10)]);
+        if let AppStatus::Overview(overview) = &self.status {
+            overview.render(&self.tabs, centered(browser, 80, 60), buf);
+        } else if let AppStatus::Links(links) = &self.status {
+            links.render(centered(browser, 80, 60), buf);
+        } else if let AppStatus::History(view) = &self.status {
+            view.render(&self.history, centered(browser, 80, 60), buf);
+        } else if let AppStatus::Identities(menu) = &self.status {
+            let current = self.client.identity_of(&self.tab().nav.current());
+            menu.render(
+                &self.identities,
+                current.as_deref(),
+                centered(browser, 60, 40),
+                buf,
+            );
+        } else if self.show_page_info {
+            self.render_page_info(centered(browser, 80, 60), buf);
+        } else if self.show_notifications {
+            self.notifications
+                .render_log(centered(browser, 80, 60), buf, &self.theme);
+        } else {
+            self.notifications.render_toast(browser, buf, &self.theme);
+        }
+        let status = self
+            .status_segments
+            .iter()
+            .filter_map(|segment| self.status_segment(*segment))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let status_width = (status.width() as u16).max(22);
+        let layout = Layout::horizontal([Constraint::Min(2), Constraint::Length(status_width)]);
         let [left, right] = layout.areas(command);
         let cmd_block = Block::new();
         let status_block = Block::new();
         let typed = match &self.status {
-            AppStatus::Typing(text) | AppStatus::Input(text) => text.as_str(),
+            AppStatus::Typing(text) | AppStatus::Input(text) | AppStatus::Saving(text) => {
+                text.as_str()
+            }
+            AppStatus::Confirm(confirmation) => confirmation.prompt.as_str(),
+            AppStatus::Overview(overview) => overview.filter.as_str(),
+            AppStatus::Links(links) => links.filter.as_str(),
+            AppStatus::History(view) => view.filter.as_str(),
             _ => "",
         };
-        Paragraph::new(format!("=> {typed}"))
+        let mut command_line = Line::raw(format!("=> {typed}"));
+        if let Some(error) = &self.command_error {
+            command_line.push_span(Span::styled(
+                format!("  {error}"),
+                self.theme.notification(Level::Error),
+            ));
+        }
+        Paragraph::new(command_line)
             .block(cmd_block)
             .wrap(Wrap { trim: true })
             .render(left, buf);
-        Paragraph::new(self.status.as_str())
+        Paragraph::new(status)
             .block(status_block)
             .render(right, buf);
     }
 }
 
 impl App {
-    pub(crate) fn new(config: Option<Config>) -> Self {
-        Self {
-            gemspaces_nav: GemspaceNav::new(
-                Url::parse("gemini://tlgs.one/").expect("We know that this is a valid url"),
-            ),
-            client: Client::new(
-                true,
-                config.map(|cfg| Certificates {
-                    cert_file: cfg.cert_file,
-                    key_file: cfg.key_file,
-                }),
-            ),
-            content: None,
-            scroll: (0, 0),
+    pub(crate) fn new(config: Config) -> Result<Self> {
+        let (identity, identity_problem) = match config.identity() {
+            Ok(identity) => (identity, None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        let identity_files = config.certificates();
+        let known_hosts = Arc::new(KnownHosts::load(config.known_hosts_file.into())?);
+        let client = Client::new(
+            config.client.follow_redirects,
+            identity,
+            config.client,
+            known_hosts.clone(),
+        );
+        let schemes = Schemes::new(client.clone());
+        let pool = {
+            let schemes = schemes.clone();
+            let delay = Duration::from_millis(config.tabs.background_host_delay_ms);
+            WorkerPool::new(config.tabs.background_workers, delay, move |url| {
+                schemes.fetch(url, &mut |_| Ok(()))
+            })
+        };
+        let (translated_sender, translated) = mpsc::channel();
+        let session_file = PathBuf::from(config.tabs.session_file);
+        let session = Session::load(&session_file).unwrap_or_else(|err| {
+            tracing::error!("Error loading the session from {session_file:?}: {err}");
+            None
+        });
+        if !config.sync_dir.is_empty() {
+            if let Err(err) = fs::create_dir_all(&config.sync_dir) {
+                tracing::error!(
+                    "Error creating the sync directory {}: {err}",
+                    config.sync_dir
+                );
+            }
+        }
+        let positions_file = sync::path(
+            &config.sync_dir,
+            "positions.toml",
+            &config.tabs.positions_file,
+        );
+        let positions = ReadingPositions::load(&positions_file).unwrap_or_else(|err| {
+            tracing::error!("Error loading the reading positions: {err}");
+            ReadingPositions::default()
+        });
+        let inputs =
+            RememberedInputs::load(&config.remembered_inputs_file, config.passphrase.as_ref());
+        let bookmarks_file = sync::path(&config.sync_dir, "bookmarks.toml", &config.bookmarks_file);
+        let bookmarks = Bookmarks::load(&bookmarks_file);
+        let history = History::load(&config.history, SystemTime::now());
+        let identities = Identities::load(&config.identities_dir);
+        let mut app = Self {
+            tabs: vec![Tab::new(config.tabs.home_url.clone())],
+            current_tab: 0,
+            split: None,
+            closed_tabs: VecDeque::new(),
+            client,
+            schemes,
+            pool,
+            page_load: None,
+            session_file,
+            home_url: config.tabs.home_url,
+            new_tab: config.tabs.new_tab,
+            positions,
+            inputs: RememberedInputs::default(),
+            bookmarks: Bookmarks::default(),
+            history: History::default(),
+            identities: Identities::default(),
+            known_hosts,
             status: AppStatus::Loading,
+            loading_started: Instant::now(),
+            received: 0,
+            command_error: None,
+            trusted_about_url: None,
+            notifications: Notifications::default(),
+            show_notifications: false,
+            show_page_info: false,
+            downloads: Downloads::new(config.handlers, config.downloads_dir.map(PathBuf::from)),
+            external: External::new(config.external_schemes),
+            confirmations: VecDeque::new(),
+            search_url: config.search_url,
+            translation: config.translation,
+            cache: PageCache::new(config.cache),
+            diff: config.diff,
+            trail: Trail::default(),
+            translated_sender,
+            translated,
+            speech: config.speech,
+            reader: None,
+            theme: Theme::new(config.theme),
+            tick_rate: Duration::from_millis(config.ui.tick_rate_ms),
+            frame_budget: Duration::from_millis(config.ui.frame_budget_ms),
+            status_segments: config.ui.status_segments,
+            mark_duplicate_links: config.ui.mark_duplicate_links,
+            link_numbers: config.ui.link_numbers,
+            break_long_words: config.ui.break_long_words,
+            list_continuations: config.ui.list_continuations,
+            smooth_scrolling: config.ui.smooth_scrolling,
+            line_numbers: config.ui.line_numbers,
+            icons: config.ui.icons,
+            scroll_animation: None,
+            auto_scroll_interval: Duration::from_millis(config.ui.auto_scroll_ms),
+            auto_scroll: None,
+            config_modified: config_modified(),
+            config_checked: Instant::now(),
+            config_problems: config.problems,
+            identity_problem,
+            identity_files,
+            passphrase: config.passphrase,
+            area: Rect::default(),
+            window_title: String::new(),
+        };
+        if let Some(session) = session.filter(|session| !session.tabs.is_empty()) {
+            app.restore_session(session);
+        }
+        app.notify_config_problems();
+        match inputs {
+            Ok(inputs) => app.inputs = inputs,
+            Err(err) => app.notifications.error(format!(
+                "Remembered inputs {}: {err}, nothing is remembered",
+                config.remembered_inputs_file
+            )),
+        }
+        match bookmarks {
+            Ok(bookmarks) => app.bookmarks = bookmarks,
+            Err(err) => app.notifications.error(format!(
+                "Bookmarks {bookmarks_file}: {err}, new bookmarks are not kept"
+            )),
+        }
+        match history {
+            Ok(history) => app.history = history,
+            Err(err) => app.notifications.error(format!(
+                "History {}: {err}, visits are not kept",
+                config.history.file
+            )),
+        }
+        match identities {
+            Ok(identities) => {
+                app.identities = identities;
+                app.apply_identities();
+            }
+            Err(err) => app.notifications.error(format!(
+                "Identities {}: {err}, only the client certificate of the configuration is used",
+                config.identities_dir
+            )),
+        }
+        if let Some(problem) = &app.identity_problem {
+            app.notifications.error(format!(
+                "No client certificate, {problem}, see {CERTIFICATES_URL}"
+            ));
         }
+        Ok(app)
     }
 
     pub fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut last_draw: Option<Instant> = None;
+        let mut dirty = true;
         loop {
-            terminal.draw(|frame: &mut Frame| self.draw(frame))?;
-            if matches!(self.status, AppStatus::Loading) {
-                self.load_site()?;
+            if self.poll_page_load() {
+                dirty = true;
+            }
+            while let Some(loaded) = self.pool.try_recv() {
+                self.finish_load(loaded.tab, loaded.response);
+                dirty = true;
+            }
+            while let Ok((id, translation)) = self.translated.try_recv() {
+                self.finish_translation(id, translation);
+                dirty = true;
+            }
+            if let Some(finished) = self.reader.as_mut().and_then(Reader::finished) {
+                if let Err(err) = finished {
+                    self.notifications
+                        .error(format!("Reading aloud failed: {err}"));
+                }
+                self.reader = None;
+                dirty = true;
+            }
+            let ended = self.downloads.take_ended();
+            for download in &ended {
+                let message = format!("{}: {}", download.file_name(), download.state().describe());
+                match download.state() {
+                    DownloadState::Finished { .. } => {
+                        self.notifications.info(message);
+                        self.auto_open(download.clone());
+                    }
+                    _ => self.notifications.error(message),
+                }
+                dirty = true;
+            }
+            if matches!(self.status, AppStatus::Browsing) {
+                if let Some(confirmation) = self.confirmations.pop_front() {
+                    self.status = AppStatus::Confirm(confirmation);
+                    dirty = true;
+                }
+            }
+            if self.config_checked.elapsed() >= CONFIG_CHECK_INTERVAL {
+                self.config_checked = Instant::now();
+                let modified = config_modified();
+                if modified.is_some() && modified != self.config_modified {
+                    self.config_modified = modified;
+                    self.reload_config();
+                    dirty = true;
+                }
+            }
+            if self.auto_scroll_step() {
+                dirty = true;
+            }
+            if (self.downloads.in_progress() || !ended.is_empty()) && self.is_on_downloads_page() {
+                self.show_downloads_page();
+            }
+            let should_draw = match last_draw {
+                None => true,
+                Some(last_draw) => {
+                    let elapsed = last_draw.elapsed();
+                    (dirty && elapsed >= self.frame_budget) || elapsed >= self.tick_rate
+                }
+            };
+            if should_draw {
+                self.animate_scroll();
+                self.update_window_title()?;
+                let started = Instant::now();
+                self.area = terminal.draw(|frame: &mut Frame| self.draw(frame))?.area;
+                last_draw = Some(Instant::now());
+                // Pages too long to draw in the frame budget jump to the end of the animation
+                if let Some(animation) = &mut self.scroll_animation {
+                    if started.elapsed() > self.frame_budget {
+                        animation.frames_left = 1;
+                    }
+                }
+                // The next frame of the animation is drawn as soon as the frame budget allows
+                dirty = self.scroll_animation.is_some();
+            }
+            // Only start loading once the loading screen made it to the terminal
+            if matches!(self.status, AppStatus::Loading) && self.page_load.is_none() && !dirty {
+                if let Err(err) = self.load_site() {
+                    self.status = AppStatus::Browsing;
+                    self.load_failed(self.tab().nav.current(), err);
+                }
+                dirty = true;
+                continue;
+            }
+            let elapsed = last_draw.map(|i| i.elapsed()).unwrap_or_default();
+            let mut timeout = if dirty {
+                self.frame_budget.saturating_sub(elapsed)
+            } else {
+                self.tick_rate.saturating_sub(elapsed)
+            };
+            if let Some(next) = self
+                .auto_scroll
+                .as_ref()
+                .and_then(|auto_scroll| auto_scroll.until_next(Instant::now()))
+            {
+                timeout = timeout.min(next);
             }
-            if event::poll(Duration::from_millis(300))? {
+            if event::poll(timeout)? {
+                dirty = true;
                 if let Event::Key(key_event) = event::read()? {
-                    match self.status {
-                        AppStatus::Loading => {}
-                        AppStatus::Browsing => match key_event.code {
-                            KeyCode::Esc => {
-                                break Ok(());
-                            }
-                            KeyCode::PageUp => {
-                                let step = terminal::size()?.1 - 3;
-                                self.scroll.0 = self.scroll.0.saturating_sub(step);
-                            }
-                            KeyCode::PageDown => {
-                                let step = terminal::size()?.1 - 3;
-                                self.scroll.0 = self.scroll.0.saturating_add(step);
-                            }
-                            KeyCode::Up => {
-                                self.scroll.0 = self.scroll.0.saturating_sub(1);
-                            }
-                            KeyCode::Down => {
-                                self.scroll.0 = self.scroll.0.saturating_add(1);
-                            }
-                            KeyCode::Char('i') => {
-                                self.status = AppStatus::Typing(String::new());
-                            }
-                            KeyCode::Char('<') => {
-                                self.gemspaces_nav.back();
-                                self.set_status_to_loading();
-                            }
-                            KeyCode::Char('>') => {
-                                self.gemspaces_nav.advance();
-                                self.set_status_to_loading();
-                            }
-                            _ => {}
-                        },
-                        AppStatus::Typing(ref mut text) => match key_event.code {
-                            KeyCode::Esc => {
-                                self.status = AppStatus::Browsing;
-                            }
-                            KeyCode::Char(c) => {
-                                text.push(c);
-                            }
-                            KeyCode::Enter => {
-                                if let Ok(n) = text.parse::<usize>() {
-                                    let Some(Content { body, .. }) = &self.content else {
-                                        continue;
-                                    };
-                                    let Body::String(body) = body else {
-                                        continue;
-                                    };
-                                    let parser =
-                                        GemTextParser::new(body, self.gemspaces_nav.current());
-                                    let Some(link) = parser
-                                        .flatten()
-                                        .filter_map(|line| match line {
-                                            GemTextLine::Link { url, .. } => Some(url),
-                                            _ => None,
-                                        })
-                                        .enumerate()
-                                        .filter_map(
-                                            |(n_link, link)| {
-                                                if n_link == n {
-                                                    Some(link)
-                                                } else {
-                                                    None
-                                                }
-                                            },
-                                        )
-                                        .next()
-                                    else {
-                                        continue;
-                                    };
-                                    self.push_url(link);
-                                    continue;
-                                }
-                                if text.starts_with("gemini://") {
-                                    let url = Url::parse(text)?;
-                                    self.push_url(url);
-                                    continue;
-                                }
-                                let url = self.gemspaces_nav.current().join(text)?;
-                                self.push_url(url);
-                            }
-                            _ => {}
-                        },
-                        AppStatus::Input(ref mut text) => match key_event.code {
-                            KeyCode::Esc => {
-                                *text = String::new();
-                            }
-                            KeyCode::Char(c) => {
-                                text.push(c);
-                            }
-                            KeyCode::Enter => {
-                                let mut url = self.gemspaces_nav.current();
-                                url.set_query(Some(text));
-                                self.gemspaces_nav.back();
-                                self.push_url(url);
-                            }
-                            _ => {}
-                        },
+                    if self.handle_key(key_event)?.is_break() {
+                        break Ok(());
                     }
                 }
             }
         }
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self, frame.area());
-    }
-
-    fn load_site(&mut self) -> Result<()> {
-        let response = self.client.request(self.gemspaces_nav.current());
-        let Ok(response) = response else {
-            let err = response.unwrap_err();
-            tracing::error!("Error requesting gemini url: {}", err);
-            return Err(err);
-        };
-        match response {
-            GeminiResponse::Success { mime, body } => {
-                self.content = Some(Content::from_mime_and_bytes(mime, body)?);
-            }
-            GeminiResponse::Input { status: _, prompt } => {
-                self.content = Some(Content {
-                    mime: "text/plain".into(),
-                    body: Body::String(prompt),
-                });
-                self.status = AppStatus::Input(String::new());
-                return Ok(());
+    /// Updates the state for a key press. Breaks when the user asked to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> Result<ControlFlow<()>> {
+        match self.status {
+            AppStatus::Loading => {
+                if key.code == KeyCode::Esc {
+                    self.cancel_load();
+                }
             }
-            response => unimplemented!("For {response:?}"),
+            AppStatus::Browsing => match key.code {
+                KeyCode::Esc if self.show_notifications => {
+                    self.show_notifications = false;
+                }
+                KeyCode::Esc if self.show_page_info => {
+                    self.show_page_info = false;
+                }
+                KeyCode::Esc => {
+                    self.save_session();
+                    if let Err(err) = self.positions.save() {
+                        tracing::error!("Error saving the reading positions: {err}");
+                    }
+                    return Ok(ControlFlow::Break(()));
+                }
+                KeyCode::Char('t') => {
+                    self.open_new_tab()?;
+                }
+                KeyCode::Char('w') => {
+                    self.close_tab();
+                }
+                KeyCode::Char('U') => {
+                    self.reopen_closed_tab();
+                }
+                KeyCode::Char('c') => {
+                    self.insert_tab(self.tab().duplicate());
+                }
+                KeyCode::Char('O') => {
+                    self.status = AppStatus::Overview(TabOverview {
+                        filter: String::new(),
+                        selected: self.current_tab,
+                    });
+                }
+                KeyCode::Char('l') => {
+                    if let Some(document) = self
+                        .tab()
+                        .content
+                        .as_ref()
+                        .and_then(|content| content.document.as_ref())
+                    {
+                        self.status = AppStatus::Links(LinkList::new(document));
+                    }
+                }
+                KeyCode::Char('|') => {
+                    self.toggle_split();
+                }
+                KeyCode::Char('p') => {
+                    let tab = self.tab_mut();
+                    tab.pinned = !tab.pinned;
+                }
+                KeyCode::Char(']') => {
+                    self.visit_sibling(1);
+                }
+                KeyCode::Char('[') => {
+                    self.visit_sibling(-1);
+                }
+                KeyCode::Char('o') => {
+                    if let Some(other) = self.split_tab() {
+                        self.select_tab(other);
+                    }
+                }
+                KeyCode::Tab => {
+                    self.select_tab((self.current_tab + 1) % self.tabs.len());
+                }
+                KeyCode::BackTab => {
+                    self.select_tab((self.current_tab + self.tabs.len() - 1) % self.tabs.len());
+                }
+                KeyCode::Char('n') => {
+                    self.show_notifications = !self.show_notifications;
+                }
+                KeyCode::Char('I') => {
+                    self.show_page_info = !self.show_page_info;
+                }
+                KeyCode::Char('s') => {
+                    let tab = self.tab();
+                    if let Some(content) = &tab.content {
+                        self.status = AppStatus::Saving(
+                            self.downloads
+                                .suggested_path(&tab.nav.current(), &content.mime),
+                        );
+                    }
+                }
+                KeyCode::Char('d') => {
+                    self.push_url(
+                        Url::parse(DOWNLOADS_URL).expect("We know that this is a valid url"),
+                    );
+                }
+                KeyCode::Char('D') => {
+                    self.show_changes();
+                }
+                KeyCode::Char('H') => {
+                    self.push_url(Url::parse(TRAIL_URL).expect("We know that this is a valid url"));
+                }
+                KeyCode::Char('h') => {
+                    self.status = AppStatus::History(HistoryView::default());
+                }
+                KeyCode::Char('C') => {
+                    let url = self.tab().nav.current();
+                    self.status = AppStatus::Identities(IdentityMenu::new(&url));
+                }
+                KeyCode::Char('L') => {
+                    self.translate_page();
+                }
+                KeyCode::Char('r') => match &mut self.reader {
+                    Some(reader) => reader.play_pause(),
+                    None => self.read_aloud(),
+                },
+                KeyCode::Char('R') => {
+                    self.reader = None;
+                }
+                KeyCode::Char('}') => {
+                    if let Some(reader) = &self.reader {
+                        reader.next_paragraph();
+                    }
+                }
+                KeyCode::Char('a') => match self.auto_scroll {
+                    Some(_) => self.auto_scroll = None,
+                    None => {
+                        let url = self.tab().nav.current();
+                        self.auto_scroll = Some(AutoScroll::new(
+                            url,
+                            self.auto_scroll_interval,
+                            Instant::now(),
+                        ));
+                    }
+                },
+                KeyCode::Char('+') if self.auto_scroll.is_some() => {
+                    if let Some(auto_scroll) = &mut self.auto_scroll {
+                        auto_scroll.faster();
+                    }
+                }
+                KeyCode::Char('-') if self.auto_scroll.is_some() => {
+                    if let Some(auto_scroll) = &mut self.auto_scroll {
+                        auto_scroll.slower();
+                    }
+                }
+                KeyCode::Char(' ') if self.auto_scroll.is_some() => {
+                    if let Some(auto_scroll) = &mut self.auto_scroll {
+                        auto_scroll.play_pause(Instant::now());
+                    }
+                }
+                KeyCode::Char('#') => {
+                    self.line_numbers = !self.line_numbers;
+                }
+                KeyCode::Char('T') => {
+                    self.cycle_trust_policy();
+                }
+                KeyCode::Char('u') => {
+                    self.set_status_to_loading();
+                }
+                KeyCode::Char('b') => {
+                    self.bookmark_current_page();
+                }
+                KeyCode::Char('B') => {
+                    self.push_url(
+                        Url::parse(BOOKMARKS_URL).expect("We know that this is a valid url"),
+                    );
+                }
+                KeyCode::Char('y') => {
+                    let url = self.tab().nav.current();
+                    match clipboard::copy(url.as_str()) {
+                        Ok(()) => self.notifications.info(format!("Copied {url}")),
+                        Err(err) => self.notifications.error(format!("Copy failed: {err}")),
+                    }
+                }
+                KeyCode::PageUp => {
+                    self.scroll_page(-(self.area.height.saturating_sub(3) as i16));
+                }
+                KeyCode::PageDown => {
+                    self.scroll_page(self.area.height.saturating_sub(3) as i16);
+                }
+                KeyCode::Up => {
+                    self.scroll_by(-1);
+                }
+                KeyCode::Down => {
+                    self.scroll_by(1);
+                }
+                KeyCode::Char('i') => {
+                    self.status = AppStatus::Typing(String::new());
+                }
+                KeyCode::Char('<') => {
+                    self.tab_mut().nav.back();
+                    self.set_status_to_loading();
+                }
+                KeyCode::Char('>') => {
+                    self.tab_mut().nav.advance();
+                    self.set_status_to_loading();
+                }
+                _ => {}
+            },
+            AppStatus::Typing(ref mut text) => match key.code {
+                KeyCode::Esc => {
+                    self.command_error = None;
+                    self.status = AppStatus::Browsing;
+                }
+                KeyCode::Char(c) => {
+                    self.command_error = None;
+                    text.push(c);
+                }
+                KeyCode::Backspace => {
+                    self.command_error = None;
+                    text.pop();
+                }
+                KeyCode::Enter => {
+                    // Before numbers, as parsing them accepts a leading +
+                    if let Some(links) = text.strip_prefix('+') {
+                        let links = links.to_string();
+                        self.open_links_in_background(&links);
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    if let Ok(n) = text.parse::<usize>() {
+                        let links = self.links().to_vec();
+                        let Some(link) = links.get(n).cloned() else {
+                            self.command_error = Some(format!("No link {n}"));
+                            return Ok(ControlFlow::Continue(()));
+                        };
+                        self.push_url(link);
+                        self.tab_mut().siblings = Some(Siblings { links, position: n });
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    if let Some(n) = text.strip_prefix('|') {
+                        let n = n.trim().to_string();
+                        match n.parse().ok().and_then(|n| self.link(n)) {
+                            Some(link) => self.open_in_other_pane(link),
+                            None => {
+                                self.command_error = Some(format!("No link {n}"));
+                            }
+                        }
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    if let Some(command) = text.strip_prefix(':') {
+                        let command = command.trim().to_string();
+                        self.run_command(&command);
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    match url_bar::resolve(
+                        &self.tabs[self.current_tab].nav.current(),
+                        text,
+                        &self.search_url,
+                    ) {
+                        Ok(url) => {
+                            self.trusted_about_url = Some(url.clone());
+                            self.push_url(url);
+                        }
+                        Err(err) => {
+                            self.command_error = Some(format!("Invalid URL: {err}"));
+                        }
+                    }
+                }
+                _ => {}
+            },
+            AppStatus::Confirm(_) => match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    let status = std::mem::replace(&mut self.status, AppStatus::Browsing);
+                    if let AppStatus::Confirm(confirmation) = status {
+                        self.confirmed(confirmation.action);
+                    }
+                }
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.status = AppStatus::Browsing;
+                }
+                _ => {}
+            },
+            AppStatus::Overview(ref mut overview) => match key.code {
+                KeyCode::Esc => {
+                    self.status = AppStatus::Browsing;
+                }
+                KeyCode::Char(c) => {
+                    overview.filter.push(c);
+                    overview.selected = 0;
+                }
+                KeyCode::Backspace => {
+                    overview.filter.pop();
+                    overview.selected = 0;
+                }
+                KeyCode::Up => {
+                    let matching = overview.matching(&self.tabs).len();
+                    overview.move_selection(-1, matching);
+                }
+                KeyCode::Down => {
+                    let matching = overview.matching(&self.tabs).len();
+                    overview.move_selection(1, matching);
+                }
+                KeyCode::Enter => {
+                    if let Some(index) = overview.selected_tab(&self.tabs) {
+                        self.select_tab(index);
+                    }
+                }
+                _ => {}
+            },
+            AppStatus::Identities(ref mut menu) => match key.code {
+                KeyCode::Esc => {
+                    self.status = AppStatus::Browsing;
+                }
+                KeyCode::Char('n') => {
+                    self.status = AppStatus::Typing(":new-identity ".to_string());
+                }
+                KeyCode::Up => menu.move_selection(-1, &self.identities),
+                KeyCode::Down => menu.move_selection(1, &self.identities),
+                KeyCode::Enter => {
+                    let name = menu.chosen(&self.identities).map(str::to_string);
+                    let prefix = menu.prefix.clone();
+                    self.status = AppStatus::Browsing;
+                    self.use_identity(name.as_deref(), &prefix);
+                }
+                _ => {}
+            },
+            AppStatus::History(ref mut view) => match key.code {
+                KeyCode::Esc => {
+                    self.status = AppStatus::Browsing;
+                }
+                KeyCode::Char(c) => {
+                    view.filter.push(c);
+                    view.selected = 0;
+                }
+                KeyCode::Backspace => {
+                    view.filter.pop();
+                    view.selected = 0;
+                }
+                KeyCode::Up => {
+                    let matching = self.history.search(&view.filter).len();
+                    view.move_selection(-1, matching);
+                }
+                KeyCode::Down => {
+                    let matching = self.history.search(&view.filter).len();
+                    view.move_selection(1, matching);
+                }
+                KeyCode::Enter => {
+                    let url = view.selected_url(&self.history);
+                    self.status = AppStatus::Browsing;
+                    if let Some(url) = url {
+                        self.push_url(url);
+                    }
+                }
+                _ => {}
+            },
+            AppStatus::Links(ref mut links) => match key.code {
+                KeyCode::Esc => {
+                    self.status = AppStatus::Browsing;
+                }
+                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    let (count, gemtext) = links.export();
+                    match self.downloads.write("links.gmi", &gemtext) {
+                        Ok(path) => self
+                            .notifications
+                            .info(format!("Exported {count} links to {}", path.display())),
+                        Err(err) => self
+                            .notifications
+                            .error(format!("Could not export the links: {err}")),
+                    }
+                }
+                KeyCode::Char(' ') => links.toggle_mark(),
+                KeyCode::Char(c) => links.type_char(c),
+                KeyCode::Backspace => links.erase_char(),
+                KeyCode::Tab => links.cycle_order(),
+                KeyCode::Up => links.move_selection(-1),
+                KeyCode::Down => links.move_selection(1),
+                KeyCode::Enter => {
+                    let chosen = links.chosen();
+                    self.status = AppStatus::Browsing;
+                    match chosen.as_slice() {
+                        [] => {}
+                        [link] => self.push_url(link.clone()),
+                        _ => {
+                            let count = chosen.len();
+                            for link in chosen {
+                                self.open_background_tab(link);
+                            }
+                            self.notifications
+                                .info(format!("Opening {count} tabs in the background"));
+                        }
+                    }
+                }
+                _ => {}
+            },
+            AppStatus::Saving(ref mut text) => match key.code {
+                KeyCode::Esc => {
+                    self.status = AppStatus::Browsing;
+                }
+                KeyCode::Char(c) => {
+                    text.push(c);
+                }
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Enter => {
+                    let path = text.clone();
+                    self.status = AppStatus::Browsing;
+                    self.save_content(&path);
+                }
+                _ => {}
+            },
+            AppStatus::Input(ref mut text) => match key.code {
+                KeyCode::Esc => {
+                    self.command_error = None;
+                    *text = String::new();
+                }
+                KeyCode::Char(c) => {
+                    self.command_error = None;
+                    text.push(c);
+                }
+                KeyCode::Backspace => {
+                    self.command_error = None;
+                    text.pop();
+                }
+                KeyCode::Enter => {
+                    let answer = text.clone();
+                    let mut url = self.tabs[self.current_tab].nav.current();
+                    url.set_query(Some(&answer));
+                    // Kept to be shortened, the answer is percent-encoded in the URL
+                    let excess = url.as_str().len().saturating_sub(MAX_REQUEST_URL_SIZE);
+                    if excess > 0 {
+                        self.command_error = Some(format!(
+                            "{excess} bytes too long, requests are at most \
+                             {MAX_REQUEST_URL_SIZE} bytes"
+                        ));
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    self.remember_input(&answer);
+                    if url.scheme() == "misfin" {
+                        let host = url.host_str().unwrap_or_default();
+                        self.status = AppStatus::Confirm(Confirmation {
+                            prompt: format!("Send the message to {}@{host}?", url.username()),
+                            action: ConfirmAction::SendMessage(url),
+                        });
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    match self.input_warning(&url) {
+                        Some(prompt) => {
+                            self.status = AppStatus::Confirm(Confirmation {
+                                prompt,
+                                action: ConfirmAction::SendInput(url),
+                            });
+                        }
+                        None => self.send_input(url),
+                    }
+                }
+                _ => {}
+            },
         }
-        self.status = AppStatus::Browsing;
-        Ok(())
+        Ok(ControlFlow::Continue(()))
     }
 
-    fn push_url(&mut self, url: Url) {
-        self.gemspaces_nav.push(url);
-        self.set_status_to_loading();
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self, frame.area());
     }
 
-    fn set_status_to_loading(&mut self) {
-        self.scroll = (0, 0);
-        self.status = AppStatus::Loading;
-        self.content = None;
+    fn render_tab(&self, tab: &Tab, area: Rect, buf: &mut Buffer, border: BorderType) {
+        let url = tab.nav.current();
+        let area = match tab.cached {
+            Some(fetched) => {
+                let [banner, area] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+                Line::from(format!(
+                    " Cached copy fetched {} ({}), press u to reload",
+                    trail::format_time(fetched),
+                    format_age(fetched)
+                ))
+                .style(self.theme.banner())
+                .render(banner, buf);
+                area
+            }
+            None => area,
+        };
+        let frame_style = self.theme.frame_style(&url);
+        let mut main_block = Block::bordered()
+            .border_type(border)
+            .border_style(frame_style);
+        // Long URLs are shortened to fit the border, I shows them in full
+        let display_url = url_display::decoded(url.as_str());
+        let short_url = url_display::shorten(&display_url, area.width.saturating_sub(4) as usize);
+        main_block = match tab.page_title() {
+            Some(title) => main_block
+                .title_top(Line::from(title).bold().style(frame_style))
+                .title_bottom(Line::from(short_url).style(frame_style)),
+            None => main_block.title_top(Line::from(short_url).bold().style(frame_style)),
+        };
+        match &tab.content {
+            None => {
+                Paragraph::new(if tab.loading {
+                    "Loading..."
+                } else {
+                    "No content"
+                })
+                .wrap(Wrap { trim: true })
+                .block(main_block)
+                .render(area, buf);
+            }
+            Some(content) => match &content.body {
+                Body::Bytes(bytes) => {
+                    Paragraph::new(format!(
+                        "Format not supported! ({} bytes of {}) Press s to save it.",
+                        bytes.len(),
+                        content.mime
+                    ))
+                    .wrap(Wrap { trim: true })
+                    .block(main_block)
+                    .render(area, buf);
+                }
+                Body::File(file) if content.mime.starts_with("text/") => {
+                    let height = area.height.saturating_sub(2) as usize;
+                    let text = match file.lines(tab.scroll.0 as usize, height) {
+                        Ok(lines) => lines.join("\n"),
+                        Err(err) => format!("Error reading body: {err}"),
+                    };
+                    Paragraph::new(text)
+                        .wrap(Wrap { trim: true })
+                        .block(main_block)
+                        .render(area, buf);
+                }
+                Body::File(file) => {
+                    Paragraph::new(format!(
+                        "Format not supported! ({} bytes of {}) Press s to save it.",
+                        file.len(),
+                        content.mime
+                    ))
+                    .wrap(Wrap { trim: true })
+                    .block(main_block)
+                    .render(area, buf);
+                }
+                Body::String(body) => {
+                    let rtl = bidi::is_rtl(&content.mime, body);
+                    let width = area.width.saturating_sub(2) as usize;
+                    if content.mime.starts_with("text/gemini") {
+                        let gutter = self.gutter_width(content);
+                        let text_width = width.saturating_sub(gutter);
+                        let mut lines = Vec::new();
+                        // With the gutter, lines are wrapped here to number only their first row.
+                        // Quotes and list items are always wrapped here, to indent their rows under
+                        // their `prefix`, repeated on each row, or with `hanging` only on the first.
+                        let push = |lines: &mut Vec<Line>,
+                                    source: usize,
+                                    text: String,
+                                    style: Style,
+                                    pre: bool,
+                                    prefix: &str,
+                                    hanging: bool| {
+                            let indent = prefix.width();
+                            let rows = if pre && gutter > 0 {
+                                wrap::chunks(&text, text_width)
+                            } else if pre {
+                                lines.push(Line::styled(text, style).left_aligned());
+                                return;
+                            } else if rtl {
+                                bidi::visual_lines(
+                                    &text,
+                                    text_width.saturating_sub(indent),
+                                    self.break_long_words,
+                                )
+                            } else if self.break_long_words || gutter > 0 || indent > 0 {
+                                wrap::wrap(
+                                    &text,
+                                    text_width.saturating_sub(indent),
+                                    self.break_long_words,
+                                )
+                            } else {
+                                lines.push(Line::styled(text, style).left_aligned());
+                                return;
+                            };
+                            for (row, text) in rows.into_iter().enumerate() {
+                                let prefix = if hanging && row > 0 {
+                                    // No-break spaces, which the wrapping does not trim
+                                    "\u{a0}".repeat(indent)
+                                } else if rtl {
+                                    // Right to left quotes have their bars on the right
+                                    prefix.chars().rev().collect()
+                                } else {
+                                    prefix.to_string()
+                                };
+                                if gutter == 0 {
+                                    let line = if prefix.is_empty() {
+                                        Line::styled(text, style)
+                                    } else if rtl {
+                                        Line::from(vec![
+                                            Span::styled(text, style),
+                                            Span::styled(prefix, style),
+                                        ])
+                                    } else {
+                                        Line::from(vec![
+                                            Span::styled(prefix, style),
+                                            Span::styled(text, style),
+                                        ])
+                                    };
+                                    lines.push(if rtl {
+                                        line.right_aligned()
+                                    } else {
+                                        line.left_aligned()
+                                    });
+                                    continue;
+                                }
+                                let number = if row == 0 {
+                                    source.to_string()
+                                } else {
+                                    String::new()
+                                };
+                                // Padded with no-break spaces, which the wrapping does not trim
+                                let padding = "\u{a0}".repeat(gutter - 3 - number.len());
+                                let mut spans = vec![Span::styled(
+                                    format!("{padding}{number} │ "),
+                                    self.theme.gutter(),
+                                )];
+                                if rtl && !pre {
+                                    spans.push(Span::raw(
+                                        " ".repeat(
+                                            text_width.saturating_sub(text.width() + indent),
+                                        ),
+                                    ));
+                                    // Trailing spaces of a full row would wrap to an empty one
+                                    spans.push(Span::styled(text.trim_end().to_string(), style));
+                                    spans.push(Span::styled(prefix, style));
+                                } else {
+                                    spans.push(Span::styled(prefix, style));
+                                    spans.push(Span::styled(text.trim_end().to_string(), style));
+                                }
+                                lines.push(Line::from(spans).left_aligned());
+                            }
+                        };
+                        let document_lines = content.document.iter().flat_map(|document| {
+                            document
+                                .lines
+                                .iter()
+                                .zip(&document.source_lines)
+                                .map(move |(line, source)| (document, *source, line))
+                        });
+                        // Whether indented text lines continue the list item before them
+                        let mut in_item = false;
+                        for (document, source, line) in document_lines {
+                            match line {
+                                DocLine::Text(text)
+                                    if in_item
+                                        && self.list_continuations
+                                        && text.starts_with([' ', '\t'])
+                                        && !text.trim().is_empty() =>
+                                {
+                                    let indent = "\u{a0}".repeat(document::BULLET.width());
+                                    push(
+                                        &mut lines,
+                                        source,
+                                        text.trim().to_string(),
+                                        Style::new(),
+                                        false,
+                                        &indent,
+                                        false,
+                                    );
+                                    continue;
+                                }
+                                DocLine::Text(text) => {
+                                    push(
+                                        &mut lines,
+                                        source,
+                                        text.clone(),
+                                        Style::new(),
+                                        false,
+                                        "",
+                                        false,
+                                    );
+                                }
+                                DocLine::ListItem(text) => {
+                                    push(
+                                        &mut lines,
+                                        source,
+                                        text.clone(),
+                                        Style::new(),
+                                        false,
+                                        document::BULLET,
+                                        true,
+                                    );
+                                    in_item = true;
+                                    continue;
+                                }
+                                DocLine::PreFormatted(text) => {
+                                    push(
+                                        &mut lines,
+                                        source,
+                                        text.clone(),
+                                        self.theme.preformatted(),
+                                        true,
+                                        "",
+                                        false,
+                                    );
+                                }
+                                DocLine::Heading { level, text } => {
+                                    push(
+                                        &mut lines,
+                                        source,
+                                        text.clone(),
+                                        self.theme.heading(*level),
+                                        false,
+                                        "",
+                                        false,
+                                    );
+                                }
+                                DocLine::Quote { level, text } => {
+                                    push(
+                                        &mut lines,
+                                        source,
+                                        text.clone(),
+                                        self.theme.quote(),
+                                        false,
+                                        &document::quote_prefix(*level),
+                                        false,
+                                    );
+                                }
+                                DocLine::Link {
+                                    url, duplicate_of, ..
+                                } => {
+                                    let mut text = document
+                                        .display(line, self.shown_link_numbers(), self.icons)
+                                        .into_owned();
+                                    if let Some(first) =
+                                        duplicate_of.filter(|_| self.mark_duplicate_links)
+                                    {
+                                        text.push_str(&format!(" (same as [{first}])"));
+                                    }
+                                    let style = if self.trail.visited(url) {
+                                        self.theme.visited_link()
+                                    } else {
+                                        self.theme.link(url.scheme() != "gemini")
+                                    };
+                                    push(&mut lines, source, text, style, false, "", false);
+                                }
+                            }
+                            in_item = false;
+                        }
+
+                        Paragraph::new(lines)
+                            .wrap(Wrap { trim: true })
+                            .block(main_block)
+                            .scroll(tab.scroll)
+                            .render(area, buf);
+                    } else if rtl {
+                        let lines: Vec<Line> = body
+                            .lines()
+                            .flat_map(|line| bidi::visual_lines(line, width, self.break_long_words))
+                            .map(|line| Line::raw(line).right_aligned())
+                            .collect();
+                        Paragraph::new(lines)
+                            .wrap(Wrap { trim: true })
+                            .block(main_block)
+                            .scroll(tab.scroll)
+                            .render(area, buf);
+                    } else if content.mime.starts_with("text/plain") {
+                        Paragraph::new(self.linkified_lines(body))
+                            .wrap(Wrap { trim: true })
+                            .block(main_block)
+                            .scroll(tab.scroll)
+                            .render(area, buf);
+                    } else {
+                        Paragraph::new(body.as_str())
+                            .wrap(Wrap { trim: true })
+                            .block(main_block)
+                            .scroll(tab.scroll)
+                            .render(area, buf);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Plain text with its URLs numbered like gemtext links, so they can be followed the same way
+    fn linkified_lines<'a>(&self, body: &'a str) -> Vec<Line<'a>> {
+        let mut n_links = 0;
+        body.lines()
+            .map(|line| {
+                let mut spans = Vec::new();
+                let mut position = 0;
+                for (start, end, url) in linkify::find_urls(line) {
+                    spans.push(Span::raw(&line[position..start]));
+                    spans.push(Span::styled(
+                        format!(
+                            "{}{}",
+                            self.shown_link_numbers().prefix(n_links, 0),
+                            &line[start..end]
+                        ),
+                        self.theme.link(url.scheme() != "gemini"),
+                    ));
+                    n_links += 1;
+                    position = end;
+                }
+                spans.push(Span::raw(&line[position..]));
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    fn status_text(&self) -> String {
+        match self.status {
+            AppStatus::Browsing if self.tab().loading => {
+                let frame = self.loading_started.elapsed().as_millis() / SPINNER_FRAME.as_millis();
+                let spinner = SPINNER[frame as usize % SPINNER.len()];
+                format!("{spinner} {}", AppStatus::Loading.as_str())
+            }
+            AppStatus::Loading => {
+                let frame = self.loading_started.elapsed().as_millis() / SPINNER_FRAME.as_millis();
+                let spinner = SPINNER[frame as usize % SPINNER.len()];
+                if self.received == 0 {
+                    format!("{spinner} {}", self.status.as_str())
+                } else {
+                    let max = self.client.options().max_body_bytes;
+                    let elapsed = self.loading_started.elapsed();
+                    format!(
+                        "{spinner} {} {}",
+                        self.status.as_str(),
+                        status_bar::transfer(self.received, elapsed, Some(max))
+                    )
+                }
+            }
+            AppStatus::Browsing => match (&self.reader, &self.auto_scroll) {
+                (Some(reader), _) if reader.is_paused() => "Reading paused".to_string(),
+                (Some(_), _) => "Reading aloud".to_string(),
+                (None, Some(auto_scroll)) => auto_scroll.describe(),
+                (None, None) => self.status.as_str().to_string(),
+            },
+            _ => self.status.as_str().to_string(),
+        }
+    }
+
+    fn status_segment(&self, segment: Segment) -> Option<String> {
+        let tab = self.tab();
+        match segment {
+            Segment::Mode => Some(self.status_text()),
+            Segment::Url => Some(url_display::decoded(tab.nav.current().as_str()).into_owned()),
+            Segment::Scroll => {
+                let (rows, height) = self.page_rows()?;
+                Some(status_bar::scroll(tab.scroll.0, rows, height))
+            }
+            Segment::Clock => Some(status_bar::clock(SystemTime::now())),
+            Segment::Identity => {
+                let name = self.identity_for(&tab.nav.current())?;
+                Some(format!("as {name}"))
+            }
+            Segment::Trust => {
+                let known = self.known_hosts.get(tab.nav.current().host_str()?)?;
+                let trust = status_bar::trust(known.policy);
+                Some(match self.icons.trust(known.policy) {
+                    Some(icon) => format!("{icon} {trust}"),
+                    None => trust.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Rows of the current page once wrapped, and rows of it shown at once
+    fn page_rows(&self) -> Option<(usize, usize)> {
+        let document = self.tab().content.as_ref()?.document.as_ref()?;
+        let width = self.area.width.saturating_sub(2) as usize;
+        let width = width.saturating_sub(self.gutter_width(self.tab().content.as_ref()?));
+        let rows = document.rows_before(
+            document.lines.len(),
+            width,
+            self.shown_link_numbers(),
+            self.icons,
+        );
+        let tab_bar = if self.tabs.len() > 1 { 1 } else { 0 };
+        let height = self.area.height.saturating_sub(3 + tab_bar) as usize;
+        Some((rows, height))
+    }
+
+    /// Columns taken left of a page by its source line numbers, when shown
+    fn gutter_width(&self, content: &Content) -> usize {
+        match &content.document {
+            Some(document) if self.line_numbers => document.gutter_width(),
+            _ => 0,
+        }
+    }
+
+    /// Name of the client certificate the capsule of `url` was sent
+    fn identity_for(&self, url: &Url) -> Option<String> {
+        let name = self.client.identity_of(url)?;
+        self.client.identity_sent_to(url).then_some(name)
+    }
+
+    fn render_page_info(&self, area: Rect, buf: &mut Buffer) {
+        let tab = self.tab();
+        let url = tab.nav.current();
+        let display_url = url_display::decoded(url.as_str());
+        let mut lines = vec![format!("URL: {display_url}")];
+        if display_url != url.as_str() {
+            lines.push(format!("Encoded: {url}"));
+        }
+        if let Some(title) = tab.page_title() {
+            lines.push(format!("Title: {title}"));
+        }
+        if let Some(content) = &tab.content {
+            lines.push(format!("Type: {}", content.mime));
+            if let Some(document) = &content.document {
+                lines.push(format!("Links: {}", document.links.len()));
+            }
+        }
+        if let Some(fetched) = tab.cached {
+            lines.push(format!(
+                "Cached copy fetched {}",
+                trail::format_time(fetched)
+            ));
+        } else if let Some(info) = self.client.response_info(&url) {
+            if let Some(tls) = info.tls {
+                lines.push(format!("TLS: {tls}"));
+            }
+            // Quoted so that stray spaces show
+            if let Some(meta) = info.meta {
+                lines.push(format!("Meta: {meta:?}"));
+            }
+            match info.close_notify {
+                Some(true) => lines.push("Closed with TLS close_notify".to_string()),
+                Some(false) => lines
+                    .push("Closed without TLS close_notify, the body may be cut short".to_string()),
+                None => {}
+            }
+            for warning in info.warnings {
+                lines.push(format!("Not conforming: {warning}"));
+            }
+        }
+        Clear.render(area, buf);
+        Paragraph::new(lines.into_iter().map(Line::from).collect::<Vec<_>>())
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title_top("Page info"))
+            .render(area, buf);
+    }
+
+    fn render_tab_bar(&self, area: Rect, buf: &mut Buffer) {
+        let spans: Vec<Span> = self
+            .tabs
+            .iter()
+            .enumerate()
+            .map(|(index, tab)| {
+                let loading = if tab.loading { "… " } else { "" };
+                let label = if tab.pinned {
+                    tab.host_label()
+                } else {
+                    tab.label()
+                };
+                let label = format!(" {}:{loading}{label} ", index + 1);
+                if index == self.current_tab {
+                    Span::styled(label, Style::new().reversed())
+                } else {
+                    Span::raw(label)
+                }
+            })
+            .collect();
+        Line::from(spans).render(area, buf);
+    }
+
+    fn tab(&self) -> &Tab {
+        &self.tabs[self.current_tab]
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.current_tab]
+    }
+
+    /// Opens a tab after the current one and selects it.
+    fn open_tab(&mut self, url: Url) {
+        self.current_tab += 1;
+        self.tabs.insert(self.current_tab, Tab::new(url));
+        self.set_status_to_loading();
+    }
+
+    /// Opens a tab with the configured new tab page.
+    fn open_new_tab(&mut self) -> Result<()> {
+        match self.new_tab {
+            NewTabPage::Blank => {
+                let blank = Url::parse("about:blank").expect("We know that this is a valid url");
+                self.open_tab(blank.clone());
+                self.load_about_page(blank)?;
+                self.status = AppStatus::Typing(String::new());
+            }
+            NewTabPage::Home => self.open_tab(self.home_url.clone()),
+            NewTabPage::Trail => {
+                self.open_tab(Url::parse(TRAIL_URL).expect("We know that this is a valid url"))
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens a tab at the end, loading it with the worker pool.
+    fn open_background_tab(&mut self, url: Url) {
+        let tab = self.background_tab(url);
+        self.tabs.push(tab);
+    }
+
+    /// A tab loaded with the worker pool. About pages are loaded once the tab is selected.
+    fn background_tab(&self, url: Url) -> Tab {
+        let mut tab = Tab::new(url.clone());
+        if url.scheme() != "about" {
+            tab.loading = true;
+            self.pool.load(tab.id, url);
+        }
+        tab
+    }
+
+    /// Opens the links typed like `1 3 5-8` in background tabs.
+    fn open_links_in_background(&mut self, text: &str) {
+        let mut links = Vec::new();
+        for part in text.split([' ', ',']).filter(|part| !part.is_empty()) {
+            let range = match part.split_once('-') {
+                Some((start, end)) => start.parse().and_then(|start| Ok(start..=end.parse()?)),
+                None => part.parse().map(|n| n..=n),
+            };
+            let Ok(range) = range else {
+                self.command_error = Some(format!("Invalid link number {part}"));
+                return;
+            };
+            for n in range {
+                let Some(link) = self.link(n) else {
+                    self.command_error = Some(format!("No link {n}"));
+                    return;
+                };
+                links.push(link);
+            }
+        }
+        self.status = AppStatus::Browsing;
+        let count = links.len();
+        for link in links {
+            self.open_background_tab(link);
+        }
+        self.notifications
+            .info(format!("Opening {count} tabs in the background"));
+    }
+
+    /// Index of the tab in the other pane, if the view is split
+    fn split_tab(&self) -> Option<usize> {
+        let id = self.split?;
+        self.tabs
+            .iter()
+            .position(|tab| tab.id == id)
+            .filter(|index| *index != self.current_tab)
+    }
+
+    /// Splits the view, with the current page in a new tab for the other pane, or goes back to a
+    /// single pane.
+    fn toggle_split(&mut self) {
+        if self.split_tab().is_some() {
+            self.split = None;
+            return;
+        }
+        let url = self.tab().nav.current();
+        let tab = self.background_tab(url);
+        self.split = Some(tab.id);
+        self.tabs.insert(self.current_tab + 1, tab);
+    }
+
+    fn open_in_other_pane(&mut self, url: Url) {
+        self.status = AppStatus::Browsing;
+        let Some(other) = self.split_tab() else {
+            let tab = self.background_tab(url);
+            self.split = Some(tab.id);
+            self.tabs.insert(self.current_tab + 1, tab);
+            return;
+        };
+        let tab = &mut self.tabs[other];
+        tab.nav.push(url.clone());
+        tab.scroll = (0, 0);
+        tab.content = None;
+        tab.input = false;
+        if url.scheme() != "about" {
+            tab.loading = true;
+            self.pool.load(tab.id, url);
+        }
+    }
+
+    fn close_tab(&mut self) {
+        if self.tabs.len() == 1 {
+            self.notifications.error("Can not close the last tab");
+            return;
+        }
+        if self.tab().pinned {
+            self.notifications
+                .error("Pinned tabs are not closed, press p to unpin it");
+            return;
+        }
+        let mut tab = self.tabs.remove(self.current_tab);
+        // What it was loading is dropped, it is loaded again once reopened
+        tab.loading = false;
+        self.closed_tabs.truncate(CLOSED_TABS_CAPACITY - 1);
+        self.closed_tabs.push_front(tab);
+        self.select_tab(self.current_tab.min(self.tabs.len() - 1));
+    }
+
+    /// Opens the last closed tab again after the current one, with its history and position.
+    fn reopen_closed_tab(&mut self) {
+        let Some(tab) = self.closed_tabs.pop_front() else {
+            self.notifications.error("No closed tab to reopen");
+            return;
+        };
+        self.insert_tab(tab);
+    }
+
+    /// Inserts a tab after the current one and selects it, loading it if it has no content.
+    fn insert_tab(&mut self, tab: Tab) {
+        self.current_tab += 1;
+        self.tabs.insert(self.current_tab, tab);
+        self.select_tab(self.current_tab);
+    }
+
+    fn select_tab(&mut self, index: usize) {
+        // Selecting the tab of the other pane moves the focus to it
+        if self.split_tab() == Some(index) {
+            self.split = Some(self.tab().id);
+        }
+        self.current_tab = index;
+        self.tabs[index].unread = false;
+        let tab = &self.tabs[index];
+        let unloaded = tab.content.is_none() && !tab.loading;
+        self.status = if tab.input {
+            self.prompt_status()
+        } else {
+            AppStatus::Browsing
+        };
+        self.command_error = None;
+        // Tabs that could not be loaded in the background are loaded once selected
+        if unloaded {
+            self.set_status_to_loading();
+        }
+    }
+
+    /// Scrolls the current page, remembering where it is read.
+    fn scroll_by(&mut self, rows: i16) {
+        let tab = self.tab_mut();
+        tab.scroll.0 = tab.scroll.0.saturating_add_signed(rows);
+        if tab.content.is_some() {
+            let (url, row) = (tab.nav.current(), tab.scroll.0);
+            self.positions.set(&url, row);
+        }
+    }
+
+    /// Scrolls a line when auto-scroll is due, stopping at the end of the page or when another
+    /// page is shown. Returns whether anything changed.
+    fn auto_scroll_step(&mut self) -> bool {
+        let Some(auto_scroll) = &mut self.auto_scroll else {
+            return false;
+        };
+        if auto_scroll.url != self.tabs[self.current_tab].nav.current() {
+            self.auto_scroll = None;
+            return true;
+        }
+        if !auto_scroll.due(Instant::now()) {
+            return false;
+        }
+        let scroll = self.tab().scroll.0 as usize;
+        match self.page_rows() {
+            Some((rows, height)) if scroll + height >= rows => {
+                self.auto_scroll = None;
+                self.notifications
+                    .info("Auto-scroll reached the end of the page");
+            }
+            _ => self.scroll_by(1),
+        }
+        true
+    }
+
+    /// Scrolls the current page by a page, in a few frames with smooth scrolling. Pages scrolled
+    /// again before the end of the animation go on from where it was going.
+    fn scroll_page(&mut self, rows: i16) {
+        if !self.smooth_scrolling {
+            self.scroll_by(rows);
+            return;
+        }
+        let url = self.tab().nav.current();
+        let from = match &self.scroll_animation {
+            Some(animation) if animation.url == url => animation.target,
+            _ => self.tab().scroll.0,
+        };
+        self.scroll_animation = Some(ScrollAnimation {
+            url,
+            target: from.saturating_add_signed(rows),
+            frames_left: SCROLL_FRAMES,
+        });
+    }
+
+    /// Scrolls one frame of the animation, the last one ending where it goes
+    fn animate_scroll(&mut self) {
+        let Some(animation) = self.scroll_animation.as_mut() else {
+            return;
+        };
+        let tab = &self.tabs[self.current_tab];
+        if animation.url != tab.nav.current() {
+            self.scroll_animation = None;
+            return;
+        }
+        let distance = i32::from(animation.target) - i32::from(tab.scroll.0);
+        let step = distance / i32::from(animation.frames_left);
+        animation.frames_left -= 1;
+        let step = if animation.frames_left == 0 {
+            self.scroll_animation = None;
+            distance
+        } else {
+            step
+        };
+        self.scroll_by(step.clamp(i16::MIN.into(), i16::MAX.into()) as i16);
+    }
+
+    /// Opens the tabs of the last session. The selected one is loaded right away and the others
+    /// with the worker pool.
+    fn restore_session(&mut self, mut session: Session) {
+        session.current = session.current.min(session.tabs.len() - 1);
+        session.pinned_first();
+        let current = session.current;
+        self.tabs.clear();
+        for (index, saved) in session.tabs.into_iter().enumerate() {
+            let mut tab = if index == current {
+                Tab::new(saved.url)
+            } else {
+                self.background_tab(saved.url)
+            };
+            tab.pinned = saved.pinned;
+            self.tabs.push(tab);
+        }
+        self.current_tab = current;
+        self.set_status_to_loading();
+    }
+
+    fn save_session(&self) {
+        let session = Session {
+            current: self.current_tab,
+            tabs: self
+                .tabs
+                .iter()
+                .map(|tab| SessionTab {
+                    url: tab.nav.current(),
+                    pinned: tab.pinned,
+                })
+                .collect(),
+        };
+        if let Err(err) = session.save(&self.session_file) {
+            tracing::error!("Error saving the session to {:?}: {err}", self.session_file);
+        }
+    }
+
+    /// Opens a tab with the current page translated by the configured command.
+    fn translate_page(&mut self) {
+        let Some(command) = self.translation.command.clone() else {
+            self.notifications
+                .error("No translation command, set one in the translation section");
+            return;
+        };
+        let tab = self.tab();
+        let Some(Content {
+            mime,
+            body: Body::String(body),
+            ..
+        }) = &tab.content
+        else {
+            self.notifications
+                .error("Only text pages can be translated");
+            return;
+        };
+        let (mime, body) = (mime.clone(), body.clone());
+        // The translation keeps the URL of the original, so its links still work
+        let url = tab.nav.current();
+        let mut translated = Tab::new(url.clone());
+        translated.loading = true;
+        let id = translated.id;
+        self.current_tab += 1;
+        self.tabs.insert(self.current_tab, translated);
+        let sender = self.translated_sender.clone();
+        thread::spawn(move || {
+            let text = if mime.starts_with("text/gemini") {
+                translate::translate_gemtext(&command, &body)
+            } else {
+                translate::translate_text(&command, &body)
+            };
+            let content = text.map(|text| Content::text(mime, text, url));
+            let _ = sender.send((id, content));
+        });
+    }
+
+    /// Starts reading the current page aloud with the configured command.
+    fn read_aloud(&mut self) {
+        let Some(command) = self.speech.command.clone() else {
+            self.notifications
+                .error("No speech command, set one in the speech section");
+            return;
+        };
+        let Some(Content {
+            mime,
+            body: Body::String(body),
+            document,
+        }) = &self.tab().content
+        else {
+            self.notifications
+                .error("Only text pages can be read aloud");
+            return;
+        };
+        let paragraphs = match document {
+            Some(document) if mime.starts_with("text/gemini") => {
+                speech::gemtext_paragraphs(document)
+            }
+            _ => speech::text_paragraphs(body),
+        };
+        self.reader = Some(Reader::start(command, paragraphs));
+    }
+
+    /// Opens a tab with what changed in the current page since the previous visit it differed.
+    fn show_changes(&mut self) {
+        let tab = self.tab();
+        let url = tab.nav.current();
+        let Some(Content {
+            body: Body::String(body),
+            ..
+        }) = &tab.content
+        else {
+            self.notifications.error("Only text pages can be compared");
+            return;
+        };
+        let previous = match self.cache.previous(&url) {
+            Ok(Some(previous)) => previous,
+            Ok(None) => {
+                self.notifications
+                    .info("This page did not change since it was first visited");
+                return;
+            }
+            Err(err) => {
+                self.notifications
+                    .error(format!("Error reading the cache: {err}"));
+                return;
+            }
+        };
+        match diff::diff(&self.diff, &previous.body, body) {
+            Ok(changes) => {
+                let text = format!(
+                    "Changes since the version fetched {}\n\n{changes}",
+                    format_age(previous.fetched)
+                );
+                let mut tab = Tab::new(url.clone());
+                tab.content = Some(Content::text("text/plain".into(), text, url));
+                self.current_tab += 1;
+                self.tabs.insert(self.current_tab, tab);
+            }
+            Err(err) => self.notifications.error(format!("Diff failed: {err}")),
+        }
+    }
+
+    fn finish_translation(&mut self, id: u64, translation: Result<Content>) {
+        let Some(tab) = self.tabs.iter_mut().find(|tab| tab.id == id) else {
+            return;
+        };
+        tab.loading = false;
+        match translation {
+            Ok(content) => tab.content = Some(content),
+            Err(err) => self
+                .notifications
+                .error(format!("Translation failed: {err}")),
+        }
+    }
+
+    /// How links are numbered on screen, as hidden numbers show while typing one to follow
+    fn shown_link_numbers(&self) -> LinkNumbers {
+        match (self.link_numbers, &self.status) {
+            (LinkNumbers::Hidden, AppStatus::Typing(_)) => LinkNumbers::Brackets,
+            (numbers, _) => numbers,
+        }
+    }
+
+    /// The link number `n` of the current page
+    fn link(&self, n: usize) -> Option<Url> {
+        self.links().get(n).cloned()
+    }
+
+    fn links(&self) -> &[Url] {
+        match self
+            .tab()
+            .content
+            .as_ref()
+            .and_then(|c| c.document.as_ref())
+        {
+            Some(document) => &document.links,
+            None => &[],
+        }
+    }
+
+    /// Visits the link `offset` positions away in the page the current one was opened from.
+    fn visit_sibling(&mut self, offset: isize) {
+        let Some(siblings) = &self.tab().siblings else {
+            self.notifications
+                .error("This page was not opened from a link");
+            return;
+        };
+        let Some((url, siblings)) = siblings.moved(offset) else {
+            self.notifications.error(if offset > 0 {
+                "This was the last link"
+            } else {
+                "This was the first link"
+            });
+            return;
+        };
+        self.push_url(url);
+        self.tab_mut().siblings = Some(siblings);
+    }
+
+    /// Sets the title of the terminal window to the one of the current page, when it changed.
+    fn update_window_title(&mut self) -> Result<()> {
+        let tab = self.tab();
+        let url = tab.nav.current();
+        let mut title = match tab.page_title() {
+            Some(title) => title.to_string(),
+            None => url_display::decoded(url.as_str()).into_owned(),
+        };
+        if let Some(name) = self.identity_for(&url) {
+            title.push_str(&format!(" as {name}"));
+        }
+        let title = format!("{title} - taurus");
+        if title != self.window_title {
+            execute!(stdout(), SetTitle(&title))?;
+            self.window_title = title;
+        }
+        Ok(())
+    }
+
+    fn is_on_downloads_page(&self) -> bool {
+        let url = self.tab().nav.current();
+        url.scheme() == "about" && url.path() == "downloads"
+    }
+
+    fn show_downloads_page(&mut self) {
+        let url = self.tab().nav.current();
+        self.tab_mut().content = Some(Content::text(
+            "text/gemini".into(),
+            self.downloads.page(self.icons),
+            url,
+        ));
+    }
+
+    fn load_about_page(&mut self, url: Url) -> Result<()> {
+        match url.path() {
+            "downloads" => {
+                let url = self.guard_about_actions(url);
+                if url.query().is_some() {
+                    self.download_action(&url);
+                    self.tab_mut().nav.replace(
+                        Url::parse(DOWNLOADS_URL).expect("We know that this is a valid url"),
+                    );
+                }
+                self.show_downloads_page();
+            }
+            "bookmarks" => {
+                let url = self.guard_about_actions(url);
+                let mut editing = None;
+                if url.query().is_some() {
+                    editing = self.bookmark_action(&url);
+                    self.tab_mut().nav.replace(
+                        Url::parse(BOOKMARKS_URL).expect("We know that this is a valid url"),
+                    );
+                }
+                let url = self.tab().nav.current();
+                let page = self.bookmarks.page();
+                self.tab_mut().content = Some(Content::text("text/gemini".into(), page, url));
+                if let Some(command) = editing {
+                    self.status = AppStatus::Typing(command);
+                    return Ok(());
+                }
+            }
+            "identities" => {
+                let url = self.guard_about_actions(url);
+                let mut editing = None;
+                if url.query().is_some() {
+                    editing = self.identity_action(&url);
+                    self.tab_mut().nav.replace(
+                        Url::parse(IDENTITIES_URL).expect("We know that this is a valid url"),
+                    );
+                }
+                let url = self.tab().nav.current();
+                let page = self.identities.page();
+                self.tab_mut().content = Some(Content::text("text/gemini".into(), page, url));
+                if let Some(command) = editing {
+                    self.status = AppStatus::Typing(command);
+                    return Ok(());
+                }
+            }
+            "certificates" => {
+                let url = self.guard_about_actions(url);
+                let mut editing = None;
+                if let Some(query) = url.query() {
+                    match query {
+                        // The name is asked in the command line
+                        "create" => editing = Some(":create-identity taurus".to_string()),
+                        _ => self.certificate_action(&url),
+                    }
+                    self.tab_mut().nav.replace(
+                        Url::parse(CERTIFICATES_URL).expect("We know that this is a valid url"),
+                    );
+                }
+                let identity = match (self.client.identity(), &self.identity_problem) {
+                    (Some(name), _) => IdentityStatus::Loaded(name),
+                    (None, Some(problem)) => IdentityStatus::Broken(problem.clone()),
+                    (None, None) => IdentityStatus::None,
+                };
+                let page = certificates::page(
+                    &self.known_hosts.hosts(),
+                    &identity,
+                    &self.client.identified_hosts(),
+                );
+                let url = self.tab().nav.current();
+                self.tab_mut().content = Some(Content::text("text/gemini".into(), page, url));
+                if let Some(command) = editing {
+                    self.status = AppStatus::Typing(command);
+                    return Ok(());
+                }
+            }
+            "config" => {
+                let url = self.guard_about_actions(url);
+                let mut editing = None;
+                if let Some((action, key)) = url.query_pairs().next() {
+                    match (action.as_ref(), self.setting(&key)) {
+                        ("edit", Some(value)) => editing = Some(format!(":set {key} {value}")),
+                        ("save", Some(value)) => {
+                            let saved = settings::check(&key, &value).and_then(|()| {
+                                settings::save(Path::new(CONFIG_FILE), &key, &value)
+                            });
+                            match saved {
+                                Ok(()) => {
+                                    // Reloading the file would undo the settings not saved
+                                    self.config_modified = config_modified();
+                                    self.notifications.info(format!("Saved {key} = {value}"));
+                                }
+                                Err(err) => self
+                                    .notifications
+                                    .error(format!("Could not save {key}: {err}")),
+                            }
+                        }
+                        (_, None) => self.notifications.error(format!("Unknown setting {key}")),
+                        (action, _) => self
+                            .notifications
+                            .error(format!("Unknown settings action {action}")),
+                    }
+                    self.tab_mut().nav.replace(
+                        Url::parse(SETTINGS_URL).expect("We know that this is a valid url"),
+                    );
+                }
+                let values: Vec<_> = SETTINGS
+                    .iter()
+                    .filter_map(|key| Some((*key, self.setting(key)?)))
+                    .collect();
+                let url = self.tab().nav.current();
+                self.tab_mut().content = Some(Content::text(
+                    "text/gemini".into(),
+                    settings::page(&values),
+                    url,
+                ));
+                if let Some(command) = editing {
+                    self.status = AppStatus::Typing(command);
+                    return Ok(());
+                }
+            }
+            "diagnostics" => {
+                self.tab_mut().content = Some(Content::text(
+                    "text/gemini".into(),
+                    diagnostics::page(
+                        CONFIG_FILE,
+                        &[&self.config_problems[..], self.identity_problem.as_slice()].concat(),
+                    ),
+                    url,
+                ));
+            }
+            "blank" => {
+                self.tab_mut().content =
+                    Some(Content::text("text/gemini".into(), String::new(), url));
+            }
+            "trail" => {
+                self.tab_mut().content = Some(Content::text(
+                    "text/gemini".into(),
+                    self.trail.gemtext(),
+                    url,
+                ));
+            }
+            page => bail!("Unknown page about:{page}"),
+        }
+        self.status = AppStatus::Browsing;
+        Ok(())
+    }
+
+    /// `url` without its actions unless it was typed or followed from a page of taurus, as any
+    /// capsule can link to them
+    fn guard_about_actions(&mut self, mut url: Url) -> Url {
+        let trusted = self.trusted_about_url.take();
+        if url.query().is_none() || trusted.as_ref() == Some(&url) {
+            return url;
+        }
+        self.notifications.error(format!(
+            "Ignored the actions of {url}, they only run from the pages of taurus or when typed"
+        ));
+        url.set_query(None);
+        self.tab_mut().nav.replace(url.clone());
+        url
+    }
+
+    fn download_action(&mut self, url: &Url) {
+        for (action, index) in url.query_pairs() {
+            let Some(download) = index.parse::<usize>().ok().and_then(|index| {
+                self.downloads
+                    .get(index)
+                    .map(|download| (index, download.clone()))
+            }) else {
+                self.notifications.error(format!("No download {index}"));
+                continue;
+            };
+            let (index, download) = download;
+            let result = match action.as_ref() {
+                "open" => self.downloads.open(&download),
+                "folder" => downloads::open_external(download.folder()),
+                "copy" => {
+                    let path = download.saved_path();
+                    clipboard::copy(&path.to_string_lossy()).map(|_| {
+                        self.notifications
+                            .info(format!("Copied {}", path.display()))
+                    })
+                }
+                "checksum" => match download.state() {
+                    DownloadState::Finished { sha256, .. } => clipboard::copy(&sha256)
+                        .map(|_| self.notifications.info(format!("Copied {sha256}"))),
+                    _ => Err(anyhow!("Not downloaded yet")),
+                },
+                "retry" => self.downloads.retry(index, self.client.clone()),
+                "cancel" => {
+                    download.cancel();
+                    Ok(())
+                }
+                action => Err(anyhow!("Unknown download action {action}")),
+            };
+            if let Err(err) = result {
+                self.notifications
+                    .error(format!("{}: {err}", download.file_name()));
+            }
+        }
+    }
+
+    /// Bookmarks the current page under its title, or its URL when it has none
+    fn bookmark_current_page(&mut self) {
+        let url = self.tab().nav.current();
+        if url.scheme() == "about" {
+            self.notifications
+                .error("Pages of taurus itself cannot be bookmarked");
+            return;
+        }
+        let name = match self.tab().page_title() {
+            Some(title) => title.to_string(),
+            None => url_display::decoded(url.as_str()).into_owned(),
+        };
+        match self.bookmarks.add(name.clone(), url) {
+            Ok(true) => self.notifications.info(format!("Bookmarked {name}")),
+            Ok(false) => self
+                .notifications
+                .info(format!("Already bookmarked, renamed {name}")),
+            Err(err) => self
+                .notifications
+                .error(format!("Could not save the bookmark: {err}")),
+        }
+    }
+
+    /// Runs the actions of an about:bookmarks URL. Returns the command to type when one asks
+    /// for a name.
+    fn bookmark_action(&mut self, url: &Url) -> Option<String> {
+        let mut editing = None;
+        for (action, index) in url.query_pairs() {
+            // The file is asked in the command line
+            match action.as_ref() {
+                "export" => {
+                    editing = Some(":export-bookmarks bookmarks.opml".to_string());
+                    continue;
+                }
+                "import" => {
+                    editing = Some(":import-bookmarks ".to_string());
+                    continue;
+                }
+                _ => {}
+            }
+            let Some((index, bookmark)) = index.parse::<usize>().ok().and_then(|index| {
+                self.bookmarks
+                    .get(index)
+                    .map(|bookmark| (index, bookmark.clone()))
+            }) else {
+                self.notifications.error(format!("No bookmark {index}"));
+                continue;
+            };
+            match action.as_ref() {
+                // The name is asked in the command line
+                "rename" => editing = Some(format!(":rename-bookmark {index} {}", bookmark.name)),
+                "delete" => match self.bookmarks.remove(index) {
+                    Ok(_) => self
+                        .notifications
+                        .info(format!("Deleted the bookmark {}", bookmark.name)),
+                    Err(err) => self
+                        .notifications
+                        .error(format!("Could not delete {}: {err}", bookmark.name)),
+                },
+                action => self
+                    .notifications
+                    .error(format!("Unknown bookmark action {action}")),
+            }
+        }
+        editing
+    }
+
+    /// Writes the bookmarks to the path typed, in OPML for `.opml` and `.xml` files and as
+    /// gemtext links otherwise, so other clients and feed aggregators can read them
+    fn export_bookmarks(&mut self, file: &str) -> Result<String> {
+        let path = self.downloads.target(file);
+        let bookmarks = self.bookmarks.list();
+        let text = feed_list::write(feed_list::Format::of(&path), &bookmarks);
+        fs::write(&path, text)?;
+        Ok(format!(
+            "Exported {} bookmarks to {}",
+            bookmarks.len(),
+            path.display()
+        ))
+    }
+
+    /// Bookmarks the pages listed in the file typed, read as OPML or as gemtext links like it is
+    /// exported
+    fn import_bookmarks(&mut self, file: &str) -> Result<String> {
+        let path = self.downloads.target(file);
+        let text = fs::read_to_string(&path)?;
+        let pages = feed_list::read(feed_list::Format::of(&path), &text);
+        if pages.is_empty() {
+            bail!("No pages found in {}", path.display());
+        }
+        let count = pages.len();
+        let added = self.bookmarks.import(pages)?;
+        Ok(format!(
+            "Imported {added} new bookmarks of the {count} pages of {}",
+            path.display()
+        ))
+    }
+
+    /// Runs the actions of an about:identities URL. Returns the command to type when one asks
+    /// for a name.
+    fn identity_action(&mut self, url: &Url) -> Option<String> {
+        let mut editing = None;
+        for (action, value) in url.query_pairs() {
+            match action.as_ref() {
+                // The name is asked in the command line
+                "create" => editing = Some(":new-identity ".to_string()),
+                "forget" => match self.identities.use_for(None, &value) {
+                    Ok(()) => {
+                        self.apply_identities();
+                        self.notifications
+                            .info(format!("No identity is sent to {value} anymore"));
+                    }
+                    Err(err) => self.notifications.error(format!("{value}: {err}")),
+                },
+                // Its files cannot be brought back
+                "delete" => self.confirmations.push_back(Confirmation {
+                    prompt: format!("Delete the identity {value} with its certificate and key?"),
+                    action: ConfirmAction::DeleteIdentity(value.into_owned()),
+                }),
+                action => self
+                    .notifications
+                    .error(format!("Unknown identity action {action}")),
+            }
+        }
+        editing
+    }
+
+    /// Gives the client the identities to send to their pages, telling which cannot be loaded
+    fn apply_identities(&mut self) {
+        let (scoped, problems) = self.identities.loaded(self.passphrase.as_ref());
+        self.client.set_scoped_identities(scoped);
+        for problem in problems {
+            self.notifications
+                .error(format!("Identity {problem}, it is not sent"));
+        }
+    }
+
+    /// Sends the identity named `name`, or none, to the pages starting with `prefix` and loads
+    /// the current page again with it.
+    fn use_identity(&mut self, name: Option<&str>, prefix: &str) {
+        match self.identities.use_for(name, prefix) {
+            Ok(()) => {
+                self.apply_identities();
+                match name {
+                    Some(name) => self
+                        .notifications
+                        .info(format!("Sending {name} to {prefix}")),
+                    None => self
+                        .notifications
+                        .info(format!("No identity is sent to {prefix}")),
+                }
+                self.set_status_to_loading();
+            }
+            Err(err) => self
+                .notifications
+                .error(format!("Could not change the identity: {err}")),
+        }
+    }
+
+    fn certificate_action(&mut self, url: &Url) {
+        for (action, host) in url.query_pairs() {
+            let Some(known) = self.known_hosts.get(&host) else {
+                self.notifications
+                    .error(format!("No certificate known for {host}"));
+                continue;
+            };
+            match action.as_ref() {
+                "pin" | "unpin" => {
+                    let policy = match action.as_ref() {
+                        "pin" => TrustPolicy::Pinned,
+                        _ => TrustPolicy::Tofu,
+                    };
+                    self.known_hosts.set_policy(&host, policy);
+                    self.notifications.info(format!("{host}: {policy}"));
+                }
+                "copy" => match clipboard::copy(&known.fingerprint) {
+                    Ok(()) => self
+                        .notifications
+                        .info(format!("Copied the fingerprint of {host}")),
+                    Err(err) => self.notifications.error(format!("Copy failed: {err}")),
+                },
+                "forget" => {
+                    self.known_hosts.forget(&host);
+                    self.notifications
+                        .info(format!("Forgot the certificate of {host}"));
+                }
+                action => self
+                    .notifications
+                    .error(format!("Unknown certificate action {action}")),
+            }
+        }
+    }
+
+    /// Creates a client certificate named `name` and uses it right away, in place of the one of
+    /// the configuration if there was one. Otherwise the configuration is changed to use it.
+    fn create_identity(&mut self, name: &str) -> Result<()> {
+        if name.is_empty() {
+            bail!("The certificate needs a name");
+        }
+        let configured = self.identity_files.is_some();
+        let files = self.identity_files.clone().unwrap_or_default();
+        let identity = Identity::create(name, &files, self.passphrase.as_ref())?;
+        if !configured {
+            let path = Path::new(CONFIG_FILE);
+            for (key, file) in [
+                ("cert_file", &files.cert_file),
+                ("key_file", &files.key_file),
+            ] {
+                settings::save(path, key, &toml::Value::String(file.clone()))?;
+            }
+            self.config_modified = config_modified();
+            self.identity_files = Some(files.clone());
+        }
+        self.client.set_identity(identity);
+        self.identity_problem = None;
+        self.notifications.info(format!(
+            "Created {} and {} for {name}",
+            files.cert_file, files.key_file
+        ));
+        Ok(())
+    }
+
+    /// Saves the page to the path typed, asking first when a file is already there
+    fn save_content(&mut self, text: &str) {
+        let path = self.downloads.target(text);
+        if path.exists() {
+            self.status = AppStatus::Confirm(Confirmation {
+                prompt: format!("Replace {}?", path.display()),
+                action: ConfirmAction::ReplaceFile(path),
+            });
+            return;
+        }
+        self.write_content(path);
+    }
+
+    fn write_content(&mut self, path: PathBuf) {
+        let tab = &self.tabs[self.current_tab];
+        let Some(content) = &tab.content else {
+            return;
+        };
+        let url = tab.nav.current();
+        let shown = path.display().to_string();
+        match self.downloads.save_content(url, path, content) {
+            Ok(download) => {
+                self.notifications.info(format!("Saved to {shown}"));
+                self.auto_open(download);
+            }
+            Err(err) => self
+                .notifications
+                .error(format!("Could not save to {shown}: {err}")),
+        }
+    }
+
+    /// Opens a finished download if its type is configured to, asking first the first time.
+    fn auto_open(&mut self, download: Arc<Download>) {
+        let DownloadState::Finished { mime, .. } = download.state() else {
+            return;
+        };
+        let Some((pattern, handler)) = self.downloads.handler(&mime) else {
+            return;
+        };
+        if !handler.auto_open {
+            return;
+        }
+        if self.downloads.is_confirmed(pattern) {
+            if let Err(err) = self.downloads.open(&download) {
+                self.notifications
+                    .error(format!("Could not open {}: {err}", download.file_name()));
+            }
+            return;
+        }
+        let with = handler
+            .command
+            .as_deref()
+            .unwrap_or("the default application");
+        self.confirmations.push_back(Confirmation {
+            prompt: format!(
+                "Open {} with {with}? {pattern} files will be opened automatically from now on",
+                download.file_name()
+            ),
+            action: ConfirmAction::OpenDownload {
+                download,
+                pattern: pattern.to_string(),
+            },
+        });
+    }
+
+    fn confirmed(&mut self, action: ConfirmAction) {
+        match action {
+            ConfirmAction::OpenDownload { download, pattern } => {
+                self.downloads.confirm(pattern);
+                if let Err(err) = self.downloads.open(&download) {
+                    self.notifications
+                        .error(format!("Could not open {}: {err}", download.file_name()));
+                }
+            }
+            ConfirmAction::TrustCertificate(mismatch) => {
+                self.known_hosts.trust(&mismatch.host, mismatch.fingerprint);
+                self.set_status_to_loading();
+            }
+            ConfirmAction::SendInput(url) => self.send_input(url),
+            ConfirmAction::SendMessage(url) => {
+                self.client.confirm_misfin(&url);
+                self.send_input(url);
+            }
+            ConfirmAction::ReplaceFile(path) => self.write_content(path),
+            ConfirmAction::OpenExternal(url) => {
+                if let Err(err) = self.external.open(&url) {
+                    self.notifications
+                        .error(format!("Could not open the {} link: {err}", url.scheme()));
+                }
+            }
+            ConfirmAction::DeleteIdentity(name) => match self.identities.delete(&name) {
+                Ok(()) => {
+                    self.apply_identities();
+                    self.notifications
+                        .info(format!("Deleted the identity {name}"));
+                    if self.tab().nav.current().as_str() == IDENTITIES_URL {
+                        self.set_status_to_loading();
+                    }
+                }
+                Err(err) => self
+                    .notifications
+                    .error(format!("Could not delete {name}: {err}")),
+            },
+        }
+    }
+
+    /// A warning about sending input to a host whose certificate was only just accepted, as
+    /// inputs are often passwords.
+    fn input_warning(&self, url: &Url) -> Option<String> {
+        let host = url.host_str()?;
+        let accepted = self.known_hosts.get(host)?.accepted_at()?;
+        if accepted.elapsed().unwrap_or_default() >= RECENT_CERTIFICATE {
+            return None;
+        }
+        Some(format!(
+            "The certificate of {host} was accepted {}. Send the input anyway?",
+            format_age(accepted)
+        ))
+    }
+
+    /// The command line for the prompt of the current page, with the last answer to it filled in
+    fn prompt_status(&mut self) -> AppStatus {
+        let url = self.tab().nav.current();
+        let identity = self.identity_for(&url);
+        match self.inputs.get(identity.as_deref(), &url) {
+            Some(answer) => {
+                let answer = answer.to_string();
+                self.notifications
+                    .info("Filled in the last answer, Esc clears it");
+                AppStatus::Input(answer)
+            }
+            None => AppStatus::Input(String::new()),
+        }
+    }
+
+    fn remember_input(&mut self, answer: &str) {
+        if !self.inputs.enabled() || answer.is_empty() {
+            return;
+        }
+        let url = self.tab().nav.current();
+        let identity = self.identity_for(&url);
+        if let Err(err) = self.inputs.set(identity.as_deref(), &url, answer) {
+            self.notifications
+                .error(format!("Could not remember the answer: {err}"));
+        }
+    }
+
+    /// Requests `url`, which answers the prompt of the current page in place of it.
+    fn send_input(&mut self, url: Url) {
+        self.tab_mut().nav.back();
+        self.push_url(url);
+    }
+
+    /// Runs a command typed after `:` in the command line, leaving it open with the error when
+    /// it fails.
+    fn run_command(&mut self, command: &str) {
+        let (name, arguments) = command.split_once(' ').unwrap_or((command, ""));
+        match name {
+            "reload-config" => {
+                self.status = AppStatus::Browsing;
+                self.reload_config();
+            }
+            "create-identity" => match self.create_identity(arguments.trim()) {
+                Ok(()) => {
+                    self.status = AppStatus::Browsing;
+                    if self.tab().nav.current().as_str() == CERTIFICATES_URL {
+                        self.set_status_to_loading();
+                    }
+                }
+                Err(err) => self.command_error = Some(err.to_string()),
+            },
+            "new-identity" => {
+                let name = arguments.trim();
+                match self.identities.create(name, self.passphrase.as_ref()) {
+                    Ok(()) => {
+                        self.status = AppStatus::Browsing;
+                        let url = self.tab().nav.current();
+                        if url.scheme() == "about" {
+                            self.notifications
+                                .info(format!("Created the identity {name}"));
+                            if url.as_str() == IDENTITIES_URL {
+                                self.set_status_to_loading();
+                            }
+                        } else {
+                            // Created for the page it was typed on
+                            self.use_identity(Some(name), &identities::prefix(&url));
+                        }
+                    }
+                    Err(err) => self.command_error = Some(err.to_string()),
+                }
+            }
+            "rename-bookmark" => {
+                let (index, name) = arguments.trim().split_once(' ').unwrap_or((arguments, ""));
+                let result = match (index.parse(), name.trim()) {
+                    (Err(_), _) => Err(anyhow!("Which bookmark? :rename-bookmark NUMBER NAME")),
+                    (_, "") => Err(anyhow!("The bookmark needs a name")),
+                    (Ok(index), name) => self.bookmarks.rename(index, name.to_string()),
+                };
+                match result {
+                    Ok(()) => {
+                        self.status = AppStatus::Browsing;
+                        if self.tab().nav.current().as_str() == BOOKMARKS_URL {
+                            self.set_status_to_loading();
+                        }
+                    }
+                    Err(err) => self.command_error = Some(err.to_string()),
+                }
+            }
+            "export-bookmarks" | "import-bookmarks" => {
+                let file = arguments.trim();
+                let result = match (name, file) {
+                    (_, "") => Err(anyhow!("Which file? :{name} FILE")),
+                    ("export-bookmarks", file) => self.export_bookmarks(file),
+                    (_, file) => self.import_bookmarks(file),
+                };
+                match result {
+                    Ok(done) => {
+                        self.status = AppStatus::Browsing;
+                        self.notifications.info(done);
+                        if self.tab().nav.current().as_str() == BOOKMARKS_URL {
+                            self.set_status_to_loading();
+                        }
+                    }
+                    Err(err) => self.command_error = Some(err.to_string()),
+                }
+            }
+            "set" => {
+                let (key, value) = arguments.trim().split_once(' ').unwrap_or((arguments, ""));
+                match self.set_setting(key, settings::parse_value(value.trim())) {
+                    Ok(()) => {
+                        self.status = AppStatus::Browsing;
+                        // Shows the new value
+                        if self.tab().nav.current().as_str() == SETTINGS_URL {
+                            self.set_status_to_loading();
+                        }
+                    }
+                    Err(err) => self.command_error = Some(format!("{key}: {err}")),
+                }
+            }
+            name => self.command_error = Some(format!("Unknown command {name}")),
+        }
+    }
+
+    /// Applies the settings that can change without restarting. Those of the client, the tabs
+    /// and the files taurus keeps are only read at startup.
+    fn reload_config(&mut self) {
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(err) => {
+                self.notifications
+                    .error(format!("Could not reload {CONFIG_FILE}: {err}"));
+                return;
+            }
+        };
+        self.theme = Theme::new(config.theme);
+        self.tick_rate = Duration::from_millis(config.ui.tick_rate_ms);
+        self.frame_budget = Duration::from_millis(config.ui.frame_budget_ms);
+        self.status_segments = config.ui.status_segments;
+        self.mark_duplicate_links = config.ui.mark_duplicate_links;
+        self.link_numbers = config.ui.link_numbers;
+        self.break_long_words = config.ui.break_long_words;
+        self.list_continuations = config.ui.list_continuations;
+        self.smooth_scrolling = config.ui.smooth_scrolling;
+        self.line_numbers = config.ui.line_numbers;
+        self.icons = config.ui.icons;
+        self.auto_scroll_interval = Duration::from_millis(config.ui.auto_scroll_ms);
+        self.home_url = config.tabs.home_url;
+        self.new_tab = config.tabs.new_tab;
+        self.search_url = config.search_url;
+        self.translation = config.translation;
+        self.speech = config.speech;
+        self.diff = config.diff;
+        self.config_problems = config.problems;
+        match self.config_problems_left_out() {
+            Some(left_out) => self.notifications.error(format!(
+                "Reloaded {CONFIG_FILE} without {left_out}, see {DIAGNOSTICS_URL}"
+            )),
+            None => self.notifications.info(format!("Reloaded {CONFIG_FILE}")),
+        }
+    }
+
+    fn notify_config_problems(&mut self) {
+        if let Some(left_out) = self.config_problems_left_out() {
+            self.notifications.error(format!(
+                "{CONFIG_FILE}: {left_out} left out, see {DIAGNOSTICS_URL}"
+            ));
+        }
+    }
+
+    fn config_problems_left_out(&self) -> Option<String> {
+        match self.config_problems.len() {
+            0 => None,
+            1 => Some("1 setting".to_string()),
+            count => Some(format!("{count} settings")),
+        }
+    }
+
+    /// Current value of one of the settings that can change while taurus runs
+    fn setting(&self, key: &str) -> Option<toml::Value> {
+        let options = self.client.options();
+        let millis = |duration: Duration| toml::Value::Integer(duration.as_millis() as i64);
+        Some(match key {
+            "search_url" => toml::Value::String(self.search_url.to_string()),
+            "tabs.home_url" => toml::Value::String(self.home_url.to_string()),
+            "ui.tick_rate_ms" => millis(self.tick_rate),
+            "ui.frame_budget_ms" => millis(self.frame_budget),
+            "ui.link_numbers" => toml::Value::try_from(self.link_numbers).ok()?,
+            "ui.mark_duplicate_links" => toml::Value::Boolean(self.mark_duplicate_links),
+            "ui.break_long_words" => toml::Value::Boolean(self.break_long_words),
+            "ui.list_continuations" => toml::Value::Boolean(self.list_continuations),
+            "ui.smooth_scrolling" => toml::Value::Boolean(self.smooth_scrolling),
+            "ui.line_numbers" => toml::Value::Boolean(self.line_numbers),
+            "ui.icons" => toml::Value::try_from(self.icons).ok()?,
+            "ui.auto_scroll_ms" => millis(self.auto_scroll_interval),
+            "client.follow_redirects" => toml::Value::Boolean(self.client.follows_redirects()),
+            "client.min_request_interval_ms" => {
+                toml::Value::Integer(options.min_request_interval_ms as i64)
+            }
+            "client.header_timeout_ms" => toml::Value::Integer(options.header_timeout_ms as i64),
+            "client.body_timeout_ms" => toml::Value::Integer(options.body_timeout_ms as i64),
+            "client.max_body_bytes" => toml::Value::Integer(options.max_body_bytes as i64),
+            _ => return None,
+        })
+    }
+
+    fn set_setting(&mut self, key: &str, value: toml::Value) -> Result<()> {
+        settings::check(key, &value)?;
+        match key {
+            "search_url" => self.search_url = value.try_into()?,
+            "tabs.home_url" => self.home_url = value.try_into()?,
+            "ui.tick_rate_ms" => self.tick_rate = Duration::from_millis(value.try_into()?),
+            "ui.frame_budget_ms" => self.frame_budget = Duration::from_millis(value.try_into()?),
+            "ui.link_numbers" => self.link_numbers = value.try_into()?,
+            "ui.mark_duplicate_links" => self.mark_duplicate_links = value.try_into()?,
+            "ui.break_long_words" => self.break_long_words = value.try_into()?,
+            "ui.list_continuations" => self.list_continuations = value.try_into()?,
+            "ui.smooth_scrolling" => self.smooth_scrolling = value.try_into()?,
+            "ui.line_numbers" => self.line_numbers = value.try_into()?,
+            "ui.icons" => self.icons = value.try_into()?,
+            "ui.auto_scroll_ms" => {
+                self.auto_scroll_interval = Duration::from_millis(value.try_into()?);
+            }
+            "client.follow_redirects" => self.client.set_follow_redirects(value.try_into()?),
+            "client.min_request_interval_ms" => {
+                let interval = value.try_into()?;
+                self.client
+                    .update_options(|options| options.min_request_interval_ms = interval);
+            }
+            "client.header_timeout_ms" => {
+                let timeout = value.try_into()?;
+                self.client
+                    .update_options(|options| options.header_timeout_ms = timeout);
+            }
+            "client.body_timeout_ms" => {
+                let timeout = value.try_into()?;
+                self.client
+                    .update_options(|options| options.body_timeout_ms = timeout);
+            }
+            "client.max_body_bytes" => {
+                let max = value.try_into()?;
+                self.client
+                    .update_options(|options| options.max_body_bytes = max);
+            }
+            _ => bail!("Unknown setting"),
+        }
+        Ok(())
+    }
+
+    /// Cycles how certificate changes of the current host are handled.
+    fn cycle_trust_policy(&mut self) {
+        let url = self.tab().nav.current();
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        let Some(known) = self.known_hosts.get(host) else {
+            self.notifications
+                .error(format!("No certificate known for {host} yet"));
+            return;
+        };
+        let policy = known.policy.next();
+        self.known_hosts.set_policy(host, policy);
+        self.notifications.info(format!("{host}: {policy}"));
+    }
+
+    fn load_site(&mut self) -> Result<()> {
+        let url = self.tab().nav.current();
+        if url.scheme() == "about" {
+            return self.load_about_page(url);
+        }
+        let (schemes, id) = (self.schemes.clone(), self.tab().id);
+        self.page_load = Some(PageLoad::start(schemes, id, url, self.frame_budget));
+        Ok(())
+    }
+
+    /// Shows what arrived of the page being loaded. Returns whether anything did.
+    fn poll_page_load(&mut self) -> bool {
+        let Some(load) = &self.page_load else {
+            return false;
+        };
+        let (id, events) = (load.tab, load.events());
+        let changed = !events.is_empty();
+        for event in events {
+            match event {
+                LoadEvent::Progress { received, preview } => {
+                    self.received = received;
+                    let Some((mime, text)) = preview else {
+                        continue;
+                    };
+                    if let Some(tab) = self.tabs.iter_mut().find(|tab| tab.id == id) {
+                        let url = tab.nav.current();
+                        tab.content = Some(Content::text(mime, text, url));
+                    }
+                }
+                LoadEvent::Done(response) => {
+                    self.page_load = None;
+                    // The tab loaded may not be the current one anymore
+                    if let (Err(err), Some(url)) = (&response, self.tab_url(id)) {
+                        tracing::error!("Error requesting {}: {err}", logging::url(&url));
+                    }
+                    self.finish_load(id, response);
+                }
+            }
+        }
+        changed
+    }
+
+    /// The page of the tab `id`, if it is still open
+    fn tab_url(&self, id: u64) -> Option<Url> {
+        self.tabs
+            .iter()
+            .find(|tab| tab.id == id)
+            .map(|tab| tab.nav.current())
+    }
+
+    /// Stops loading the page of the current tab. A copy of it is shown if one was cached.
+    fn cancel_load(&mut self) {
+        let Some(load) = self.page_load.take() else {
+            return;
+        };
+        load.cancel();
+        if let Some(url) = self.tab_url(load.tab) {
+            tracing::debug!("Cancelled loading {}", logging::url(&url));
+        }
+        self.finish_load(load.tab, Err(anyhow!("Loading cancelled")));
+    }
+
+    /// Shows the response in its tab, which may not be the current one when it was loaded in the
+    /// background.
+    fn finish_load(&mut self, id: u64, response: Result<GeminiResponse>) {
+        let is_current = self.tab().id == id;
+        let width = self.area.width.saturating_sub(2) as usize;
+        let Some(tab) = self.tabs.iter_mut().find(|tab| tab.id == id) else {
+            return;
+        };
+        tab.loading = false;
+        tab.cached = None;
+        let url = tab.nav.current();
+        // The capsule asks for an identity, chosen to load the page again with it
+        let identity_menu = (is_current
+            && !self.identities.is_empty()
+            && matches!(response, Ok(GeminiResponse::ClientCertificateError { .. })))
+        .then(|| IdentityMenu::new(&url));
+        // The message of a link, to be read and confirmed before it is sent
+        let draft = match url.query() {
+            Some(query) if url.scheme() == "misfin" => {
+                Some(percent_decode_str(query).decode_utf8_lossy().into_owned())
+            }
+            _ => None,
+        };
+        if draft.is_some() {
+            // Going back or restoring the session must not send the message again
+            let mut compose = url.clone();
+            compose.set_query(None);
+            tab.nav.replace(compose);
+        }
+        let result = response.and_then(|response| match response {
+            GeminiResponse::Success { mime, body, .. } => {
+                let content = Content::from_mime_and_body(mime, body, url.clone())?;
+                let info = self.client.response_info(&url);
+                let truncated = info.is_some_and(|info| info.possibly_truncated());
+                if truncated {
+                    self.notifications.info(format!(
+                        "{} may be cut short, the server closed the connection without TLS \
+                         close_notify",
+                        url_display::shorten(url.as_str(), 40)
+                    ));
+                }
+                // A copy that may be cut short would replace a whole one
+                if let (Body::String(body), "gemini", false) =
+                    (&content.body, url.scheme(), truncated)
+                {
+                    if let Err(err) = self.cache.store(&url, &content.mime, body) {
+                        tracing::error!("Error caching {}: {err}", logging::url(&url));
+                    }
+                }
+                tab.content = Some(content);
+                tab.unread = !is_current;
+                let title = tab.page_title().map(str::to_string);
+                self.trail
+                    .record(url.clone(), title.clone(), SystemTime::now());
+                if let Err(err) = self.history.record(url.clone(), title, SystemTime::now()) {
+                    tracing::error!(
+                        "Error recording {} in the history: {err}",
+                        logging::url(&url)
+                    );
+                }
+                tab.scroll.0 = match url.fragment() {
+                    Some(fragment) => {
+                        let gutter = tab
+                            .content
+                            .as_ref()
+                            .and_then(|content| content.document.as_ref())
+                            .filter(|_| self.line_numbers)
+                            .map_or(0, Document::gutter_width);
+                        let width = width.saturating_sub(gutter);
+                        anchor_scroll(tab, fragment, width, self.link_numbers, self.icons)
+                            .unwrap_or_default()
+                    }
+                    None => self.positions.get(&url).unwrap_or_default(),
+                };
+                Ok(())
+            }
+            GeminiResponse::Input { prompt, .. } => {
+                tab.content = Some(Content::text("text/plain".into(), prompt, url.clone()));
+                tab.input = true;
+                Ok(())
+            }
+            GeminiResponse::Redirect { url: target, .. } => {
+                let page = format!("# Redirect\n\n{url} leads to\n=> {target}\n");
+                tab.content = Some(Content::text("text/gemini".into(), page, url.clone()));
+                Ok(())
+            }
+            response => match error_page::failure(&url, &response) {
+                Some(page) => {
+                    tab.content = Some(Content::text("text/gemini".into(), page, url.clone()));
+                    Ok(())
+                }
+                None => bail!("Unsupported response {response:?}"),
+            },
+        });
+        let result = match result {
+            Err(err) if url.scheme() == "gemini" && !err.is::<CertificateMismatch>() => {
+                match self.cache.latest(&url) {
+                    Ok(Some(page)) => {
+                        self.notifications.error(format!(
+                            "Error loading {url}: {err}, showing the copy cached {}",
+                            format_age(page.fetched)
+                        ));
+                        tab.content = Some(Content::text(page.mime, page.body, url.clone()));
+                        tab.cached = Some(page.fetched);
+                        Ok(())
+                    }
+                    _ => Err(err),
+                }
+            }
+            result => result,
+        };
+        let result = match result {
+            Err(err) if err.is::<HostNotFound>() => {
+                let page = error_page::host_not_found(&url);
+                tab.content = Some(Content::text("text/gemini".into(), page, url.clone()));
+                Ok(())
+            }
+            Err(err) => match err.downcast::<NoResponse>() {
+                Ok(err) => {
+                    let page = error_page::no_response(&url, &err);
+                    tab.content = Some(Content::text("text/gemini".into(), page, url.clone()));
+                    Ok(())
+                }
+                Err(err) => Err(err),
+            },
+            result => result,
+        };
+        let input = tab.input;
+        if let Err(err) = result {
+            self.load_failed(url, err);
+        }
+        if is_current && matches!(self.status, AppStatus::Loading | AppStatus::Browsing) {
+            self.status = match identity_menu {
+                Some(menu) => AppStatus::Identities(menu),
+                None if input => match draft {
+                    Some(draft) => AppStatus::Input(draft),
+                    None => self.prompt_status(),
+                },
+                None => AppStatus::Browsing,
+            };
+        }
+    }
+
+    fn load_failed(&mut self, url: Url, err: anyhow::Error) {
+        match err.downcast::<CertificateMismatch>() {
+            Ok(mismatch) if mismatch.policy != TrustPolicy::Pinned => {
+                self.confirmations.push_back(Confirmation {
+                    prompt: format!(
+                        "{mismatch}, new fingerprint {}. Trust it?",
+                        mismatch.fingerprint
+                    ),
+                    action: ConfirmAction::TrustCertificate(mismatch),
+                });
+            }
+            Ok(mismatch) => self.notifications.error(mismatch.to_string()),
+            Err(err) => self
+                .notifications
+                .error(format!("Error loading {url}: {err}")),
+        }
+    }
+
+    fn push_url(&mut self, url: Url) {
+        // Without a mail command the address is copied, to write from any mail program
+        if url.scheme() == "mailto" && !self.external.handles(&url) {
+            let address = external::mail_address(&url);
+            match clipboard::copy(&address) {
+                Ok(()) => self.notifications.info(format!("Copied {address}")),
+                Err(err) => self
+                    .notifications
+                    .error(format!("Could not copy {address}: {err}")),
+            }
+            self.status = AppStatus::Browsing;
+            return;
+        }
+        if self.external.handles(&url) {
+            let link = url_display::shorten(url.as_str(), 40);
+            self.status = AppStatus::Confirm(Confirmation {
+                prompt: format!("Open {link} with {}?", self.external.program(&url)),
+                action: ConfirmAction::OpenExternal(url),
+            });
+            return;
+        }
+        // Links of the pages of taurus run their actions, those of capsules do not
+        if url.scheme() == "about" && self.tab().nav.current().scheme() == "about" {
+            self.trusted_about_url = Some(url.clone());
+        }
+        self.tab_mut().nav.push(url);
+        self.set_status_to_loading();
+    }
+
+    fn set_status_to_loading(&mut self) {
+        self.status = AppStatus::Loading;
+        self.command_error = None;
+        self.loading_started = Instant::now();
+        self.received = 0;
+        let tab = self.tab_mut();
+        tab.scroll = (0, 0);
+        tab.content = None;
+        tab.input = false;
+        tab.siblings = None;
+        tab.cached = None;
+    }
+}
+
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// How long ago `time` was, roughly
+fn format_age(time: SystemTime) -> String {
+    let seconds = time.elapsed().unwrap_or_default().as_secs();
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} minutes ago", seconds / 60),
+        3600..86400 => format!("{} hours ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+/// Rows to scroll for the heading a fragment points to to be at the top, with lines wrapped at
+/// `width` columns. The heading may be a bit off in a split view.
+fn anchor_scroll(
+    tab: &Tab,
+    fragment: &str,
+    width: usize,
+    numbers: LinkNumbers,
+    icons: Icons,
+) -> Option<u16> {
+    let document = tab.content.as_ref()?.document.as_ref()?;
+    let line = document.anchor_line(fragment)?;
+    let rows = document.rows_before(line, width, numbers, icons);
+    Some(rows.min(u16::MAX as usize) as u16)
+}
+
+fn config_modified() -> Option<SystemTime> {
+    fs::metadata(CONFIG_FILE)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// A rectangle in the middle of `area` taking the given percentages of its size.
+fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [_, vertical, _] = Layout::vertical([
+        Constraint::Percentage((100 - percent_y) / 2),
+        Constraint::Percentage(percent_y),
+        Constraint::Percentage((100 - percent_y) / 2),
+    ])
+    .areas(area);
+    let [_, centered, _] = Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .areas(vertical);
+    centered
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        client::{
+            BodyProgress, ClientCertificateErrorStatus, InputStatus, PermanentFailureStatus,
+            ResponseBody,
+        },
+        schemes::SchemeHandler,
+    };
+
+    use super::*;
+
+    fn path(name: &str, file: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("taurus-test-{}-{name}-{file}", std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn app(name: &str) -> App {
+        let path = |file: &str| path(name, file);
+        App::new(Config {
+            known_hosts_file: path("hosts.toml"),
+            identities_dir: path("identities"),
+            bookmarks_file: path("bookmarks.toml"),
+            history: history::HistoryConfig {
+                file: String::new(),
+                days: 0,
+            },
+            tabs: tabs::TabsConfig {
+                session_file: path("session.toml"),
+                positions_file: String::new(),
+                background_workers: 1,
+                ..tabs::TabsConfig::default()
+            },
+            cache: page_cache::CacheConfig { dir: String::new() },
+            ..Config::default()
+        })
+        .unwrap()
+    }
+
+    /// Whether the key made the app quit
+    fn press(app: &mut App, code: KeyCode) -> bool {
+        app.handle_key(KeyEvent::from(code)).unwrap().is_break()
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+        press(app, KeyCode::Enter);
+    }
+
+    fn load(app: &mut App, response: GeminiResponse) {
+        app.finish_load(app.tab().id, Ok(response));
+    }
+
+    fn load_page(app: &mut App, gemtext: &str) {
+        load(
+            app,
+            GeminiResponse::Success {
+                mime: "text/gemini".to_string(),
+                meta: "text/gemini".to_string(),
+                body: ResponseBody::Bytes(gemtext.as_bytes().to_vec()),
+            },
+        );
+    }
+
+    #[test]
+    fn pages_scrolled_in_frames() {
+        let mut app = app("smooth");
+        load_page(&mut app, &"line\n".repeat(100));
+        app.area = Rect::new(0, 0, 80, 23);
+        app.smooth_scrolling = true;
+        press(&mut app, KeyCode::PageDown);
+        press(&mut app, KeyCode::PageDown);
+        assert_eq!(0, app.tab().scroll.0);
+        let mut rows = Vec::new();
+        while app.scroll_animation.is_some() {
+            app.animate_scroll();
+            rows.push(app.tab().scroll.0);
+        }
+        assert_eq!(vec![8, 16, 24, 32, 40], rows);
+    }
+
+    #[test]
+    fn keys_ignored_while_loading() {
+        let mut app = app("loading");
+        assert!(matches!(app.status, AppStatus::Loading));
+        press(&mut app, KeyCode::Char('i'));
+        assert!(matches!(app.status, AppStatus::Loading));
+        load_page(&mut app, "# Home");
+        assert!(matches!(app.status, AppStatus::Browsing));
+        assert_eq!("Home", app.tab().label());
+    }
+
+    #[test]
+    fn typing_a_url_loads_it() {
+        let mut app = app("typing");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('i'));
+        assert!(matches!(app.status, AppStatus::Typing(_)));
+        press(&mut app, KeyCode::Char('x'));
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.status, AppStatus::Browsing));
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/page");
+        assert!(matches!(app.status, AppStatus::Loading));
+        assert_eq!("gemini://a.com/page", app.tab().nav.current().as_str());
+    }
+
+    #[test]
+    fn following_link_numbers() {
+        let mut app = app("links");
+        load_page(&mut app, "=> gemini://a.com/ A\n=> gemini://b.com/ B");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "5");
+        assert!(matches!(app.status, AppStatus::Typing(_)));
+        assert_eq!(Some("No link 5"), app.command_error.as_deref());
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "1");
+        assert!(matches!(app.status, AppStatus::Loading));
+        assert_eq!(None, app.command_error);
+        assert_eq!("gemini://b.com/", app.tab().nav.current().as_str());
+        assert_eq!(Some(1), app.tab().siblings.as_ref().map(|s| s.position));
+    }
+
+    #[test]
+    fn magnet_links_opened_after_asking() {
+        let mut app = app("magnet");
+        load_page(
+            &mut app,
+            "=> magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c Torrent",
+        );
+        let page = app.tab().nav.current();
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "0");
+        assert!(matches!(app.status, AppStatus::Confirm(_)));
+        press(&mut app, KeyCode::Char('n'));
+        assert_eq!(page, app.tab().nav.current());
+    }
+
+    #[test]
+    fn answers_too_long_kept() {
+        let mut app = app("long-input");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/post");
+        load(
+            &mut app,
+            GeminiResponse::Input {
+                status: InputStatus::Normal,
+                prompt: "Message".to_string(),
+                meta: "Message".to_string(),
+            },
+        );
+        type_text(&mut app, &"é".repeat(168));
+        assert!(matches!(app.status, AppStatus::Input(_)));
+        let error = app.command_error.clone().unwrap();
+        assert!(error.starts_with("3 bytes too long"), "{error}");
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.status, AppStatus::Loading));
+    }
+
+    #[test]
+    fn misfin_links_only_fill_in_the_message() {
+        let mut app = app("misfin");
+        load_page(&mut app, "# Home");
+        let link = Url::parse("misfin://bob@a.com?Hello%20Bob").unwrap();
+        app.push_url(link.clone());
+        load(
+            &mut app,
+            GeminiResponse::Input {
+                status: InputStatus::Normal,
+                prompt: "Message to bob@a.com".to_string(),
+                meta: "Message to bob@a.com".to_string(),
+            },
+        );
+        assert!(matches!(&app.status, AppStatus::Input(draft) if draft == "Hello Bob"));
+        assert_eq!("misfin://bob@a.com", app.tab().nav.current().as_str());
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.client.take_misfin_confirmation(&link));
+        assert!(matches!(app.status, AppStatus::Confirm(_)));
+        press(&mut app, KeyCode::Char('y'));
+        assert!(app.client.take_misfin_confirmation(&link));
+        assert_eq!(link, app.tab().nav.current());
+    }
+
+    #[test]
+    fn input_sent_as_query() {
+        let mut app = app("input");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/name");
+        load(
+            &mut app,
+            GeminiResponse::Input {
+                status: InputStatus::Normal,
+                prompt: "Your name".to_string(),
+                meta: "Your name".to_string(),
+            },
+        );
+        assert!(matches!(app.status, AppStatus::Input(_)));
+        type_text(&mut app, "a b");
+        assert!(matches!(app.status, AppStatus::Loading));
+        assert_eq!(
+            "gemini://a.com/name?a%20b",
+            app.tab().nav.current().as_str()
+        );
+        // The page asking for input is replaced by the one answering it
+        app.tab_mut().nav.back();
+        assert_eq!("gemini://tlgs.one/", app.tab().nav.current().as_str());
+    }
+
+    #[test]
+    fn answers_filled_in_again() {
+        let mut app = app("remembered");
+        let file = path("remembered", "inputs");
+        app.inputs = RememberedInputs::load(&file, None).unwrap();
+        let prompt = || GeminiResponse::Input {
+            status: InputStatus::Sensitive,
+            prompt: "Token".to_string(),
+            meta: "Token".to_string(),
+        };
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/play");
+        load(&mut app, prompt());
+        type_text(&mut app, "xyzzy");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/play");
+        load(&mut app, prompt());
+        std::fs::remove_file(&file).unwrap();
+        std::fs::remove_file(format!("{file}.key")).unwrap();
+        assert!(matches!(&app.status, AppStatus::Input(answer) if answer == "xyzzy"));
+    }
+
+    #[test]
+    fn input_to_new_certificate_confirmed() {
+        let mut app = app("new-certificate");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/login");
+        app.known_hosts.handshake_completed("a.com", b"certificate");
+        load(
+            &mut app,
+            GeminiResponse::Input {
+                status: InputStatus::Sensitive,
+                prompt: "Password".to_string(),
+                meta: "Password".to_string(),
+            },
+        );
+        type_text(&mut app, "secret");
+        std::fs::remove_file(path("new-certificate", "hosts.toml")).unwrap();
+        assert!(matches!(app.status, AppStatus::Confirm(_)));
+        press(&mut app, KeyCode::Char('y'));
+        assert!(matches!(app.status, AppStatus::Loading));
+        assert_eq!(
+            "gemini://a.com/login?secret",
+            app.tab().nav.current().as_str()
+        );
+    }
+
+    #[test]
+    fn certificates_not_forgotten_from_capsules() {
+        let mut app = app("forget-certificate");
+        app.known_hosts.handshake_completed("a.com", b"certificate");
+        load_page(&mut app, "=> about:certificates?forget=a.com Forget");
+        let link = app.links()[0].clone();
+        app.push_url(link);
+        app.load_site().unwrap();
+        assert!(app.known_hosts.get("a.com").is_some());
+        assert_eq!("about:certificates", app.tab().nav.current().as_str());
+        // The links of the page itself still forget it
+        app.push_url(Url::parse("about:certificates?forget=a.com").unwrap());
+        app.load_site().unwrap();
+        std::fs::remove_file(path("forget-certificate", "hosts.toml")).unwrap();
+        assert!(app.known_hosts.get("a.com").is_none());
+    }
+
+    #[test]
+    fn cached_copy_shown_when_offline() {
+        let mut app = app("offline");
+        let dir = path("offline", "cache");
+        app.cache = PageCache::new(page_cache::CacheConfig { dir: dir.clone() });
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('u'));
+        assert!(matches!(app.status, AppStatus::Loading));
+        app.finish_load(app.tab().id, Err(anyhow!("Connection refused")));
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(matches!(app.status, AppStatus::Browsing));
+        assert!(app.tab().cached.is_some());
+        assert_eq!(Some("Home"), app.tab().page_title());
+        press(&mut app, KeyCode::Char('u'));
+        assert!(app.tab().cached.is_none());
+    }
+
+    #[test]
+    fn page_for_unknown_hosts() {
+        let mut app = app("not-found");
+        let err = HostNotFound("tlgs.one".to_string());
+        app.finish_load(app.tab().id, Err(err.into()));
+        assert!(matches!(app.status, AppStatus::Browsing));
+        assert_eq!(Some("Host not found"), app.tab().page_title());
+        press(&mut app, KeyCode::Char('u'));
+        assert!(matches!(app.status, AppStatus::Loading));
+    }
+
+    #[test]
+    fn page_for_servers_not_responding() {
+        let mut app = app("no-response");
+        let err = NoResponse { received: 0 };
+        app.finish_load(app.tab().id, Err(err.into()));
+        assert_eq!(Some("No response"), app.tab().page_title());
+        press(&mut app, KeyCode::Char('u'));
+        assert!(matches!(app.status, AppStatus::Loading));
+    }
+
+    #[test]
+    fn loads_cancelled() {
+        struct Slow;
+        impl SchemeHandler for Slow {
+            fn fetch(
+                &self,
+                _: Url,
+                _: &mut dyn FnMut(BodyProgress) -> Result<()>,
+            ) -> Result<GeminiResponse> {
+                thread::sleep(Duration::from_millis(200));
+                bail!("Too slow")
+            }
+        }
+        let mut app = app("cancel");
+        app.schemes.register("slow", Slow);
+        app.push_url(Url::parse("slow://a.com/").unwrap());
+        app.load_site().unwrap();
+        // Keys are still handled while the page loads
+        assert!(!press(&mut app, KeyCode::Esc));
+        assert!(matches!(app.status, AppStatus::Browsing));
+        assert!(app.page_load.is_none());
+        assert!(!app.tab().loading);
+    }
+
+    #[test]
+    fn bookmarks_managed() {
+        let mut app = app("bookmarks");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('b'));
+        press(&mut app, KeyCode::Char('B'));
+        app.load_site().unwrap();
+        assert_eq!(Some("Bookmarks"), app.tab().page_title());
+        app.push_url(Url::parse("about:bookmarks?rename=0").unwrap());
+        app.load_site().unwrap();
+        assert!(
+            matches!(&app.status, AppStatus::Typing(command) if command == ":rename-bookmark 0 Home")
+        );
+        for _ in 0.."Home".len() {
+            press(&mut app, KeyCode::Backspace);
+        }
+        type_text(&mut app, "Start");
+        assert_eq!("Start", app.bookmarks.get(0).unwrap().name);
+        let opml = path("bookmarks", "bookmarks.opml");
+        app.push_url(Url::parse("about:bookmarks?export").unwrap());
+        app.load_site().unwrap();
+        for _ in 0.."bookmarks.opml".len() {
+            press(&mut app, KeyCode::Backspace);
+        }
+        type_text(&mut app, &opml);
+        app.push_url(Url::parse("about:bookmarks?delete=0").unwrap());
+        app.load_site().unwrap();
+        assert!(app.bookmarks.get(0).is_none());
+        let links = path("bookmarks", "links.gmi");
+        std::fs::write(&links, "=> gemini://a.com/ A\ngemini://b.com/\n").unwrap();
+        for file in [&opml, &links] {
+            app.status = AppStatus::Typing(format!(":import-bookmarks {file}"));
+            press(&mut app, KeyCode::Enter);
+        }
+        assert_eq!("Start", app.bookmarks.get(0).unwrap().name);
+        assert_eq!("gemini://b.com/", app.bookmarks.get(2).unwrap().name);
+        // Pages already bookmarked keep their bookmark
+        app.status = AppStatus::Typing(format!(":import-bookmarks {opml}"));
+        press(&mut app, KeyCode::Enter);
+        for file in [opml, links, path("bookmarks", "bookmarks.toml")] {
+            std::fs::remove_file(file).unwrap();
+        }
+        assert!(app.bookmarks.get(3).is_none());
+        assert_eq!("about:bookmarks", app.tab().nav.current().as_str());
+    }
+
+    #[test]
+    fn about_actions_only_run_from_taurus() {
+        let mut app = app("about-actions");
+        load_page(&mut app, "=> about:bookmarks?delete=0 Delete");
+        press(&mut app, KeyCode::Char('b'));
+        // Followed from a capsule
+        let link = app.links()[0].clone();
+        app.push_url(link);
+        app.load_site().unwrap();
+        assert!(app.bookmarks.get(0).is_some());
+        assert_eq!("about:bookmarks", app.tab().nav.current().as_str());
+        app.status = AppStatus::Typing("about:bookmarks?delete=0".to_string());
+        press(&mut app, KeyCode::Enter);
+        app.load_site().unwrap();
+        std::fs::remove_file(path("about-actions", "bookmarks.toml")).unwrap();
+        assert!(app.bookmarks.get(0).is_none());
+    }
+
+    #[test]
+    fn identities_chosen_per_page() {
+        let mut app = app("identities");
+        let url = app.tab().nav.current();
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('C'));
+        press(&mut app, KeyCode::Char('n'));
+        type_text(&mut app, "alice");
+        assert_eq!(Some("alice".to_string()), app.client.identity_of(&url));
+        let other = Url::parse("gemini://a.com/").unwrap();
+        assert_eq!(None, app.client.identity_of(&other));
+        load(
+            &mut app,
+            GeminiResponse::ClientCertificateError {
+                status: ClientCertificateErrorStatus::NotAuthorized,
+                meta: String::new(),
+            },
+        );
+        assert!(matches!(app.status, AppStatus::Identities(_)));
+        // No identity, the first choice
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(None, app.client.identity_of(&url));
+        // Linked from a capsule
+        load_page(&mut app, "=> about:identities?delete=alice Delete");
+        let link = app.links()[0].clone();
+        app.push_url(link);
+        app.load_site().unwrap();
+        assert!(app.confirmations.is_empty());
+        app.push_url(Url::parse("about:identities?delete=alice").unwrap());
+        app.load_site().unwrap();
+        assert!(app.identities.get(0).is_some());
+        let confirmation = app.confirmations.pop_front().unwrap();
+        app.confirmed(confirmation.action);
+        std::fs::remove_dir_all(path("identities", "identities")).unwrap();
+        assert!(app.identities.is_empty());
+    }
+
+    #[test]
+    fn history_searched_and_opened() {
+        let mut app = app("history");
+        let home = app.tab().nav.current();
+        load_page(&mut app, "# Home");
+        app.push_url(Url::parse("gemini://a.com/other").unwrap());
+        load_page(&mut app, "# Other");
+        press(&mut app, KeyCode::Char('h'));
+        assert_eq!(2, app.history.search("").len());
+        type_text(&mut app, "home");
+        assert!(matches!(app.status, AppStatus::Loading));
+        assert_eq!(home, app.tab().nav.current());
+    }
+
+    #[test]
+    fn page_for_failures() {
+        let mut app = app("failure");
+        load(
+            &mut app,
+            GeminiResponse::PermanentFailure {
+                status: PermanentFailureStatus::NotFound,
+                meta: "No such file".to_string(),
+            },
+        );
+        assert!(matches!(app.status, AppStatus::Browsing));
+        assert_eq!(Some("51 Not found"), app.tab().page_title());
+        load(
+            &mut app,
+            GeminiResponse::ClientCertificateError {
+                status: ClientCertificateErrorStatus::Required,
+                meta: String::new(),
+            },
+        );
+        assert_eq!(Some("60 Certificate required"), app.tab().page_title());
+        press(&mut app, KeyCode::Char('u'));
+        assert!(matches!(app.status, AppStatus::Loading));
+    }
+
+    #[test]
+    fn config_reloaded_by_command() {
+        let mut app = app("reload");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, ":reload");
+        assert_eq!(Some("Unknown command reload"), app.command_error.as_deref());
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, ":reload-config");
+        assert!(matches!(app.status, AppStatus::Browsing));
+        let toast = app.notifications.toast().unwrap();
+        // The settings left out depend on the files next to the configuration
+        assert!(toast.message.starts_with("Reloaded Config.toml"));
+    }
+
+    #[test]
+    fn pinned_tabs_not_closed() {
+        let mut app = app("pinned");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('t'));
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('p'));
+        press(&mut app, KeyCode::Char('w'));
+        assert_eq!(2, app.tabs.len());
+        press(&mut app, KeyCode::Char('p'));
+        press(&mut app, KeyCode::Char('w'));
+        assert_eq!(1, app.tabs.len());
+    }
+
+    #[test]
+    fn closed_tab_reopened() {
+        let mut app = app("reopen");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/");
+        load_page(&mut app, "# Long page");
+        app.tab_mut().scroll = (12, 0);
+        press(&mut app, KeyCode::Char('t'));
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::BackTab);
+        press(&mut app, KeyCode::Char('w'));
+        assert_eq!(1, app.tabs.len());
+        press(&mut app, KeyCode::Char('U'));
+        assert_eq!(2, app.tabs.len());
+        assert!(matches!(app.status, AppStatus::Browsing));
+        assert_eq!(Some("Long page"), app.tab().page_title());
+        assert_eq!((12, 0), app.tab().scroll);
+        app.tab_mut().nav.back();
+        assert_eq!("gemini://tlgs.one/", app.tab().nav.current().as_str());
+    }
+
+    #[test]
+    fn duplicated_tab_keeps_history() {
+        let mut app = app("duplicate");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "gemini://a.com/");
+        load_page(&mut app, "# Capsule");
+        app.tab_mut().scroll = (5, 0);
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(2, app.tabs.len());
+        assert_eq!(1, app.current_tab);
+        assert_ne!(app.tabs[0].id, app.tab().id);
+        assert_eq!(Some("Capsule"), app.tab().page_title());
+        assert_eq!((5, 0), app.tab().scroll);
+        press(&mut app, KeyCode::Char('<'));
+        assert_eq!("gemini://tlgs.one/", app.tab().nav.current().as_str());
+        assert_eq!("gemini://a.com/", app.tabs[0].nav.current().as_str());
+    }
+
+    #[test]
+    fn new_tab_page() {
+        let mut app = app("new-tab");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('t'));
+        assert!(matches!(app.status, AppStatus::Typing(_)));
+        assert_eq!("about:blank", app.tab().nav.current().as_str());
+        press(&mut app, KeyCode::Esc);
+        app.new_tab = NewTabPage::Home;
+        press(&mut app, KeyCode::Char('t'));
+        assert!(matches!(app.status, AppStatus::Loading));
+        assert_eq!(3, app.tabs.len());
+        assert_eq!("gemini://tlgs.one/", app.tab().nav.current().as_str());
+    }
+
+    #[test]
+    fn switching_from_the_overview() {
+        let mut app = app("overview");
+        load_page(&mut app, "# Home");
+        for url in ["gemini://a.com/", "gemini://b.com/"] {
+            let tab = app.background_tab(Url::parse(url).unwrap());
+            app.tabs.push(tab);
+            let id = app.tabs.last().unwrap().id;
+            app.finish_load(
+                id,
+                Ok(GeminiResponse::Success {
+                    mime: "text/gemini".to_string(),
+                    meta: "text/gemini".to_string(),
+                    body: ResponseBody::Bytes(Vec::new()),
+                }),
+            );
+        }
+        assert!(app.tabs[2].unread);
+        press(&mut app, KeyCode::Char('O'));
+        for c in "b.c".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.status, AppStatus::Browsing));
+        assert_eq!(2, app.current_tab);
+        assert!(!app.tab().unread);
+        assert!(app.tabs[1].unread);
+    }
+
+    #[test]
+    fn escape_quits_once_notifications_are_closed() {
+        let mut app = app("quit");
+        load_page(&mut app, "# Home");
+        press(&mut app, KeyCode::Char('n'));
+        assert!(!press(&mut app, KeyCode::Esc));
+        assert!(press(&mut app, KeyCode::Esc));
+        std::fs::remove_file(&app.session_file).unwrap();
     }
 }
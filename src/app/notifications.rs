@@ -0,0 +1,121 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    text::Line,
+    widgets::{Block, Clear, Paragraph, Widget, Wrap},
+};
+
+use super::theme::Theme;
+
+const LOG_CAPACITY: usize = 50;
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+pub struct Notification {
+    pub level: Level,
+    pub message: String,
+    created: Instant,
+}
+
+/// Non-fatal errors and confirmations. The latest one is shown for a while as a toast, and the
+/// most recent ones are kept so they can be reviewed later.
+#[derive(Default)]
+pub struct Notifications {
+    log: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Level::Info, message.into());
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Level::Error, message.into());
+    }
+
+    fn push(&mut self, level: Level, message: String) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_back();
+        }
+        self.log.push_front(Notification {
+            level,
+            message,
+            created: Instant::now(),
+        });
+    }
+
+    /// The notification that should still be displayed as a toast
+    pub fn toast(&self) -> Option<&Notification> {
+        self.log
+            .front()
+            .filter(|notification| notification.created.elapsed() < TOAST_DURATION)
+    }
+
+    pub fn render_toast(&self, area: Rect, buf: &mut Buffer, theme: &Theme) {
+        let Some(notification) = self.toast() else {
+            return;
+        };
+        let width = (notification.message.chars().count() as u16 + 4)
+            .min(area.width / 2)
+            .max(10)
+            .min(area.width);
+        let height = 3.min(area.height);
+        let toast = Rect::new(area.right() - width, area.top(), width, height);
+        Clear.render(toast, buf);
+        let style = theme.notification(notification.level);
+        Paragraph::new(notification.message.as_str())
+            .style(style)
+            .block(Block::bordered().border_style(style))
+            .render(toast, buf);
+    }
+
+    pub fn render_log(&self, area: Rect, buf: &mut Buffer, theme: &Theme) {
+        let lines: Vec<Line> = if self.log.is_empty() {
+            vec![Line::raw("No notifications")]
+        } else {
+            self.log
+                .iter()
+                .map(|notification| {
+                    let age = notification.created.elapsed().as_secs();
+                    Line::styled(
+                        format!("{age:>5}s ago  {}", notification.message),
+                        theme.notification(notification.level),
+                    )
+                })
+                .collect()
+        };
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title_top("Notifications"))
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn newest_first_and_bounded() {
+        let mut notifications = Notifications::default();
+        for n in 0..(LOG_CAPACITY + 5) {
+            notifications.info(format!("{n}"));
+        }
+        notifications.error("failed");
+        assert_eq!(LOG_CAPACITY, notifications.log.len());
+        let toast = notifications.toast().unwrap();
+        assert_eq!(Level::Error, toast.level);
+        assert_eq!("failed", toast.message);
+    }
+}
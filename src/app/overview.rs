@@ -0,0 +1,114 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+
+use super::tabs::Tab;
+
+/// A list of all the tabs, narrowed down by what is typed, to switch to one when they do not fit
+/// in the tab bar.
+#[derive(Default)]
+pub struct TabOverview {
+    pub filter: String,
+    /// Position of the highlighted tab in the matching ones
+    pub selected: usize,
+}
+
+impl TabOverview {
+    /// Positions of the tabs whose label or URL contain the filter, ignoring case
+    pub fn matching(&self, tabs: &[Tab]) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        tabs.iter()
+            .enumerate()
+            .filter(|(_, tab)| {
+                tab.label().to_lowercase().contains(&filter)
+                    || tab.nav.current().as_str().to_lowercase().contains(&filter)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Moves the highlight by `step` tabs, staying in the matching ones.
+    pub fn move_selection(&mut self, step: isize, matching: usize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(step)
+            .min(matching.saturating_sub(1));
+    }
+
+    /// The tab to switch to
+    pub fn selected_tab(&self, tabs: &[Tab]) -> Option<usize> {
+        self.matching(tabs).get(self.selected).copied()
+    }
+
+    pub fn render(&self, tabs: &[Tab], area: Rect, buf: &mut Buffer) {
+        let matching = self.matching(tabs);
+        let lines: Vec<Line> = if matching.is_empty() {
+            vec![Line::raw("No tab matches")]
+        } else {
+            matching
+                .iter()
+                .enumerate()
+                .map(|(position, &index)| {
+                    let tab = &tabs[index];
+                    let marker = if tab.unread { "*" } else { " " };
+                    let (label, host) = (tab.label(), tab.host_label());
+                    let line = if label == host {
+                        Line::raw(format!("{marker}{:>3}: {label}", index + 1))
+                    } else {
+                        Line::raw(format!("{marker}{:>3}: {label}  {host}", index + 1))
+                    };
+                    let mut style = Style::new();
+                    if tab.unread {
+                        style = style.bold();
+                    }
+                    if position == self.selected {
+                        style = style.reversed();
+                    }
+                    line.style(style)
+                })
+                .collect()
+        };
+        // Keeps the highlighted tab visible
+        let height = area.height.saturating_sub(2) as usize;
+        let scroll = (self.selected + 1).saturating_sub(height) as u16;
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .scroll((scroll, 0))
+            .block(
+                Block::bordered()
+                    .title_top(format!("Tabs ({})", tabs.len()))
+                    .title_bottom("Type to filter, Enter to switch"),
+            )
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use url::Url;
+
+    use super::*;
+
+    #[test]
+    fn filtered_by_url() {
+        let tabs: Vec<Tab> = ["gemini://a.com/", "gemini://b.com/notes", "gemini://B.org/"]
+            .into_iter()
+            .map(|url| Tab::new(Url::parse(url).unwrap()))
+            .collect();
+        let mut overview = TabOverview {
+            filter: "B.".to_string(),
+            selected: 0,
+        };
+        assert_eq!(vec![1, 2], overview.matching(&tabs));
+        overview.move_selection(5, 2);
+        assert_eq!(Some(2), overview.selected_tab(&tabs));
+        overview.move_selection(-3, 2);
+        assert_eq!(Some(1), overview.selected_tab(&tabs));
+        overview.filter = "missing".to_string();
+        assert_eq!(None, overview.selected_tab(&tabs));
+    }
+}
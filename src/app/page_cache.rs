@@ -0,0 +1,126 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use anyhow::Result;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Where the last two versions of the visited text pages are kept, empty to keep nothing
+    pub dir: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: "cache".to_string(),
+        }
+    }
+}
+
+/// A version of a page, as it was fetched
+pub struct CachedPage {
+    pub mime: String,
+    pub body: String,
+    pub fetched: SystemTime,
+}
+
+/// Text pages kept on disk, the latest version and the one before it, to see what changed.
+pub struct PageCache {
+    dir: Option<PathBuf>,
+}
+
+impl PageCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            dir: (!config.dir.is_empty()).then(|| PathBuf::from(config.dir)),
+        }
+    }
+
+    /// Keeps `body` as the latest version of the page. The version it replaces becomes the
+    /// previous one, unless nothing changed.
+    pub fn store(&self, url: &Url, mime: &str, body: &str) -> Result<()> {
+        let Some(dir) = self.page_dir(url) else {
+            return Ok(());
+        };
+        fs::create_dir_all(&dir)?;
+        let latest = dir.join("latest");
+        if let Some(page) = read(&latest)? {
+            if page.mime != mime || page.body != body {
+                fs::rename(&latest, dir.join("previous"))?;
+            }
+        }
+        fs::write(&latest, format!("{mime}\n{body}"))?;
+        Ok(())
+    }
+
+    /// The version fetched last, shown when the page cannot be fetched again
+    pub fn latest(&self, url: &Url) -> Result<Option<CachedPage>> {
+        match self.page_dir(url) {
+            Some(dir) => read(&dir.join("latest")),
+            None => Ok(None),
+        }
+    }
+
+    /// The version before the latest one, if the page changed since it was first visited
+    pub fn previous(&self, url: &Url) -> Result<Option<CachedPage>> {
+        match self.page_dir(url) {
+            Some(dir) => read(&dir.join("previous")),
+            None => Ok(None),
+        }
+    }
+
+    fn page_dir(&self, url: &Url) -> Option<PathBuf> {
+        let mut url = url.clone();
+        url.set_fragment(None);
+        let hash: String = Sha256::digest(url.as_str())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Some(self.dir.as_ref()?.join(hash))
+    }
+}
+
+/// Versions are stored as their mime type on the first line, then the body.
+fn read(path: &PathBuf) -> Result<Option<CachedPage>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let (mime, body) = contents.split_once('\n').unwrap_or((&contents, ""));
+    Ok(Some(CachedPage {
+        mime: mime.to_string(),
+        body: body.to_string(),
+        fetched: fs::metadata(path)?.modified()?,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn previous_version_kept() {
+        let dir = std::env::temp_dir().join(format!("taurus-test-{}-cache", std::process::id()));
+        let cache = PageCache::new(CacheConfig {
+            dir: dir.to_string_lossy().into_owned(),
+        });
+        let url = Url::parse("gemini://a.com/wiki#section").unwrap();
+        cache.store(&url, "text/gemini", "first").unwrap();
+        assert!(cache.previous(&url).unwrap().is_none());
+        cache.store(&url, "text/gemini", "second").unwrap();
+        cache.store(&url, "text/gemini", "second").unwrap();
+        let other = Url::parse("gemini://a.com/wiki").unwrap();
+        let previous = cache.previous(&other).unwrap().unwrap();
+        let latest = cache.latest(&other).unwrap().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!("second", latest.body);
+        assert_eq!(
+            ("text/gemini", "first"),
+            (previous.mime.as_str(), previous.body.as_str())
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use url::Url;
+
+use crate::{client::GeminiResponse, schemes::Schemes};
+
+/// The page of the current tab, fetched on a thread of its own so the browser keeps drawing and
+/// answering keys while a slow server takes its time.
+pub struct PageLoad {
+    pub tab: u64,
+    events: Receiver<LoadEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+pub enum LoadEvent {
+    /// How much of the body arrived, with the text received so far once every `frame_budget`
+    /// while the body is kept in memory
+    Progress {
+        received: u64,
+        preview: Option<(String, String)>,
+    },
+    Done(Result<GeminiResponse>),
+}
+
+impl PageLoad {
+    pub fn start(schemes: Schemes, tab: u64, url: Url, frame_budget: Duration) -> Self {
+        let (sender, events) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let stop = cancelled.clone();
+        thread::spawn(move || {
+            let mut last_preview = Instant::now();
+            let response = schemes.fetch(url, &mut |progress| {
+                // Stops reading the body, the header cannot be interrupted
+                if stop.load(Ordering::Relaxed) {
+                    return Err(anyhow!("Loading cancelled"));
+                }
+                let preview = if progress.mime.starts_with("text/")
+                    && !progress.bytes.is_empty()
+                    && last_preview.elapsed() >= frame_budget
+                {
+                    last_preview = Instant::now();
+                    let text = String::from_utf8_lossy(progress.bytes).into_owned();
+                    Some((progress.mime.to_string(), text))
+                } else {
+                    None
+                };
+                let event = LoadEvent::Progress {
+                    received: progress.received,
+                    preview,
+                };
+                sender.send(event).map_err(|_| anyhow!("Loading cancelled"))
+            });
+            // Nobody waits for the response of a cancelled load
+            let _ = sender.send(LoadEvent::Done(response));
+        });
+        Self {
+            tab,
+            events,
+            cancelled,
+        }
+    }
+
+    /// What happened since the last call, in order
+    pub fn events(&self) -> Vec<LoadEvent> {
+        self.events.try_iter().collect()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
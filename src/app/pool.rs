@@ -0,0 +1,229 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use url::Url;
+
+use crate::client::GeminiResponse;
+
+/// Fetches pages of background tabs on a few threads. Requests to the same host wait for each
+/// other, and for a delay after the last one, so opening many tabs of a capsule does not hammer
+/// it.
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    results: Receiver<Loaded>,
+}
+
+pub struct Loaded {
+    pub tab: u64,
+    pub response: Result<GeminiResponse>,
+}
+
+struct Job {
+    tab: u64,
+    url: Url,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    /// Hosts with a request in flight
+    busy_hosts: HashSet<String>,
+    /// Hosts that were just requested, with when they can be requested again
+    resting_hosts: HashMap<String, Instant>,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+    /// Time between two requests to the same host
+    delay: Duration,
+}
+
+impl WorkerPool {
+    pub fn new(
+        workers: usize,
+        delay: Duration,
+        fetch: impl Fn(Url) -> Result<GeminiResponse> + Send + Sync + 'static,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            delay,
+            ..Shared::default()
+        });
+        let fetch = Arc::new(fetch);
+        let (sender, results) = mpsc::channel();
+        for _ in 0..workers.max(1) {
+            let shared = shared.clone();
+            let fetch = fetch.clone();
+            let sender: Sender<Loaded> = sender.clone();
+            thread::spawn(move || loop {
+                let job = shared.take();
+                let host = host_key(&job.url);
+                let response = fetch(job.url);
+                shared.release(&host);
+                let loaded = Loaded {
+                    tab: job.tab,
+                    response,
+                };
+                if sender.send(loaded).is_err() {
+                    break;
+                }
+            });
+        }
+        Self { shared, results }
+    }
+
+    pub fn load(&self, tab: u64, url: Url) {
+        let mut queue = self.shared.queue.lock().expect("Poisoned worker queue");
+        queue.jobs.push_back(Job { tab, url });
+        self.shared.changed.notify_all();
+    }
+
+    /// A page that finished loading, if any
+    pub fn try_recv(&self) -> Option<Loaded> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl Shared {
+    /// Waits for a job whose host is neither busy nor resting, marking the host as busy.
+    fn take(&self) -> Job {
+        let mut queue = self.queue.lock().expect("Poisoned worker queue");
+        loop {
+            let now = Instant::now();
+            queue.resting_hosts.retain(|_, until| *until > now);
+            let free = queue.jobs.iter().position(|job| {
+                let host = host_key(&job.url);
+                !queue.busy_hosts.contains(&host) && !queue.resting_hosts.contains_key(&host)
+            });
+            if let Some(job) = free.and_then(|position| queue.jobs.remove(position)) {
+                queue.busy_hosts.insert(host_key(&job.url));
+                return job;
+            }
+            // Wakes up when the first resting host can be requested again
+            let rested = queue.resting_hosts.values().min().copied();
+            queue = match rested {
+                Some(until) => {
+                    let timeout = until.saturating_duration_since(now);
+                    let waited = self.changed.wait_timeout(queue, timeout);
+                    waited.expect("Poisoned worker queue").0
+                }
+                None => self.changed.wait(queue).expect("Poisoned worker queue"),
+            };
+        }
+    }
+
+    fn release(&self, host: &str) {
+        let mut queue = self.queue.lock().expect("Poisoned worker queue");
+        queue.busy_hosts.remove(host);
+        if !self.delay.is_zero() {
+            let until = Instant::now() + self.delay;
+            queue.resting_hosts.insert(host.to_string(), until);
+        }
+        self.changed.notify_all();
+    }
+}
+
+fn host_key(url: &Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port().unwrap_or(1965)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::bail;
+
+    use super::*;
+
+    #[test]
+    fn one_request_per_host_at_a_time() {
+        // Requests in flight per host and the most seen at once, per host and in total
+        let in_flight = Arc::new(Mutex::new((HashMap::<String, usize>::new(), 0, 0)));
+        let pool = {
+            let in_flight = in_flight.clone();
+            WorkerPool::new(4, Duration::ZERO, move |url| {
+                let host = host_key(&url);
+                {
+                    let (hosts, per_host, total) = &mut *in_flight.lock().unwrap();
+                    let count = hosts.entry(host.clone()).or_default();
+                    *count += 1;
+                    *per_host = (*per_host).max(*count);
+                    *total = (*total).max(hosts.values().sum());
+                }
+                thread::sleep(Duration::from_millis(20));
+                *in_flight.lock().unwrap().0.get_mut(&host).unwrap() -= 1;
+                bail!("Not a real request")
+            })
+        };
+        for (tab, url) in [
+            "gemini://a.com/1",
+            "gemini://a.com/2",
+            "gemini://b.com/1",
+            "gemini://a.com/3",
+            "gemini://b.com/2",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            pool.load(tab as u64, Url::parse(url).unwrap());
+        }
+        let mut loaded = HashSet::new();
+        while loaded.len() < 5 {
+            match pool.try_recv() {
+                Some(result) => {
+                    loaded.insert(result.tab);
+                }
+                None => thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        let (_, per_host, total) = &*in_flight.lock().unwrap();
+        assert_eq!(1, *per_host);
+        assert_eq!(2, *total);
+    }
+
+    #[test]
+    fn hosts_left_alone_between_requests() {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let pool = {
+            let started = started.clone();
+            WorkerPool::new(2, Duration::from_millis(50), move |url| {
+                started
+                    .lock()
+                    .unwrap()
+                    .push((url.host_str().unwrap().to_string(), Instant::now()));
+                bail!("Not a real request")
+            })
+        };
+        for url in ["gemini://a.com/1", "gemini://a.com/2", "gemini://b.com/"] {
+            pool.load(0, Url::parse(url).unwrap());
+        }
+        for _ in 0..3 {
+            while pool.try_recv().is_none() {
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+        let started = started.lock().unwrap();
+        let at = |host: &str| -> Vec<Instant> {
+            started
+                .iter()
+                .filter(|(h, _)| h == host)
+                .map(|(_, at)| *at)
+                .collect()
+        };
+        let (a, b) = (at("a.com"), at("b.com"));
+        assert!(a[1] - a[0] >= Duration::from_millis(50));
+        // Other hosts do not wait
+        assert!(b[0] - a[0] < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::sync;
+
+/// Where pages were last scrolled to, so a long one opened again later is shown from there. The
+/// file may be shared by several machines, the latest position of each page is kept when it is
+/// read and written.
+#[derive(Default)]
+pub struct ReadingPositions {
+    path: Option<PathBuf>,
+    rows: HashMap<String, Position>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+struct Position {
+    /// 0 once scrolled back to the top, so older positions from other machines are forgotten
+    row: u16,
+    /// When it was scrolled there, in seconds since the Unix epoch
+    updated: u64,
+}
+
+/// A position as written, files written before positions had a time only have the row
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredPosition {
+    Row(u16),
+    Position(Position),
+}
+
+impl From<StoredPosition> for Position {
+    fn from(stored: StoredPosition) -> Self {
+        match stored {
+            StoredPosition::Row(row) => Position { row, updated: 0 },
+            StoredPosition::Position(position) => position,
+        }
+    }
+}
+
+/// The latest position of each page of the files
+fn merge(
+    files: impl IntoIterator<Item = HashMap<String, StoredPosition>>,
+) -> HashMap<String, Position> {
+    let mut merged: HashMap<String, Position> = HashMap::new();
+    for file in files {
+        for (url, position) in file {
+            let position = Position::from(position);
+            match merged.get(&url) {
+                Some(kept) if kept.updated >= position.updated => {}
+                _ => {
+                    merged.insert(url, position);
+                }
+            }
+        }
+    }
+    merged
+}
+
+impl ReadingPositions {
+    /// The positions kept in `path`, which is empty to keep nothing, merged with the copies of
+    /// it made by conflicts when it is synchronized between machines. The copies are then
+    /// removed.
+    pub fn load(path: &str) -> Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::default());
+        }
+        let path = PathBuf::from(path);
+        let (files, copies) = sync::read_all(&path)?;
+        let positions = Self {
+            path: Some(path),
+            rows: merge(files),
+        };
+        if !copies.is_empty() {
+            positions.write()?;
+            sync::remove_copies(&copies)?;
+        }
+        Ok(positions)
+    }
+
+    pub fn get(&self, url: &Url) -> Option<u16> {
+        self.rows
+            .get(&key(url))
+            .map(|position| position.row)
+            .filter(|row| *row > 0)
+    }
+
+    pub fn set(&mut self, url: &Url, row: u16) {
+        if self.path.is_none() {
+            return;
+        }
+        let key = key(url);
+        if row == 0 && !self.rows.contains_key(&key) {
+            return;
+        }
+        let updated = sync::now();
+        self.rows.insert(key, Position { row, updated });
+    }
+
+    /// Writes the positions merged with those other machines wrote since they were read
+    pub fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let (files, copies) = sync::read_all(path)?;
+        let own = self
+            .rows
+            .iter()
+            .map(|(url, position)| (url.clone(), StoredPosition::Position(*position)))
+            .collect();
+        self.rows = merge(std::iter::once(own).chain(files));
+        self.write()?;
+        sync::remove_copies(&copies)
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            fs::write(path, toml::to_string(&self.rows)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fragments point somewhere else in the same page, which is read from the same position
+fn key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn positions_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("taurus-test-{}-positions.toml", std::process::id()));
+        let path = path.to_string_lossy();
+        let page = Url::parse("gemini://a.com/long.gmi#part-2").unwrap();
+        let other = Url::parse("gemini://a.com/other.gmi").unwrap();
+        let mut positions = ReadingPositions::load(&path).unwrap();
+        positions.set(&page, 120);
+        positions.set(&other, 3);
+        positions.set(&other, 0);
+        positions.save().unwrap();
+        let loaded = ReadingPositions::load(&path).unwrap();
+        fs::remove_file(path.as_ref()).unwrap();
+        assert_eq!(
+            Some(120),
+            loaded.get(&Url::parse("gemini://a.com/long.gmi").unwrap())
+        );
+        assert_eq!(None, loaded.get(&other));
+    }
+
+    #[test]
+    fn latest_position_merged() {
+        let here = toml::from_str(
+            "\"gemini://a.com/\" = 40\n\
+             \"gemini://b.com/\" = { row = 10, updated = 100 }\n",
+        )
+        .unwrap();
+        let there = toml::from_str(
+            "\"gemini://a.com/\" = { row = 0, updated = 50 }\n\
+             \"gemini://b.com/\" = { row = 20, updated = 50 }\n",
+        )
+        .unwrap();
+        let positions = ReadingPositions {
+            path: None,
+            rows: merge([here, there]),
+        };
+        assert_eq!(None, positions.get(&Url::parse("gemini://a.com/").unwrap()));
+        assert_eq!(
+            Some(10),
+            positions.get(&Url::parse("gemini://b.com/").unwrap())
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Result};
+use toml_edit::DocumentMut;
+
+pub const SETTINGS_URL: &str = "about:config";
+
+/// The settings that can be changed while taurus runs, by their key in the configuration file
+pub const SETTINGS: [&str; 17] = [
+    "search_url",
+    "tabs.home_url",
+    "ui.tick_rate_ms",
+    "ui.frame_budget_ms",
+    "ui.link_numbers",
+    "ui.mark_duplicate_links",
+    "ui.break_long_words",
+    "ui.list_continuations",
+    "ui.smooth_scrolling",
+    "ui.line_numbers",
+    "ui.icons",
+    "ui.auto_scroll_ms",
+    "client.follow_redirects",
+    "client.min_request_interval_ms",
+    "client.header_timeout_ms",
+    "client.body_timeout_ms",
+    "client.max_body_bytes",
+];
+
+/// The values the numeric settings may take, inclusive. Redrawing without pause makes the
+/// interface spin, so the durations of the interface cannot be 0.
+const RANGES: [(&str, u64, u64); 6] = [
+    ("ui.tick_rate_ms", 10, 60_000),
+    ("ui.frame_budget_ms", 1, 1_000),
+    ("ui.auto_scroll_ms", 10, 60_000),
+    ("client.min_request_interval_ms", 0, 60_000),
+    ("client.header_timeout_ms", 0, 3_600_000),
+    ("client.body_timeout_ms", 0, 3_600_000),
+];
+
+/// Refuses a value out of the range of the setting, if it has one
+pub fn check(key: &str, value: &toml::Value) -> Result<()> {
+    let Some((_, min, max)) = RANGES.iter().find(|(name, ..)| *name == key) else {
+        return Ok(());
+    };
+    match value.as_integer() {
+        Some(number) if (*min as i64..=*max as i64).contains(&number) => Ok(()),
+        _ => bail!("{value} is not a number from {min} to {max}"),
+    }
+}
+
+/// The settings with their current values, with links to change them or save them to the
+/// configuration file. The actions are queries of the page, like `about:config?edit=search_url`.
+pub fn page(values: &[(&str, toml::Value)]) -> String {
+    let mut page = String::from(
+        "# Settings\n\n\
+         Changes apply right away and last until taurus stops, unless they are saved.\n",
+    );
+    for (key, value) in values {
+        page.push_str(&format!("\n## {key}\n{value}\n"));
+        page.push_str(&format!("=> {SETTINGS_URL}?edit={key} Change\n"));
+        page.push_str(&format!(
+            "=> {SETTINGS_URL}?save={key} Save to the configuration\n"
+        ));
+    }
+    page
+}
+
+/// A value typed after `:set`, as TOML like `300`, `true` or `"text"`. Anything else is taken as
+/// a string, so URLs need no quotes.
+pub fn parse_value(text: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {text}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(text.to_string()))
+}
+
+/// Writes a setting to the configuration file, keeping its comments and layout.
+pub fn save(path: &Path, key: &str, value: &toml::Value) -> Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut document: DocumentMut = contents.parse()?;
+    let mut table = document.as_table_mut();
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let Some(name) = parts.pop() else {
+        bail!("Empty setting");
+    };
+    for part in parts {
+        let Some(child) = table
+            .entry(part)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+        else {
+            bail!("{part} is not a table in {}", path.display());
+        };
+        table = child;
+    }
+    let value: toml_edit::Value = value.to_string().parse()?;
+    match table.get_mut(name).and_then(|item| item.as_value_mut()) {
+        // Keeps the comment after the value
+        Some(old) => {
+            let decor = old.decor().clone();
+            *old = value;
+            *old.decor_mut() = decor;
+        }
+        None => {
+            table.insert(name, toml_edit::Item::Value(value));
+        }
+    }
+    fs::write(path, document.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn values_typed_without_quotes() {
+        assert_eq!(toml::Value::Integer(300), parse_value("300"));
+        assert_eq!(toml::Value::Boolean(false), parse_value("false"));
+        assert_eq!(
+            toml::Value::String("gemini://a.com/".to_string()),
+            parse_value("gemini://a.com/")
+        );
+        assert_eq!(
+            toml::Value::String("superscript".to_string()),
+            parse_value("\"superscript\"")
+        );
+    }
+
+    #[test]
+    fn values_checked() {
+        assert!(check("ui.tick_rate_ms", &toml::Value::Integer(300)).is_ok());
+        assert!(check("ui.tick_rate_ms", &toml::Value::Integer(0)).is_err());
+        assert!(check("ui.frame_budget_ms", &toml::Value::String("16".into())).is_err());
+        assert!(check("client.header_timeout_ms", &toml::Value::Integer(0)).is_ok());
+        assert!(check("search_url", &toml::Value::String(String::new())).is_ok());
+    }
+
+    #[test]
+    fn saved_keeping_comments() {
+        let path =
+            std::env::temp_dir().join(format!("taurus-test-{}-settings.toml", std::process::id()));
+        fs::write(&path, "# Top\n[ui]\n# Redraws\ntick_rate_ms = 300 # ms\n").unwrap();
+        save(&path, "ui.tick_rate_ms", &toml::Value::Integer(500)).unwrap();
+        save(
+            &path,
+            "client.follow_redirects",
+            &toml::Value::Boolean(false),
+        )
+        .unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            "# Top\n[ui]\n# Redraws\ntick_rate_ms = 500 # ms\n\n[client]\nfollow_redirects = false\n",
+            contents
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    io::{ErrorKind, Write},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use super::document::{DocLine, Document};
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SpeechConfig {
+    /// Command speaking the text it reads on stdin, like `espeak-ng`. It is run once per
+    /// sentence.
+    pub command: Option<String>,
+}
+
+/// Paragraphs of a gemtext page as the sentences to speak. Preformatted blocks are skipped and
+/// links are announced with their text.
+pub fn gemtext_paragraphs(document: &Document) -> Vec<Vec<String>> {
+    document
+        .lines
+        .iter()
+        .filter_map(|line| match line {
+            DocLine::Text(text) => {
+                let text = text.trim_start_matches(['*', ' ']);
+                Some(sentences(text))
+            }
+            DocLine::Heading { text, .. }
+            | DocLine::ListItem(text)
+            | DocLine::Quote { text, .. } => Some(sentences(text)),
+            DocLine::Link { url, text, .. } => {
+                let text = if text.trim().is_empty() {
+                    url.as_str()
+                } else {
+                    text.trim()
+                };
+                Some(vec![format!("Link: {text}")])
+            }
+            DocLine::PreFormatted(_) => None,
+        })
+        .filter(|sentences| !sentences.is_empty())
+        .collect()
+}
+
+/// Paragraphs of plain text, separated by blank lines
+pub fn text_paragraphs(text: &str) -> Vec<Vec<String>> {
+    text.split("\n\n")
+        .map(|paragraph| sentences(&paragraph.split_whitespace().collect::<Vec<_>>().join(" ")))
+        .filter(|sentences| !sentences.is_empty())
+        .collect()
+}
+
+fn sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut sentence = String::new();
+    for word in text.split_whitespace() {
+        if !sentence.is_empty() {
+            sentence.push(' ');
+        }
+        sentence.push_str(word);
+        if word.ends_with(['.', '!', '?']) {
+            sentences.push(std::mem::take(&mut sentence));
+        }
+    }
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+    sentences
+}
+
+enum Control {
+    PlayPause,
+    NextParagraph,
+    Stop,
+}
+
+/// Reads paragraphs aloud on its own thread, one sentence at a time, until the end of the page
+/// or until dropped.
+pub struct Reader {
+    controls: Sender<Control>,
+    handle: Option<JoinHandle<Result<()>>>,
+    paused: bool,
+}
+
+impl Reader {
+    pub fn start(command: String, paragraphs: Vec<Vec<String>>) -> Self {
+        let (controls, received) = mpsc::channel();
+        let handle = thread::spawn(move || read(&command, &paragraphs, received));
+        Self {
+            controls,
+            handle: Some(handle),
+            paused: false,
+        }
+    }
+
+    pub fn play_pause(&mut self) {
+        self.paused = !self.paused;
+        let _ = self.controls.send(Control::PlayPause);
+    }
+
+    pub fn next_paragraph(&self) {
+        let _ = self.controls.send(Control::NextParagraph);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The result of the reading, once it is over
+    pub fn finished(&mut self) -> Option<Result<()>> {
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+        let handle = self.handle.take()?;
+        Some(handle.join().expect("Reader thread panicked"))
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        let _ = self.controls.send(Control::Stop);
+    }
+}
+
+fn read(command: &str, paragraphs: &[Vec<String>], controls: Receiver<Control>) -> Result<()> {
+    let (mut paragraph, mut sentence) = (0, 0);
+    let mut paused = false;
+    while paragraph < paragraphs.len() {
+        if paused {
+            match controls.recv() {
+                Ok(Control::PlayPause) => paused = false,
+                Ok(Control::NextParagraph) => (paragraph, sentence) = (paragraph + 1, 0),
+                Ok(Control::Stop) | Err(_) => return Ok(()),
+            }
+            continue;
+        }
+        let Some(text) = paragraphs[paragraph].get(sentence) else {
+            (paragraph, sentence) = (paragraph + 1, 0);
+            continue;
+        };
+        let mut child = speak(command, text)?;
+        loop {
+            match controls.recv_timeout(Duration::from_millis(50)) {
+                Ok(Control::PlayPause) => {
+                    // The interrupted sentence is read again from its start
+                    child.kill()?;
+                    paused = true;
+                    break;
+                }
+                Ok(Control::NextParagraph) => {
+                    child.kill()?;
+                    (paragraph, sentence) = (paragraph + 1, 0);
+                    break;
+                }
+                Ok(Control::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                    child.kill()?;
+                    return Ok(());
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if let Some(status) = child.try_wait()? {
+                if !status.success() {
+                    bail!("{command} failed with {status}");
+                }
+                sentence += 1;
+                break;
+            }
+        }
+        child.wait()?;
+    }
+    Ok(())
+}
+
+fn speak(command: &str, text: &str) -> Result<Child> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        bail!("Empty speech command");
+    };
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    match stdin.write_all(text.as_bytes()) {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => Err(err.into()),
+        _ => Ok(child),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use url::Url;
+
+    use super::*;
+
+    #[test]
+    fn gemtext_read_aloud() {
+        let page = "# Hello\nFirst one. Second one?\n```\nfn main() {}\n```\n\n=> /a A link\n=> gemini://b.com/\n* item";
+        let document = Document::gemtext(page, Url::parse("gemini://a.com/").unwrap());
+        assert_eq!(
+            vec![
+                vec!["Hello"],
+                vec!["First one.", "Second one?"],
+                vec!["Link: A link"],
+                vec!["Link: gemini://b.com/"],
+                vec!["item"],
+            ],
+            gemtext_paragraphs(&document)
+        );
+    }
+
+    #[test]
+    fn plain_text_paragraphs() {
+        assert_eq!(
+            vec![vec!["One line wrapped.", "Two!"], vec!["Next"]],
+            text_paragraphs("One line\nwrapped. Two!\n\n\nNext\n")
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use super::format_size;
+use crate::known_hosts::TrustPolicy;
+
+/// Width of the bar of a body received up to a maximum size, between its brackets
+const PROGRESS_WIDTH: usize = 10;
+
+/// What the bar at the bottom of the screen shows right of the command line, in the order
+/// listed in `status_segments`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Segment {
+    /// Browsing, typing, loading with the size received so far...
+    Mode,
+    Url,
+    /// How far down the page is scrolled
+    Scroll,
+    /// The time, in UTC
+    Clock,
+    /// Name of the client certificate, when the capsule of the current page asked for it
+    Identity,
+    /// How the certificate of the current host is trusted
+    Trust,
+}
+
+/// Position in a page of `rows` rows, `height` of them visible, scrolled down by `row`.
+pub fn scroll(row: u16, rows: usize, height: usize) -> String {
+    let last = rows.saturating_sub(height);
+    let row = row as usize;
+    if last == 0 {
+        "All".to_string()
+    } else if row == 0 {
+        "Top".to_string()
+    } else if row >= last {
+        "Bottom".to_string()
+    } else {
+        format!("{}%", row * 100 / last)
+    }
+}
+
+/// How much of a body was received in `elapsed` and how fast. Gemini does not tell the size of
+/// bodies, so there is only a bar of how far the body is from `max`, where it would be cut.
+pub fn transfer(received: u64, elapsed: Duration, max: Option<u64>) -> String {
+    let rate = match elapsed.as_secs_f64() {
+        seconds if seconds >= 1.0 => {
+            format!(", {}/s", format_size((received as f64 / seconds) as u64))
+        }
+        _ => String::new(),
+    };
+    match max.filter(|max| *max > 0) {
+        Some(max) => {
+            let filled = (received.min(max) * PROGRESS_WIDTH as u64 / max) as usize;
+            format!(
+                "[{}{}] {} of at most {}{rate}",
+                "#".repeat(filled),
+                "-".repeat(PROGRESS_WIDTH - filled),
+                format_size(received),
+                format_size(max)
+            )
+        }
+        None => format!("{}{rate}", format_size(received)),
+    }
+}
+
+pub fn clock(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        % 86400;
+    format!("{:02}:{:02} UTC", seconds / 3600, seconds % 3600 / 60)
+}
+
+pub fn trust(policy: TrustPolicy) -> &'static str {
+    match policy {
+        TrustPolicy::Tofu => "TOFU",
+        TrustPolicy::AlwaysTrust => "Always trusted",
+        TrustPolicy::Pinned => "Pinned",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn scroll_position() {
+        assert_eq!("All", scroll(0, 10, 20));
+        assert_eq!("Top", scroll(0, 100, 20));
+        assert_eq!("50%", scroll(40, 100, 20));
+        assert_eq!("Bottom", scroll(85, 100, 20));
+    }
+
+    #[test]
+    fn transfer_rate() {
+        assert_eq!("512 B", transfer(512, Duration::from_millis(300), None));
+        assert_eq!(
+            "3.0 MiB, 1.5 MiB/s",
+            transfer(3 * 1024 * 1024, Duration::from_secs(2), None)
+        );
+        assert_eq!(
+            "[###-------] 3.0 MiB of at most 10.0 MiB, 1.5 MiB/s",
+            transfer(
+                3 * 1024 * 1024,
+                Duration::from_secs(2),
+                Some(10 * 1024 * 1024)
+            )
+        );
+    }
+
+    #[test]
+    fn clock_in_utc() {
+        let time = UNIX_EPOCH + Duration::from_secs(3 * 86400 + 14 * 3600 + 5 * 60 + 59);
+        assert_eq!("14:05 UTC", clock(time));
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+
+/// The file named `file` in the sync directory when there is one, `path` otherwise. Empty paths
+/// stay empty, to keep nothing.
+pub fn path(sync_dir: &str, file: &str, path: &str) -> String {
+    if sync_dir.is_empty() || path.is_empty() {
+        return path.to_string();
+    }
+    Path::new(sync_dir)
+        .join(file)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The copies of `path` that file synchronization tools make when two machines changed it at
+/// the same time, like `bookmarks.sync-conflict-20240301-101010-ABCDEFG.toml` for Syncthing or
+/// `bookmarks (conflicted copy 2024-03-01).toml` for Dropbox. Only names made exactly this way
+/// are copies, as they are removed once merged.
+pub fn conflict_copies(path: &Path) -> Vec<PathBuf> {
+    let (Some(stem), Some(name)) = (path.file_stem(), path.file_name()) else {
+        return Vec::new();
+    };
+    let (stem, name) = (stem.to_string_lossy(), name.to_string_lossy());
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut copies: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let copy = entry.file_name().to_string_lossy().into_owned();
+            copy != name && is_conflict_copy(&copy, &stem, extension.as_deref())
+        })
+        .map(|entry| dir.join(entry.file_name()))
+        .collect();
+    copies.sort();
+    copies
+}
+
+/// Whether `name` is a conflict copy of the file `stem.extension`, as Syncthing or Dropbox names
+/// them
+fn is_conflict_copy(name: &str, stem: &str, extension: Option<&str>) -> bool {
+    let name = match extension {
+        Some(extension) => name.strip_suffix(&format!(".{extension}")),
+        None => Some(name),
+    };
+    let Some(mark) = name.and_then(|name| name.strip_prefix(stem)) else {
+        return false;
+    };
+    let digits =
+        |text: &str, count| text.len() == count && text.bytes().all(|b| b.is_ascii_digit());
+    // Syncthing: `.sync-conflict-20240301-101010-ABCDEFG`, the last part naming the device
+    if let Some(mark) = mark.strip_prefix(".sync-conflict-") {
+        return match mark.split('-').collect::<Vec<_>>()[..] {
+            [date, time, device] => {
+                digits(date, 8)
+                    && digits(time, 6)
+                    && device.len() == 7
+                    && device.bytes().all(|b| b.is_ascii_alphanumeric())
+            }
+            _ => false,
+        };
+    }
+    // Dropbox: ` (conflicted copy 2024-03-01)`, or ` (Alice's conflicted copy 2024-03-01)`
+    let Some(mark) = mark
+        .strip_prefix(" (")
+        .and_then(|mark| mark.strip_suffix(')'))
+    else {
+        return false;
+    };
+    let Some((owner, date)) = mark.split_once("conflicted copy ") else {
+        return false;
+    };
+    (owner.is_empty() || owner.ends_with(' '))
+        && match date.split('-').collect::<Vec<_>>()[..] {
+            [year, month, day] => digits(year, 4) && digits(month, 2) && digits(day, 2),
+            _ => false,
+        }
+}
+
+/// The contents of `path` then of its conflict copies, the missing file being empty, with the
+/// copies read.
+pub fn read_all<T: DeserializeOwned + Default>(path: &Path) -> Result<(Vec<T>, Vec<PathBuf>)> {
+    let copies = conflict_copies(path);
+    let mut contents = Vec::new();
+    for file in std::iter::once(path).chain(copies.iter().map(PathBuf::as_path)) {
+        match fs::read_to_string(file) {
+            Ok(text) => contents.push(toml::from_str(&text)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => contents.push(T::default()),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok((contents, copies))
+}
+
+/// Removes the conflict copies once what they held is written to the file they are copies of
+pub fn remove_copies(copies: &[PathBuf]) -> Result<()> {
+    for copy in copies {
+        fs::remove_file(copy)?;
+    }
+    Ok(())
+}
+
+/// Seconds since the Unix epoch, to tell which of two changes is the latest
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conflict_copies_found() {
+        let dir = std::env::temp_dir().join(format!("taurus-test-{}-sync", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in [
+            "bookmarks.toml",
+            "bookmarks.sync-conflict-20240301-101010-ABCDEFG.toml",
+            "bookmarks (conflicted copy 2024-03-01).toml",
+            "bookmarks.toml.bak",
+            "bookmarks conflict notes.toml",
+            "bookmarks.sync-conflict-notes.toml",
+            "bookmarks (Alice's conflicted copy 2024-03-02).toml",
+            "positions.sync-conflict-20240301-101010-ABCDEFG.toml",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let copies = conflict_copies(&dir.join("bookmarks.toml"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            vec![
+                dir.join("bookmarks (Alice's conflicted copy 2024-03-02).toml"),
+                dir.join("bookmarks (conflicted copy 2024-03-01).toml"),
+                dir.join("bookmarks.sync-conflict-20240301-101010-ABCDEFG.toml"),
+            ],
+            copies
+        );
+        assert_eq!(
+            "sync/bookmarks.toml",
+            path("sync", "bookmarks.toml", "b.toml")
+        );
+        assert_eq!("", path("sync", "bookmarks.toml", ""));
+        assert_eq!("b.toml", path("", "bookmarks.toml", "b.toml"));
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{content::Content, gemspace_nav::GemspaceNav, url_display};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TabsConfig {
+    /// Where the open tabs are kept between runs
+    pub session_file: String,
+    /// Where the scroll position of visited pages is kept, empty to keep nothing
+    pub positions_file: String,
+    /// How many background tabs are loaded at the same time
+    pub background_workers: usize,
+    /// Time a capsule is left alone after a background tab of it loaded, before loading the next
+    pub background_host_delay_ms: u64,
+    /// Page of the first tab when there is no session
+    pub home_url: Url,
+    pub new_tab: NewTabPage,
+}
+
+/// What a new tab shows
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NewTabPage {
+    /// An empty page, with the command line ready to type a URL
+    #[default]
+    Blank,
+    Home,
+    /// The pages visited since taurus was started
+    Trail,
+}
+
+impl Default for TabsConfig {
+    fn default() -> Self {
+        Self {
+            session_file: "session.toml".to_string(),
+            positions_file: "positions.toml".to_string(),
+            background_workers: 4,
+            background_host_delay_ms: 1000,
+            home_url: Url::parse("gemini://tlgs.one/").expect("We know that this is a valid url"),
+            new_tab: NewTabPage::default(),
+        }
+    }
+}
+
+pub struct Tab {
+    /// Identifies the tab while it is loaded in the background, as its position can change
+    pub id: u64,
+    pub nav: GemspaceNav,
+    pub content: Option<Content>,
+    pub scroll: (u16, u16),
+    /// The page is being fetched by the worker pool
+    pub loading: bool,
+    /// The page asked for input, the prompt is shown when the tab is selected
+    pub input: bool,
+    /// Links of the page this one was opened from
+    pub siblings: Option<Siblings>,
+    /// When the content was fetched, if it is a cached copy shown because the page could not be
+    pub cached: Option<SystemTime>,
+    /// Kept open and shown with just the host, for pages that are always open
+    pub pinned: bool,
+    /// Loaded in the background and not selected since
+    pub unread: bool,
+}
+
+impl Tab {
+    pub fn new(url: Url) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            nav: GemspaceNav::new(url),
+            content: None,
+            scroll: (0, 0),
+            loading: false,
+            input: false,
+            siblings: None,
+            cached: None,
+            pinned: false,
+            unread: false,
+        }
+    }
+
+    /// A new tab showing the same page, with the same history. It is not pinned.
+    pub fn duplicate(&self) -> Self {
+        let mut tab = Self::new(self.nav.current());
+        tab.nav = self.nav.clone();
+        tab.content = self.content.clone();
+        tab.scroll = self.scroll;
+        tab.input = self.input;
+        tab.siblings = self.siblings.clone();
+        tab.cached = self.cached;
+        tab
+    }
+
+    /// Title of the first level 1 heading of a gemtext page, if there is one.
+    pub fn page_title(&self) -> Option<&str> {
+        self.content.as_ref()?.document.as_ref()?.title()
+    }
+
+    /// Short name for the tab bar
+    pub fn label(&self) -> String {
+        if let Some(title) = self.page_title() {
+            return title.to_string();
+        }
+        self.host_label()
+    }
+
+    /// The host of the page, or its URL when it has none
+    pub fn host_label(&self) -> String {
+        let url = self.nav.current();
+        match url.host_str() {
+            Some(host) => host.to_string(),
+            None => url_display::decoded(url.as_str()).into_owned(),
+        }
+    }
+}
+
+/// The links of an index page, kept when following one of them so the next and previous ones
+/// can be visited without going back to the index.
+#[derive(Clone, Debug)]
+pub struct Siblings {
+    pub links: Vec<Url>,
+    pub position: usize,
+}
+
+impl Siblings {
+    /// The link `offset` positions away, with the siblings as seen from it
+    pub fn moved(&self, offset: isize) -> Option<(Url, Siblings)> {
+        let position = self.position.checked_add_signed(offset)?;
+        let url = self.links.get(position)?.clone();
+        let siblings = Siblings {
+            links: self.links.clone(),
+            position,
+        };
+        Some((url, siblings))
+    }
+}
+
+/// The tabs that were open when taurus was closed
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Session {
+    pub current: usize,
+    pub tabs: Vec<SessionTab>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SessionTab {
+    pub url: Url,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(toml::from_str(&contents)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Moves the pinned tabs before the others, the same tab staying the current one.
+    pub fn pinned_first(&mut self) {
+        let mut order: Vec<usize> = (0..self.tabs.len()).collect();
+        order.sort_by_key(|&index| !self.tabs[index].pinned);
+        self.current = order
+            .iter()
+            .position(|&index| index == self.current)
+            .unwrap_or_default();
+        let mut tabs: Vec<Option<SessionTab>> = self.tabs.drain(..).map(Some).collect();
+        self.tabs = order
+            .into_iter()
+            .filter_map(|index| tabs[index].take())
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("taurus-test-{}-session.toml", std::process::id()));
+        let session = Session {
+            current: 1,
+            tabs: vec![
+                SessionTab {
+                    url: Url::parse("gemini://a.com/").unwrap(),
+                    pinned: false,
+                },
+                SessionTab {
+                    url: Url::parse("about:downloads").unwrap(),
+                    pinned: true,
+                },
+            ],
+        };
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(1, loaded.current);
+        assert_eq!("about:downloads", loaded.tabs[1].url.as_str());
+        assert!(loaded.tabs[1].pinned);
+        assert!(Session::load(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn pinned_tabs_restored_first() {
+        let tab = |url: &str, pinned| SessionTab {
+            url: Url::parse(url).unwrap(),
+            pinned,
+        };
+        let mut session = Session {
+            current: 1,
+            tabs: vec![
+                tab("gemini://a.com/", false),
+                tab("gemini://b.com/", false),
+                tab("gemini://bbs.com/", true),
+                tab("gemini://feeds.com/", true),
+            ],
+        };
+        session.pinned_first();
+        let urls: Vec<&str> = session.tabs.iter().map(|tab| tab.url.as_str()).collect();
+        assert_eq!(
+            vec![
+                "gemini://bbs.com/",
+                "gemini://feeds.com/",
+                "gemini://a.com/",
+                "gemini://b.com/"
+            ],
+            urls
+        );
+        assert_eq!(3, session.current);
+    }
+
+    #[test]
+    fn siblings_moved() {
+        let siblings = Siblings {
+            links: ["gemini://a.com/1", "gemini://a.com/2", "gemini://a.com/3"]
+                .into_iter()
+                .map(|url| Url::parse(url).unwrap())
+                .collect(),
+            position: 1,
+        };
+        let (url, next) = siblings.moved(1).unwrap();
+        assert_eq!("gemini://a.com/3", url.as_str());
+        assert_eq!(2, next.position);
+        assert!(next.moved(1).is_none());
+        assert_eq!("gemini://a.com/1", siblings.moved(-1).unwrap().0.as_str());
+        assert!(siblings.moved(-2).is_none());
+    }
+}
@@ -0,0 +1,372 @@
+use ratatui::style::{Color, Style, Stylize};
+use serde::Deserialize;
+use url::Url;
+
+use super::notifications::Level;
+
+const ACCENT_PALETTE: [Color; 10] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::LightBlue,
+    Color::LightGreen,
+    Color::LightCyan,
+    Color::LightMagenta,
+    Color::LightYellow,
+    Color::LightRed,
+];
+
+/// The 16 ANSI colors with their usual xterm values, to find the closest one to other colors
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub name: ThemeName,
+    pub host_accent: bool,
+    /// Colors the terminal can display, detected from the environment when `auto`
+    pub colors: ColorSupport,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            name: ThemeName::Default,
+            host_accent: true,
+            colors: ColorSupport::Auto,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    Default,
+    /// Bright colors and bold text, for low-vision users
+    HighContrast,
+    /// Only bold, underline and reverse, for terminals without color support
+    Monochrome,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSupport {
+    Auto,
+    /// Only text attributes, also used when NO_COLOR is set
+    None,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Follows NO_COLOR (https://no-color.org), then COLORTERM and TERM like most terminal
+    /// programs do.
+    fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return ColorSupport::None;
+        }
+        if var("COLORTERM").is_some_and(|value| matches!(value.as_str(), "truecolor" | "24bit")) {
+            return ColorSupport::TrueColor;
+        }
+        match var("TERM") {
+            Some(term) if term == "dumb" => ColorSupport::None,
+            Some(term) if term.contains("256color") => ColorSupport::Ansi256,
+            _ => ColorSupport::Ansi16,
+        }
+    }
+}
+
+pub struct Theme {
+    config: ThemeConfig,
+    colors: ColorSupport,
+}
+
+impl Theme {
+    pub fn new(config: ThemeConfig) -> Self {
+        let colors = match config.colors {
+            ColorSupport::Auto => ColorSupport::detect(|name| std::env::var(name).ok()),
+            colors => colors,
+        };
+        tracing::debug!("Using {colors:?} colors");
+        Self { config, colors }
+    }
+
+    /// Degrades the colors of a style to what the terminal can display.
+    fn adapt(&self, mut style: Style) -> Style {
+        match self.colors {
+            ColorSupport::None => {
+                // Backgrounds mark blocks like preformatted text, reversing keeps them visible
+                if style.bg.is_some() {
+                    style = style.reversed();
+                }
+                style.fg = None;
+                style.bg = None;
+            }
+            ColorSupport::Ansi16 => {
+                style.fg = style.fg.map(to_ansi_16);
+                style.bg = style.bg.map(to_ansi_16);
+            }
+            ColorSupport::Ansi256 => {
+                style.fg = style.fg.map(to_ansi_256);
+                style.bg = style.bg.map(to_ansi_256);
+            }
+            ColorSupport::Auto | ColorSupport::TrueColor => {}
+        }
+        style
+    }
+
+    /// Style used for the page border and title. When host accents are enabled the color is
+    /// derived from the host so it is the same every time that capsule is visited.
+    pub fn frame_style(&self, url: &Url) -> Style {
+        self.adapt(match (self.config.name, url.host_str()) {
+            (ThemeName::HighContrast, _) => Style::new().fg(Color::White).bold(),
+            (ThemeName::Default, Some(host)) if self.config.host_accent => {
+                Style::new().fg(accent_for_host(host))
+            }
+            _ => Style::new(),
+        })
+    }
+
+    pub fn heading(&self, level: u8) -> Style {
+        self.adapt(match (self.config.name, level) {
+            (ThemeName::HighContrast, 1) => Style::new().fg(Color::LightYellow).bold().underlined(),
+            (ThemeName::HighContrast, _) => Style::new().fg(Color::LightYellow).bold(),
+            (ThemeName::Monochrome, 3) => Style::new().underlined(),
+            (_, 1) => Style::new().bold().underlined(),
+            (_, 2) => Style::new().bold(),
+            (ThemeName::Default, _) => Style::new().italic(),
+            (ThemeName::Monochrome, _) => Style::new().bold(),
+        })
+    }
+
+    /// Links to gemini pages, and with `external` links opened with other programs.
+    pub fn link(&self, external: bool) -> Style {
+        self.adapt(match (self.config.name, external) {
+            (ThemeName::Default, false) => Style::new().fg(Color::Blue),
+            (ThemeName::Default, true) => Style::new().fg(Color::Red),
+            (ThemeName::HighContrast, false) => Style::new().fg(Color::LightCyan).bold(),
+            (ThemeName::HighContrast, true) => {
+                Style::new().fg(Color::LightMagenta).bold().underlined()
+            }
+            (ThemeName::Monochrome, false) => Style::new().underlined(),
+            (ThemeName::Monochrome, true) => Style::new().bold().underlined(),
+        })
+    }
+
+    /// Links to pages visited in this session, whatever their scheme
+    pub fn visited_link(&self) -> Style {
+        self.adapt(match self.config.name {
+            ThemeName::Default => Style::new().fg(Color::Magenta),
+            ThemeName::HighContrast => Style::new().fg(Color::LightYellow).bold(),
+            ThemeName::Monochrome => Style::new().italic().underlined(),
+        })
+    }
+
+    pub fn preformatted(&self) -> Style {
+        self.adapt(match self.config.name {
+            ThemeName::Default => Style::new().bg(Color::Gray),
+            ThemeName::HighContrast => Style::new().fg(Color::Black).bg(Color::White),
+            ThemeName::Monochrome => Style::new().reversed(),
+        })
+    }
+
+    /// Quotes and the bars before them, dimmed so they stand back from the text replying to them
+    pub fn quote(&self) -> Style {
+        self.adapt(match self.config.name {
+            ThemeName::Default => Style::new().fg(Color::DarkGray).italic(),
+            ThemeName::HighContrast => Style::new().fg(Color::Gray).italic(),
+            ThemeName::Monochrome => Style::new().dim(),
+        })
+    }
+
+    /// The source line numbers left of a page
+    pub fn gutter(&self) -> Style {
+        self.adapt(match self.config.name {
+            ThemeName::Default => Style::new().fg(Color::DarkGray),
+            ThemeName::HighContrast => Style::new().fg(Color::Gray),
+            ThemeName::Monochrome => Style::new().dim(),
+        })
+    }
+
+    /// Line above a page telling it is not the one on the server
+    pub fn banner(&self) -> Style {
+        self.adapt(match self.config.name {
+            ThemeName::Default => Style::new().fg(Color::Black).bg(Color::Yellow),
+            ThemeName::HighContrast => Style::new().fg(Color::Black).bg(Color::LightYellow).bold(),
+            ThemeName::Monochrome => Style::new().reversed(),
+        })
+    }
+
+    pub fn notification(&self, level: Level) -> Style {
+        self.adapt(match (self.config.name, level) {
+            (ThemeName::Default, Level::Info) => Style::new().fg(Color::Green),
+            (ThemeName::Default, Level::Error) => Style::new().fg(Color::Red),
+            (ThemeName::HighContrast, Level::Info) => Style::new().fg(Color::LightGreen).bold(),
+            (ThemeName::HighContrast, Level::Error) => Style::new().fg(Color::LightRed).bold(),
+            (ThemeName::Monochrome, Level::Info) => Style::new(),
+            (ThemeName::Monochrome, Level::Error) => Style::new().bold().reversed(),
+        })
+    }
+}
+
+/// FNV-1a so the color does not change between runs or compiler versions.
+fn accent_for_host(host: &str) -> Color {
+    let hash = host
+        .to_ascii_lowercase()
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    ACCENT_PALETTE[(hash % ACCENT_PALETTE.len() as u64) as usize]
+}
+
+fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    match color {
+        Color::Rgb(r, g, b) => Some((r, g, b)),
+        Color::Indexed(index) if index < 16 => Some(ANSI_COLORS[index as usize].1),
+        Color::Indexed(index) if index < 232 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let index = index - 16;
+            Some((level(index / 36), level(index / 6 % 6), level(index % 6)))
+        }
+        Color::Indexed(index) => {
+            let gray = 8 + (index - 232) * 10;
+            Some((gray, gray, gray))
+        }
+        _ => None,
+    }
+}
+
+/// The closest of the 16 ANSI colors, named colors are kept.
+fn to_ansi_16(color: Color) -> Color {
+    let Some((r, g, b)) = rgb(color) else {
+        return color;
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        [(r, r2), (g, g2), (b, b2)]
+            .iter()
+            .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map(|(color, _)| *color)
+        .unwrap_or(color)
+}
+
+/// The closest color of the 6x6x6 cube of 256 color terminals, other colors are kept.
+fn to_ansi_256(color: Color) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    let level = |n: u8| ((n as u16 * 5 + 127) / 255) as u8;
+    Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accent_is_stable_per_host() {
+        let theme = Theme::new(ThemeConfig {
+            colors: ColorSupport::Ansi16,
+            ..ThemeConfig::default()
+        });
+        let page_1 = Url::parse("gemini://test.com/1").unwrap();
+        let page_2 = Url::parse("gemini://TEST.com/other/page").unwrap();
+        assert_eq!(theme.frame_style(&page_1), theme.frame_style(&page_2));
+        assert_ne!(Style::new(), theme.frame_style(&page_1));
+    }
+
+    #[test]
+    fn accent_can_be_disabled() {
+        let theme = Theme::new(ThemeConfig {
+            host_accent: false,
+            ..ThemeConfig::default()
+        });
+        let url = Url::parse("gemini://test.com/").unwrap();
+        assert_eq!(Style::new(), theme.frame_style(&url));
+    }
+
+    #[test]
+    fn monochrome_has_no_colors() {
+        let theme = Theme::new(ThemeConfig {
+            name: ThemeName::Monochrome,
+            ..ThemeConfig::default()
+        });
+        let url = Url::parse("gemini://test.com/").unwrap();
+        let styles = [
+            theme.frame_style(&url),
+            theme.heading(1),
+            theme.heading(2),
+            theme.heading(3),
+            theme.link(false),
+            theme.link(true),
+            theme.visited_link(),
+            theme.preformatted(),
+            theme.notification(Level::Info),
+            theme.notification(Level::Error),
+        ];
+        for style in styles {
+            assert_eq!((None, None), (style.fg, style.bg));
+        }
+        assert_ne!(theme.link(false), theme.link(true));
+    }
+
+    #[test]
+    fn color_support_from_environment() {
+        let detect = |vars: &[(&str, &str)]| {
+            ColorSupport::detect(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+        assert_eq!(
+            ColorSupport::None,
+            detect(&[("NO_COLOR", "1"), ("COLORTERM", "truecolor")])
+        );
+        assert_eq!(
+            ColorSupport::TrueColor,
+            detect(&[("NO_COLOR", ""), ("COLORTERM", "24bit")])
+        );
+        assert_eq!(ColorSupport::Ansi256, detect(&[("TERM", "xterm-256color")]));
+        assert_eq!(ColorSupport::None, detect(&[("TERM", "dumb")]));
+        assert_eq!(ColorSupport::Ansi16, detect(&[]));
+    }
+
+    #[test]
+    fn colors_degraded() {
+        assert_eq!(Color::LightRed, to_ansi_16(Color::Rgb(250, 10, 10)));
+        assert_eq!(Color::Cyan, to_ansi_16(Color::Indexed(44)));
+        assert_eq!(Color::Blue, to_ansi_16(Color::Blue));
+        assert_eq!(Color::Indexed(196), to_ansi_256(Color::Rgb(255, 0, 0)));
+        let theme = Theme::new(ThemeConfig {
+            colors: ColorSupport::None,
+            ..ThemeConfig::default()
+        });
+        assert_eq!(Style::new().reversed(), theme.preformatted());
+        assert_eq!(None, theme.link(false).fg);
+    }
+}
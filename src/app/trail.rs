@@ -0,0 +1,135 @@
+use std::{
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use url::Url;
+
+use super::url_display;
+
+pub const TRAIL_URL: &str = "about:trail";
+
+/// The pages visited since taurus was started, in order.
+#[derive(Default)]
+pub struct Trail {
+    visits: Vec<Visit>,
+    /// The visited URLs without their fragment, to style the links to them
+    visited: HashSet<Url>,
+}
+
+struct Visit {
+    url: Url,
+    title: Option<String>,
+    time: SystemTime,
+}
+
+impl Trail {
+    pub fn record(&mut self, url: Url, title: Option<String>, time: SystemTime) {
+        let mut visited = url.clone();
+        visited.set_fragment(None);
+        self.visited.insert(visited);
+        self.visits.push(Visit { url, title, time });
+    }
+
+    /// Whether the page was visited, whatever the fragment of the URL
+    pub fn visited(&self, url: &Url) -> bool {
+        if url.fragment().is_none() {
+            return self.visited.contains(url);
+        }
+        let mut url = url.clone();
+        url.set_fragment(None);
+        self.visited.contains(&url)
+    }
+
+    /// The trail as a gemtext page, to be published or archived.
+    pub fn gemtext(&self) -> String {
+        let mut page = "# Session trail\n\n".to_string();
+        match (self.visits.first(), self.visits.last()) {
+            (Some(first), Some(last)) => page.push_str(&format!(
+                "{} pages visited from {} to {}\n\n",
+                self.visits.len(),
+                format_time(first.time),
+                format_time(last.time)
+            )),
+            _ => page.push_str("No page visited yet\n"),
+        }
+        for visit in &self.visits {
+            let title = match &visit.title {
+                Some(title) => title.clone(),
+                None => url_display::decoded(visit.url.as_str()).into_owned(),
+            };
+            page.push_str(&format!(
+                "=> {} {} {title}\n",
+                visit.url,
+                format_time(visit.time)
+            ));
+        }
+        page
+    }
+}
+
+/// Date and time in UTC, like `2024-03-01 09:05 UTC`
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_date(seconds / 86400);
+    let minutes = seconds % 86400 / 60;
+    format!(
+        "{year}-{month:02}-{day:02} {:02}:{:02} UTC",
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Year, month and day of a number of days since 1970-01-01, in the Gregorian calendar.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Days since 0000-03-01, so leap days end the year, split in 400 years cycles
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months starting in March
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!("1970-01-01 00:00 UTC", format_time(UNIX_EPOCH));
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1_709_197_200);
+        assert_eq!("2024-02-29 09:00 UTC", format_time(leap_day));
+        let new_year = UNIX_EPOCH + Duration::from_secs(1_735_689_599);
+        assert_eq!("2024-12-31 23:59 UTC", format_time(new_year));
+    }
+
+    #[test]
+    fn trail_as_gemtext() {
+        let mut trail = Trail::default();
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_197_200);
+        let url = Url::parse("gemini://a.com/").unwrap();
+        trail.record(url.clone(), Some("A capsule".to_string()), time);
+        let later = time + Duration::from_secs(60);
+        trail.record(url.join("b.txt").unwrap(), None, later);
+        assert!(trail.visited(&url.join("#top").unwrap()));
+        assert!(!trail.visited(&url.join("c.txt").unwrap()));
+        assert_eq!(
+            "# Session trail\n\n2 pages visited from 2024-02-29 09:00 UTC to 2024-02-29 09:01 UTC\n\n\
+             => gemini://a.com/ 2024-02-29 09:00 UTC A capsule\n\
+             => gemini://a.com/b.txt 2024-02-29 09:01 UTC gemini://a.com/b.txt\n",
+            trail.gemtext()
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    io::{ErrorKind, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TranslationConfig {
+    /// Command reading text on stdin and writing its translation to stdout, like
+    /// `trans -b :en`. Services can be used through a script calling them.
+    pub command: Option<String>,
+}
+
+/// Translates the text of a gemtext page, keeping link and preformatted lines as they are.
+pub fn translate_gemtext(command: &str, gemtext: &str) -> Result<String> {
+    let lines: Vec<&str> = gemtext.lines().collect();
+    let mut pre_formatted = false;
+    // Position, line type prefix and text of the lines to translate
+    let mut translatable = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if line.starts_with("```") {
+            pre_formatted = !pre_formatted;
+            continue;
+        }
+        if pre_formatted || line.starts_with("=>") || line.trim().is_empty() {
+            continue;
+        }
+        let text_start = line
+            .find(|c: char| !matches!(c, '#' | '*' | '>' | ' '))
+            .unwrap_or(line.len());
+        let (prefix, text) = line.split_at(text_start);
+        translatable.push((index, prefix, text));
+    }
+    let input: Vec<&str> = translatable.iter().map(|(_, _, text)| *text).collect();
+    let output = run(command, &input.join("\n"))?;
+    let translated: Vec<&str> = output.lines().collect();
+    if translated.len() != translatable.len() {
+        tracing::warn!(
+            "Translation has {} lines instead of {}, links are listed at the end",
+            translated.len(),
+            translatable.len()
+        );
+        let links: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| line.starts_with("=>"))
+            .collect();
+        return Ok(format!("{}\n\n{}\n", output.trim_end(), links.join("\n")));
+    }
+    let mut page: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    for ((index, prefix, _), text) in translatable.into_iter().zip(translated) {
+        page[index] = format!("{prefix}{}", text.trim());
+    }
+    Ok(page.join("\n") + "\n")
+}
+
+pub fn translate_text(command: &str, text: &str) -> Result<String> {
+    run(command, text)
+}
+
+fn run(command: &str, input: &str) -> Result<String> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        bail!("Empty translation command");
+    };
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    // Written from another thread, so a command answering while reading does not block us
+    let writer = thread::spawn(move || match stdin.write_all(input.as_bytes()) {
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    });
+    let output = child.wait_with_output()?;
+    writer.join().expect("Translation writer panicked")?;
+    if !output.status.success() {
+        bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PAGE: &str = "# Title\nsome text\n=> /link A link\n```\ncode\n```\n* item\n";
+
+    #[test]
+    fn links_and_preformatted_are_kept() {
+        assert_eq!(
+            "# TITLE\nSOME TEXT\n=> /link A link\n```\ncode\n```\n* ITEM\n",
+            translate_gemtext("tr a-z A-Z", PAGE).unwrap()
+        );
+    }
+
+    #[test]
+    fn links_listed_when_lines_do_not_match() {
+        assert_eq!(
+            "Title\n\n=> /link A link\n",
+            translate_gemtext("head -n 1", PAGE).unwrap()
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+use url::Url;
+
+/// Extensions that make a typed `name.ext` look like a relative file instead of a host.
+const FILE_EXTENSIONS: [&str; 12] = [
+    "gmi", "gemini", "gmni", "txt", "md", "html", "xml", "atom", "png", "jpg", "gif", "pdf",
+];
+
+/// Turns what was typed in the URL bar into the URL to visit.
+pub fn resolve(current: &Url, text: &str, search_url: &Url) -> Result<Url> {
+    let text = text.trim();
+    if text.is_empty() {
+        bail!("nothing was typed");
+    }
+    if looks_like_search(text) {
+        let mut url = search_url.clone();
+        url.set_query(Some(text));
+        return Ok(url);
+    }
+    if looks_like_host(text) {
+        let mut url = Url::parse(&format!("gemini://{text}"))?;
+        if url.path().is_empty() {
+            url.set_path("/");
+        }
+        return Ok(url);
+    }
+    Ok(current.join(text)?)
+}
+
+/// Anything with spaces, or a single word that can not be a host nor a path.
+fn looks_like_search(text: &str) -> bool {
+    text.contains(char::is_whitespace) || !text.contains(['.', '/', ':', '?', '#'])
+}
+
+/// `geminiprotocol.net/docs/` or `localhost:1965` are hosts, while `index.gmi` or
+/// `notes/today.gmi` are paths relative to the current page.
+fn looks_like_host(text: &str) -> bool {
+    if text.contains("://") || text.starts_with(['.', '/', '?', '#']) {
+        return false;
+    }
+    let (authority, path) = match text.split_once('/') {
+        Some((authority, path)) => (authority, Some(path)),
+        None => (text, None),
+    };
+    if authority.is_empty() || authority.contains(char::is_whitespace) {
+        return false;
+    }
+    if let Some((host, port)) = authority.rsplit_once(':') {
+        return !host.is_empty() && !port.is_empty() && port.chars().all(|c| c.is_ascii_digit());
+    }
+    let Some((_, tld)) = authority.rsplit_once('.') else {
+        return false;
+    };
+    if tld.is_empty() || !tld.chars().all(|c| c.is_ascii_alphabetic()) {
+        return false;
+    }
+    path.is_some() || !FILE_EXTENSIONS.contains(&tld.to_ascii_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolve_from_page(text: &str) -> String {
+        let current = Url::parse("gemini://test.com/dir/page.gmi").unwrap();
+        let search_url = Url::parse("gemini://search.com/q").unwrap();
+        resolve(&current, text, &search_url).unwrap().to_string()
+    }
+
+    #[test]
+    fn empty_input_is_an_error() {
+        let current = Url::parse("gemini://test.com/").unwrap();
+        assert!(resolve(&current, "  ", &current).is_err());
+    }
+
+    #[test]
+    fn searches() {
+        assert_eq!(
+            "gemini://search.com/q?gemini%20clients",
+            resolve_from_page("gemini clients")
+        );
+        assert_eq!("gemini://search.com/q?rust", resolve_from_page("rust"));
+        assert_eq!(
+            "gemini://search.com/q?what%20is%20tlgs.one",
+            resolve_from_page("what is tlgs.one")
+        );
+    }
+
+    #[test]
+    fn bare_hosts_are_gemini_urls() {
+        assert_eq!(
+            "gemini://geminiprotocol.net/docs/",
+            resolve_from_page("geminiprotocol.net/docs/")
+        );
+        assert_eq!("gemini://tlgs.one/", resolve_from_page("tlgs.one"));
+        assert_eq!(
+            "gemini://localhost:1965/",
+            resolve_from_page("localhost:1965")
+        );
+    }
+
+    #[test]
+    fn relative_paths_are_joined() {
+        assert_eq!(
+            "gemini://test.com/dir/index.gmi",
+            resolve_from_page("index.gmi")
+        );
+        assert_eq!(
+            "gemini://test.com/dir/notes/a.gmi",
+            resolve_from_page("notes/a.gmi")
+        );
+        assert_eq!("gemini://test.com/other/", resolve_from_page("../other/"));
+        assert_eq!("gemini://test.com/root", resolve_from_page("/root"));
+    }
+
+    #[test]
+    fn absolute_urls_are_kept() {
+        assert_eq!(
+            "gemini://other.org/a.gmi",
+            resolve_from_page("gemini://other.org/a.gmi")
+        );
+        assert_eq!(
+            "https://example.com/",
+            resolve_from_page("https://example.com/")
+        );
+    }
+}
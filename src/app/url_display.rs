@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const ELLIPSIS: char = '…';
+
+/// `url` in at most `width` columns. The scheme and host are kept, then as much of the end of
+/// the path as fits after an ellipsis, as the last segments tell most about the page.
+pub fn shorten(url: &str, width: usize) -> Cow<'_, str> {
+    if url.width() <= width {
+        return Cow::Borrowed(url);
+    }
+    // URLs without a host, like about: pages, are cut at the end
+    let Some(scheme_end) = url.find("://") else {
+        return Cow::Owned(cut_end(url, width));
+    };
+    let host_start = scheme_end + 3;
+    let host_end = url[host_start..]
+        .find('/')
+        .map_or(url.len(), |host_end| host_start + host_end);
+    let (start, rest) = url.split_at(host_end);
+    // Not even a few characters of the path fit after the host, so the end is cut instead
+    let Some(tail_width) = width
+        .checked_sub(start.width() + 1)
+        .filter(|tail_width| *tail_width >= 4.min(rest.width()))
+    else {
+        return Cow::Owned(cut_end(url, width));
+    };
+    let mut tail_start = rest.len();
+    let mut used = 0;
+    for (index, c) in rest.char_indices().rev() {
+        used += c.width().unwrap_or_default();
+        if used > tail_width {
+            break;
+        }
+        tail_start = index;
+    }
+    Cow::Owned(format!("{start}{ELLIPSIS}{}", &rest[tail_start..]))
+}
+
+/// `url` with the percent encoded characters that are not ASCII decoded, so paths in other
+/// languages can be read. Encoded ASCII like `%20` or `%2F` is kept, as are invisible characters
+/// that could make a URL look like another.
+pub fn decoded(url: &str) -> Cow<'_, str> {
+    if !url.contains('%') {
+        return Cow::Borrowed(url);
+    }
+    let mut decoded = String::with_capacity(url.len());
+    let mut rest = url;
+    while let Some(start) = rest.find('%') {
+        decoded.push_str(&rest[..start]);
+        let encoded = &rest[start..];
+        let (bytes, len) = non_ascii_bytes(encoded);
+        match std::str::from_utf8(&bytes) {
+            Ok(text) if !text.is_empty() && text.chars().all(is_visible) => {
+                decoded.push_str(text);
+            }
+            _ => decoded.push_str(&encoded[..len.max(1)]),
+        }
+        rest = &encoded[len.max(1)..];
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+/// The bytes above 127 percent encoded at the start of `text`, and the length of their encoding
+fn non_ascii_bytes(text: &str) -> (Vec<u8>, usize) {
+    let mut bytes = Vec::new();
+    let mut len = 0;
+    while let Some(byte) = text
+        .get(len..len + 3)
+        .and_then(|encoded| encoded.strip_prefix('%'))
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        .filter(|byte| *byte >= 0x80)
+    {
+        bytes.push(byte);
+        len += 3;
+    }
+    (bytes, len)
+}
+
+/// Not a control, zero width or direction override character
+fn is_visible(c: char) -> bool {
+    !c.is_control()
+        && !matches!(c, '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{206F}' | '\u{FEFF}')
+}
+
+fn cut_end(text: &str, width: usize) -> String {
+    let mut cut = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        used += c.width().unwrap_or_default();
+        if used + 1 > width {
+            break;
+        }
+        cut.push(c);
+    }
+    if width > 0 {
+        cut.push(ELLIPSIS);
+    }
+    cut
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn middle_of_long_urls_elided() {
+        let url = "gemini://example.org/posts/2024/03/a-very-long-post-title.gmi";
+        assert_eq!(url, shorten(url, 80));
+        assert_eq!(
+            "gemini://example.org…/a-very-long-post-title.gmi",
+            shorten(url, 48)
+        );
+        assert_eq!(48, shorten(url, 48).width());
+    }
+
+    #[test]
+    fn non_ascii_decoded() {
+        assert_eq!(
+            "gemini://a.com/日本/notes%20old.gmi?q=%2F",
+            decoded("gemini://a.com/%E6%97%A5%E6%9C%AC/notes%20old.gmi?q=%2F")
+        );
+        // Invalid UTF-8 and a right to left override
+        assert_eq!(
+            "gemini://a.com/%FF%E2%80",
+            decoded("gemini://a.com/%FF%E2%80")
+        );
+        assert_eq!(
+            "gemini://a.com/%E2%80%AEgmi",
+            decoded("gemini://a.com/%E2%80%AEgmi")
+        );
+        assert_eq!("gemini://a.com/%", decoded("gemini://a.com/%"));
+    }
+
+    #[test]
+    fn end_cut_when_the_host_barely_fits() {
+        let url = "gemini://a-rather-long-host-name.example.org/page.gmi";
+        assert_eq!("gemini://a-rather-lo…", shorten(url, 21));
+        assert_eq!("about:dow…", shorten("about:downloads", 10));
+    }
+}
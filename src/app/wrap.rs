@@ -0,0 +1,91 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Greedy word wrapping of `text` to `width` columns. Words longer than a line, like URLs pasted
+/// in text, are either put alone on their lines or, with `break_long_words`, broken anywhere
+/// starting on the current line, so no line is left almost empty.
+pub fn wrap(text: &str, width: usize, break_long_words: bool) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut used = 0;
+    for word in text.split_whitespace() {
+        let word_width = word.width();
+        let space = usize::from(!line.is_empty());
+        if used + space + word_width <= width || (line.is_empty() && !break_long_words) {
+            if space == 1 {
+                line.push(' ');
+            }
+            line.push_str(word);
+            used += space + word_width;
+            continue;
+        }
+        if word_width <= width || !break_long_words || used + 2 > width {
+            lines.push(std::mem::take(&mut line));
+            used = 0;
+        } else if space == 1 {
+            line.push(' ');
+            used += 1;
+        }
+        if word_width <= width || !break_long_words {
+            line.push_str(word);
+            used = word_width;
+            continue;
+        }
+        for c in word.chars() {
+            let c_width = c.width().unwrap_or_default();
+            if used + c_width > width && used > 0 {
+                lines.push(std::mem::take(&mut line));
+                used = 0;
+            }
+            line.push(c);
+            used += c_width;
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `text` cut every `width` columns, keeping its spaces, for preformatted text
+pub fn chunks(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = vec![String::new()];
+    let mut used = 0;
+    for c in text.chars() {
+        let c_width = c.width().unwrap_or_default();
+        if used + c_width > width && used > 0 {
+            lines.push(String::new());
+            used = 0;
+        }
+        lines.last_mut().expect("There is always a line").push(c);
+        used += c_width;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn long_words_broken_anywhere() {
+        let text = "see gemini://example.org/long now";
+        assert_eq!(
+            vec!["see", "gemini://example.org/long", "now"],
+            wrap(text, 10, false)
+        );
+        assert_eq!(
+            vec!["see gemini", "://example", ".org/long", "now"],
+            wrap(text, 10, true)
+        );
+        assert_eq!(vec!["a b", "c"], wrap("a  b c", 3, true));
+        assert_eq!(vec!["日本", "語"], wrap("日本語", 5, true));
+    }
+
+    #[test]
+    fn preformatted_cut_keeping_spaces() {
+        assert_eq!(vec!["  /\\ ", " /  \\"], chunks("  /\\  /  \\", 5));
+        assert_eq!(vec![""], chunks("", 5));
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Display,
+    fs,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+use url::Url;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::{
+    client::{Client, GeminiResponse, ResponseBody},
+    gemtext::{GemTextLine, GemTextParser},
+};
+
+/// Longest meta line allowed by the specification, in bytes
+const MAX_META_LENGTH: usize = 1024;
+const EXPIRY_WARNING_DAYS: i64 = 30;
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// Capsule to check, like gemini://myhost/
+    pub url: Url,
+    /// How many links away from the first page to crawl
+    #[arg(long, default_value_t = 3)]
+    pub depth: usize,
+    /// Responses slower than this, in milliseconds, are reported
+    #[arg(long, default_value_t = 2000)]
+    pub slow_ms: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Broken(String),
+    InvalidLink(String),
+    Slow(Duration),
+    OversizedMeta(usize),
+    MissingLang,
+    CertificateExpired,
+    CertificateExpiresSoon { days: i64 },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::Broken(reason) => write!(f, "broken, {reason}"),
+            Problem::InvalidLink(err) => write!(f, "invalid link, {err}"),
+            Problem::Slow(elapsed) => write!(f, "slow response, took {}ms", elapsed.as_millis()),
+            Problem::OversizedMeta(len) => {
+                write!(f, "meta is {len} bytes, more than {MAX_META_LENGTH}")
+            }
+            Problem::MissingLang => f.write_str("text/gemini without a lang parameter"),
+            Problem::CertificateExpired => f.write_str("certificate expired"),
+            Problem::CertificateExpiresSoon { days } => {
+                write!(f, "certificate expires in {days} days")
+            }
+        }
+    }
+}
+
+pub struct Finding {
+    pub url: String,
+    /// The page that linked to `url`
+    pub referrer: Option<Url>,
+    pub problem: Problem,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.url, self.problem)?;
+        if let Some(referrer) = &self.referrer {
+            write!(f, " (linked from {referrer})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Crawls a capsule, printing every problem found. Links to other hosts are requested but not
+/// crawled.
+pub fn run(client: &Client, args: CheckArgs) -> Result<()> {
+    let mut findings = Vec::new();
+    let mut report = |finding: Finding| {
+        println!("{finding}");
+        findings.push(finding);
+    };
+    if let Some(problem) = certificate_problem(client, &args.url)? {
+        report(Finding {
+            url: args.url.to_string(),
+            referrer: None,
+            problem,
+        });
+    }
+
+    let host = args.url.host_str().map(str::to_string);
+    let slow = Duration::from_millis(args.slow_ms);
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([(args.url.clone(), 0, None)]);
+    while let Some((mut url, depth, referrer)) = queue.pop_front() {
+        url.set_fragment(None);
+        if !visited.insert(url.clone()) {
+            continue;
+        }
+        let mut problem = |problem: Problem| {
+            report(Finding {
+                url: url.to_string(),
+                referrer: referrer.clone(),
+                problem,
+            })
+        };
+        let started = Instant::now();
+        let response = client.request(url.clone(), &mut |_| Ok(()));
+        let elapsed = started.elapsed();
+        if elapsed > slow {
+            problem(Problem::Slow(elapsed));
+        }
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                problem(Problem::Broken(err.to_string()));
+                continue;
+            }
+        };
+        let meta_len = response.meta().len();
+        if meta_len > MAX_META_LENGTH {
+            problem(Problem::OversizedMeta(meta_len));
+        }
+        match response {
+            GeminiResponse::Redirect { url: target, .. } => {
+                queue.push_back((target, depth, referrer.clone()));
+            }
+            GeminiResponse::TemporaryFailure { status, .. } => {
+                problem(Problem::Broken(format!("temporary failure {status:?}")));
+            }
+            GeminiResponse::PermanentFailure { status, .. } => {
+                problem(Problem::Broken(format!("permanent failure {status:?}")));
+            }
+            GeminiResponse::Success { mime, body, .. } if mime.starts_with("text/gemini") => {
+                if !has_lang(&mime) {
+                    problem(Problem::MissingLang);
+                }
+                if depth >= args.depth || url.host_str() != host.as_deref() {
+                    continue;
+                }
+                let text = match body {
+                    ResponseBody::Bytes(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                    ResponseBody::File { file, .. } => fs::read_to_string(file.path())?,
+                };
+                for line in GemTextParser::new(&text, url.clone()) {
+                    match line {
+                        Ok(GemTextLine::Link { url: link, .. }) if link.scheme() == "gemini" => {
+                            queue.push_back((link, depth + 1, Some(url.clone())));
+                        }
+                        Ok(_) => {}
+                        Err(err) => problem(Problem::InvalidLink(err.to_string())),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    println!(
+        "Checked {} URLs, found {} problems",
+        visited.len(),
+        findings.len()
+    );
+    if !findings.is_empty() {
+        bail!("{} problems found in {}", findings.len(), args.url);
+    }
+    Ok(())
+}
+
+fn has_lang(mime: &str) -> bool {
+    mime.split(';')
+        .skip(1)
+        .any(|parameter| parameter.trim().to_ascii_lowercase().starts_with("lang="))
+}
+
+fn certificate_problem(client: &Client, url: &Url) -> Result<Option<Problem>> {
+    let der = client.peer_certificate(url)?;
+    let (_, certificate) = X509Certificate::from_der(&der)
+        .map_err(|err| anyhow!("Invalid server certificate: {err}"))?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    Ok(expiry_problem(
+        certificate.validity().not_after.timestamp(),
+        now,
+    ))
+}
+
+fn expiry_problem(not_after: i64, now: i64) -> Option<Problem> {
+    let days = (not_after - now).div_euclid(24 * 60 * 60);
+    if not_after < now {
+        Some(Problem::CertificateExpired)
+    } else if days < EXPIRY_WARNING_DAYS {
+        Some(Problem::CertificateExpiresSoon { days })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lang_parameter() {
+        assert!(has_lang("text/gemini; lang=en"));
+        assert!(has_lang("text/gemini;charset=utf-8;LANG=pt-BR"));
+        assert!(!has_lang("text/gemini"));
+        assert!(!has_lang("text/gemini; charset=utf-8"));
+    }
+
+    #[test]
+    fn certificate_expiry() {
+        let day = 24 * 60 * 60;
+        assert_eq!(Some(Problem::CertificateExpired), expiry_problem(0, day));
+        assert_eq!(
+            Some(Problem::CertificateExpiresSoon { days: 2 }),
+            expiry_problem(2 * day + 10, 0)
+        );
+        assert_eq!(None, expiry_problem(365 * day, 0));
+    }
+}
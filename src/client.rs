@@ -1,58 +1,372 @@
 use std::{
-    io::{BufRead, BufReader, Cursor, Read, Write},
-    net::TcpStream,
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
 use anyhow::{anyhow, bail, Result};
 use rustls::{
-    client::danger::{ServerCertVerified, ServerCertVerifier},
+    client::{
+        danger::{ServerCertVerified, ServerCertVerifier},
+        ResolvesClientCert,
+    },
     crypto::{
         aws_lc_rs::default_provider, verify_tls12_signature, verify_tls13_signature, CryptoProvider,
     },
     pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
-    ClientConfig,
+    sign::CertifiedKey,
+    CertificateError, ClientConfig, SignatureScheme,
 };
+use serde::Deserialize;
 use url::Url;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::{
+    known_hosts::{KnownHosts, Verification},
+    logging,
+    secrets::{self, Passphrase},
+};
+
+/// Longest meta servers may send
+const MAX_META_SIZE: usize = 1024;
+/// Two digits status, a space, up to 1024 bytes of meta and the CRLF
+const MAX_HEADER_SIZE: usize = 1029;
+/// Longest header read with `permissive_meta`, so a server sending no line end still fails
+const MAX_PERMISSIVE_HEADER_SIZE: usize = 64 * 1024;
+const CHUNK_SIZE: usize = 1024 * 16;
+/// The address, a space, the message and the CRLF
+const MAX_MISFIN_REQUEST_SIZE: usize = 2048;
+/// Redirects followed one after the other before giving up, as the specification recommends
+const MAX_REDIRECTS: usize = 5;
+/// Longest URL servers have to accept in a request, before the CRLF
+pub const MAX_REQUEST_URL_SIZE: usize = 1024;
 
+#[derive(Clone)]
 pub struct Client {
-    client_config: Arc<ClientConfig>,
-    auto_redirect: bool,
+    /// Shared by the clones of the client, like the options, so changes apply to all of them
+    tls: Arc<RwLock<Tls>>,
+    /// The configurations of the identities used for URLs starting with a prefix, by prefix
+    scoped_tls: Arc<RwLock<Vec<(String, Tls)>>>,
+    options: Arc<RwLock<ClientOptions>>,
+    known_hosts: Arc<KnownHosts>,
+    rate_limiter: Arc<RateLimiter>,
+    /// Hosts and ports of the servers that asked for the client certificate
+    identified_hosts: Arc<Mutex<HashSet<String>>>,
+    /// How the last responses were received, by URL
+    responses: Arc<Mutex<HashMap<String, ResponseInfo>>>,
+    /// The Misfin URLs whose message the user confirmed sending, each sent once
+    confirmed_messages: Arc<Mutex<HashSet<String>>>,
 }
 
+/// Which TLS versions servers may use
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsMode {
+    /// TLS 1.2 and 1.3, as many self-hosted capsules only have TLS 1.2
+    #[default]
+    Compat,
+    /// TLS 1.3 only
+    Strict,
+}
+
+/// How the last response to a URL was received
+#[derive(Clone, Debug, Default)]
+pub struct ResponseInfo {
+    /// The TLS version and cipher suite negotiated, like `TLS 1.3, TLS13_AES_256_GCM_SHA384`
+    pub tls: Option<String>,
+    /// What the response did not do as the specification says, which was tolerated
+    pub warnings: Vec<String>,
+    /// The meta line of the header, as the server sent it
+    pub meta: Option<String>,
+    /// Whether the server ended the body with a TLS close_notify, once it was read. Without it,
+    /// the connection may have been cut in the middle of the body.
+    pub close_notify: Option<bool>,
+}
+
+impl ResponseInfo {
+    pub fn possibly_truncated(&self) -> bool {
+        self.close_notify == Some(false)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ClientOptions {
+    /// Bodies bigger than this are written to a temporary file instead of being kept in memory
+    pub spill_threshold_bytes: usize,
+    /// Address to connect to for a host, like `"example.org" = "192.0.2.10:1965"`. The host is
+    /// still used for SNI and in the request, which helps testing a server before a DNS change.
+    pub connect_overrides: HashMap<String, String>,
+    /// Unix socket to connect to for a host, TLS is still used over it
+    pub unix_sockets: HashMap<String, PathBuf>,
+    /// Minimum time between two requests to the same host. Requests made sooner wait their turn.
+    pub min_request_interval_ms: u64,
+    /// Time the server has to answer with the response header, 0 to wait forever
+    pub header_timeout_ms: u64,
+    /// Time to wait for more of the body before giving up, 0 to wait forever
+    pub body_timeout_ms: u64,
+    /// Bodies are cut after this many bytes, 0 to receive them whole. Bodies bigger than
+    /// `spill_threshold_bytes` go to disk as they arrive, so they do not need to fit in memory.
+    pub max_body_bytes: u64,
+    /// Whether the browser follows redirects without showing them. Commands decide for
+    /// themselves.
+    pub follow_redirects: bool,
+    /// Whether servers with TLS 1.2 only are accepted
+    pub tls_mode: TlsMode,
+    /// Accept a meta longer than the 1024 bytes allowed, with a warning in the page info
+    pub permissive_meta: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            spill_threshold_bytes: 1024 * 1024 * 8,
+            connect_overrides: HashMap::new(),
+            unix_sockets: HashMap::new(),
+            min_request_interval_ms: 200,
+            header_timeout_ms: 10_000,
+            body_timeout_ms: 30_000,
+            max_body_bytes: 0,
+            follow_redirects: true,
+            tls_mode: TlsMode::default(),
+            permissive_meta: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Certificates {
     pub cert_file: String,
     pub key_file: String,
 }
 
-impl Client {
-    pub fn new(auto_redirect: bool, certificates: Option<Certificates>) -> Self {
+impl Default for Certificates {
+    fn default() -> Self {
+        Self {
+            cert_file: "cert.pem".to_string(),
+            key_file: "key.pem".to_string(),
+        }
+    }
+}
+
+/// A client certificate with its private key, checked to go together
+pub struct Identity {
+    /// What servers are told the certificate is, to show where it was sent
+    name: String,
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+}
+
+impl Identity {
+    /// Loads a certificate, with the passphrase its key was encrypted with if there is one
+    pub fn load(certificates: &Certificates, passphrase: Option<&Passphrase>) -> Result<Self> {
+        let Certificates {
+            cert_file,
+            key_file,
+        } = certificates;
+        let cert_chain = CertificateDer::pem_file_iter(cert_file)
+            .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| anyhow!("cert_file: {cert_file}: {}", pem_error(err)))?;
+        let Some(certificate) = cert_chain.first() else {
+            bail!("cert_file: {cert_file} has no certificate");
+        };
+        let name = identity_name(certificate, cert_file);
+        let key = secrets::read_secret(Path::new(key_file), passphrase)
+            .map_err(|err| anyhow!("key_file: {key_file}: {err}"))?;
+        let key = PrivateKeyDer::from_pem_slice(&key)
+            .map_err(|err| anyhow!("key_file: {key_file}: {}", pem_error(err)))?;
+        let signing_key = default_provider()
+            .key_provider
+            .load_private_key(key.clone_key())
+            .map_err(|err| anyhow!("key_file: {key_file}: {err}"))?;
+        CertifiedKey::new(cert_chain.clone(), signing_key)
+            .keys_match()
+            .map_err(|_| anyhow!("key_file: {key_file} is not the key of {cert_file}"))?;
+        Ok(Self {
+            name,
+            cert_chain,
+            key,
+        })
+    }
+
+    /// Creates a self signed certificate with `name` as its common name, which is what capsules
+    /// show, and writes it to the files of `certificates`, the key encrypted with the passphrase
+    /// if there is one. Files already there are kept with an `.old` extension.
+    pub fn create(
+        name: &str,
+        certificates: &Certificates,
+        passphrase: Option<&Passphrase>,
+    ) -> Result<Self> {
+        let key_pair = rcgen::KeyPair::generate()?;
+        let mut params = rcgen::CertificateParams::default();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, name);
+        let certificate = params.self_signed(&key_pair)?;
+        for file in [&certificates.cert_file, &certificates.key_file] {
+            if Path::new(file).exists() {
+                fs::rename(file, format!("{file}.old"))?;
+            }
+        }
+        fs::write(&certificates.cert_file, certificate.pem())?;
+        secrets::write_secret(
+            Path::new(&certificates.key_file),
+            key_pair.serialize_pem().as_bytes(),
+            passphrase,
+        )?;
+        Self::load(certificates, passphrase)
+    }
+}
+
+impl Clone for Identity {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            cert_chain: self.cert_chain.clone(),
+            key: self.key.clone_key(),
+        }
+    }
+}
+
+/// The PEM errors of missing files only tell that the file could not be read
+fn pem_error(err: rustls::pki_types::pem::Error) -> String {
+    match err {
+        rustls::pki_types::pem::Error::Io(err) => err.to_string(),
+        rustls::pki_types::pem::Error::NoItemsFound => "no PEM data found".to_string(),
+        err => err.to_string(),
+    }
+}
+
+/// The TLS configuration, which changes with the client certificate
+struct Tls {
+    config: Arc<ClientConfig>,
+    /// Name of the client certificate sent to servers asking for one, which Misfin requires
+    identity: Option<String>,
+}
+
+impl Tls {
+    fn new(identity: Option<Identity>, known_hosts: Arc<KnownHosts>, mode: TlsMode) -> Self {
         let root_store = rustls::RootCertStore { roots: Vec::new() };
-        let config_builder = rustls::ClientConfig::builder().with_root_certificates(root_store);
-        let mut config = if let Some(ceritificates) = certificates {
-            let cert_chain = CertificateDer::pem_file_iter("cert.pem")
-                .expect("Error opening certificate")
-                .map(|result| result.unwrap())
-                .collect();
-            config_builder
-                .with_client_auth_cert(
-                    cert_chain,
-                    PrivateKeyDer::from_pem_file("key.pem").expect("Error loading private key"),
-                )
-                .expect("Error opening client auth")
-        } else {
-            config_builder.with_no_client_auth()
+        let versions = match mode {
+            TlsMode::Compat => rustls::ALL_VERSIONS,
+            TlsMode::Strict => &[&rustls::version::TLS13],
+        };
+        let config_builder = rustls::ClientConfig::builder_with_protocol_versions(versions)
+            .with_root_certificates(root_store);
+        let (mut config, name) = match identity {
+            Some(identity) => (
+                config_builder
+                    .with_client_auth_cert(identity.cert_chain, identity.key)
+                    .expect("The identity was checked when loaded"),
+                Some(identity.name),
+            ),
+            None => (config_builder.with_no_client_auth(), None),
         };
         config
             .dangerous()
-            .set_certificate_verifier(Arc::new(TofuCertVerifier::new(default_provider())));
+            .set_certificate_verifier(Arc::new(TofuCertVerifier::new(
+                default_provider(),
+                known_hosts,
+            )));
+        Self {
+            config: Arc::new(config),
+            identity: name,
+        }
+    }
+}
+
+impl Client {
+    /// `follow_redirects` is used in place of the one of `options`, as commands decide for
+    /// themselves.
+    pub fn new(
+        follow_redirects: bool,
+        identity: Option<Identity>,
+        mut options: ClientOptions,
+        known_hosts: Arc<KnownHosts>,
+    ) -> Self {
+        options.follow_redirects = follow_redirects;
+        let tls = Tls::new(identity, known_hosts.clone(), options.tls_mode);
         Self {
-            client_config: Arc::new(config),
-            auto_redirect,
+            tls: Arc::new(RwLock::new(tls)),
+            scoped_tls: Arc::new(RwLock::new(Vec::new())),
+            options: Arc::new(RwLock::new(options)),
+            known_hosts,
+            rate_limiter: Arc::new(RateLimiter::default()),
+            identified_hosts: Arc::new(Mutex::new(HashSet::new())),
+            responses: Arc::new(Mutex::new(HashMap::new())),
+            confirmed_messages: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 
-    pub fn request(&self, mut url: Url) -> Result<GeminiResponse> {
+    /// Uses `identity` as the client certificate of this client and all its clones, for the
+    /// next connections
+    pub fn set_identity(&self, identity: Identity) {
+        let mode = self.options().tls_mode;
+        let tls = Tls::new(Some(identity), self.known_hosts.clone(), mode);
+        *self.tls.write().expect("Poisoned TLS configuration") = tls;
+    }
+
+    /// Uses each identity for the URLs under its prefix, in place of the client
+    /// certificate of the configuration, for the next connections
+    pub fn set_scoped_identities(&self, identities: Vec<(String, Identity)>) {
+        let mode = self.options().tls_mode;
+        let scoped = identities
+            .into_iter()
+            .map(|(prefix, identity)| {
+                let tls = Tls::new(Some(identity), self.known_hosts.clone(), mode);
+                (prefix, tls)
+            })
+            .collect();
+        *self.scoped_tls.write().expect("Poisoned TLS configuration") = scoped;
+    }
+
+    /// The TLS configuration for `url`, with the identity of the longest prefix covering it, and
+    /// the name of the client certificate it sends
+    fn tls_for(&self, url: &Url) -> (Arc<ClientConfig>, Option<String>) {
+        let scoped = self.scoped_tls.read().expect("Poisoned TLS configuration");
+        let tls = scoped
+            .iter()
+            .filter(|(prefix, _)| in_scope(prefix, url))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, tls)| tls);
+        match tls {
+            Some(tls) => (tls.config.clone(), tls.identity.clone()),
+            None => {
+                let tls = self.tls.read().expect("Poisoned TLS configuration");
+                (tls.config.clone(), tls.identity.clone())
+            }
+        }
+    }
+
+    /// `on_progress` is called every time a chunk of a success response body arrives.
+    pub fn request(
+        &self,
+        url: Url,
+        on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse> {
+        self.request_redirected(url, on_progress, 0)
+    }
+
+    /// Requests `url`, reached by following `redirects` redirects
+    fn request_redirected(
+        &self,
+        mut url: Url,
+        on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+        redirects: usize,
+    ) -> Result<GeminiResponse> {
         let port = url.port().unwrap_or(1965);
         if url.scheme() != "gemini" {
             return Err(anyhow!("Invalid scheme"));
@@ -60,167 +374,966 @@ impl Client {
         if url.path().is_empty() {
             url.set_path("/");
         }
+        // Fragments only matter to the client
+        url.set_fragment(None);
+        // Refused here, as servers answer longer ones with errors that do not tell why
+        if url.as_str().len() > MAX_REQUEST_URL_SIZE {
+            bail!(
+                "The URL is {} bytes long, requests are at most {MAX_REQUEST_URL_SIZE} bytes",
+                url.as_str().len()
+            );
+        }
         let domain = url.domain().ok_or(anyhow!("Missing domain"))?;
-        let mut conn = rustls::ClientConnection::new(
-            self.client_config.clone(),
-            domain.to_string().try_into()?,
-        )?;
-        let mut socket = TcpStream::connect(format!("{domain}:{port}"))?;
-        tracing::debug!("Connected to {domain}:{port}");
+        let interval = Duration::from_millis(self.options().min_request_interval_ms);
+        let wait = self.rate_limiter.reserve(domain, interval, Instant::now());
+        if !wait.is_zero() {
+            let host = logging::host(domain);
+            tracing::debug!("Waiting {wait:?} before requesting {host} again");
+            thread::sleep(wait);
+        }
+        let (config, resolver, identity) = self.connection_config(&url);
+        let mut conn = rustls::ClientConnection::new(config, domain.to_string().try_into()?)?;
+        let mut socket = self.connect(domain, port)?;
+        let header_timeout = Duration::from_millis(self.options().header_timeout_ms);
+        socket.set_read_timeout(timeout(header_timeout))?;
         let mut tls = rustls::Stream::new(&mut conn, &mut socket);
         tracing::debug!("Created TLS connection");
-        tls.write_all(url.as_str().as_bytes())?;
-        tls.write_all(b"\r\n")?;
-        tls.flush()?;
-        tracing::debug!("Sent request {url}");
-        let mut read = BufReader::new(tls);
-        let mut status = Vec::with_capacity(3);
-        read.read_until(b' ', &mut status)?;
-        let mut buffer = Vec::with_capacity(1024);
-        read.take(1024 * 1024 * 64).read_to_end(&mut buffer)?;
-        tracing::debug!("Read response");
-        Ok(match status.as_slice() {
-            b"10 " | b"11 " => {
-                let status = InputStatus::try_from(status.as_slice())?;
-                GeminiResponse::Input {
-                    status,
-                    prompt: String::from_utf8(buffer)?.trim().to_string(),
-                }
+        // Writing the request completes the handshake, where the certificate is verified
+        if let Err(err) = tls.write_all(url.as_str().as_bytes()) {
+            if let Some(mismatch) = self.known_hosts.take_mismatch(domain) {
+                return Err(mismatch.into());
             }
-            b"20 " => {
-                let mut cursor = Cursor::new(buffer);
-                let mut header = String::new();
-                let mut body = String::new();
-                cursor.read_line(&mut header)?;
-                cursor.read_to_string(&mut body)?;
-                GeminiResponse::Success {
-                    mime: header.trim().to_string(),
-                    body: body.into(),
-                }
+            if self.options().tls_mode == TlsMode::Strict && is_version_refused(&err) {
+                bail!(
+                    "{err}, the server may not have TLS 1.3, which client.tls_mode = \"strict\" \
+                     requires"
+                );
             }
-            b"30 " | b"31 " => {
-                let status = RedirectStatus::try_from(status.as_slice())?;
-                let string = String::from_utf8(buffer)?;
-                let url = if string.starts_with("gemini://") {
-                    Url::parse(string.trim())?
+            return Err(err.into());
+        }
+        tls.write_all(b"\r\n")?;
+        tls.flush()?;
+        self.remember_certificate(domain, tls.conn);
+        tracing::debug!("Sent request {}", logging::url(&url));
+        let session = tls_session(tls.conn);
+        if identity.is_some() && resolver.asked.load(Ordering::Relaxed) {
+            let mut hosts = self.identified_hosts.lock().expect("Poisoned lock");
+            hosts.insert(format!("{domain}:{port}"));
+        }
+        let mut info = ResponseInfo {
+            tls: session,
+            ..ResponseInfo::default()
+        };
+        let (status, meta) = read_header(
+            &mut tls,
+            Instant::now() + header_timeout,
+            self.options().permissive_meta,
+            &mut info.warnings,
+        )?;
+        // The meta of redirects and prompts tells where the user goes
+        if logging::full() {
+            tracing::debug!("Read header {status} {meta}");
+        } else {
+            tracing::debug!("Read header {status}");
+        }
+        let status = Status::try_from(status.as_str())?;
+        info.meta = Some(meta.clone());
+        let parsed = meta.trim().to_string();
+        if status == Status::Success {
+            let body_timeout = Duration::from_millis(self.options().body_timeout_ms);
+            tls.sock.set_read_timeout(timeout(body_timeout))?;
+            let max_body_bytes = match self.options().max_body_bytes {
+                0 => u64::MAX,
+                max => max,
+            };
+            let mut close_notify = true;
+            let body = self.read_body(
+                tls.take(max_body_bytes),
+                &parsed,
+                &mut close_notify,
+                on_progress,
+            );
+            info.close_notify = Some(close_notify);
+            self.record_response(&url, info);
+            let body = body?;
+            tracing::debug!("Read response");
+            return Ok(GeminiResponse::Success {
+                mime: parsed,
+                body,
+                meta,
+            });
+        }
+        // Only successes have a body, anything else the server sends is left unread. Only what
+        // came with the header is looked at, so the response is not held up waiting for more.
+        if has_trailing_data(&mut tls.conn.reader()) {
+            info.warnings.push(format!(
+                "The {} response has a body, which was ignored",
+                status.code()
+            ));
+        }
+        self.record_response(&url, info);
+        Ok(match status {
+            Status::Input(status) => GeminiResponse::Input {
+                status,
+                prompt: parsed,
+                meta,
+            },
+            Status::Redirect(status) => {
+                let url = if parsed.starts_with("gemini://") {
+                    Url::parse(&parsed)?
                 } else {
-                    url.join(string.trim())?
+                    url.join(&parsed)?
                 };
 
-                if self.auto_redirect {
-                    return self.request(url);
+                if self.follows_redirects() {
+                    // A loop of redirects would never end
+                    if redirects >= MAX_REDIRECTS {
+                        bail!("Stopped after {MAX_REDIRECTS} redirects in a row, at {url}");
+                    }
+                    return self.request_redirected(url, on_progress, redirects + 1);
                 }
-                GeminiResponse::Redirect { status, url }
+                GeminiResponse::Redirect { status, url, meta }
+            }
+            Status::TemporaryFailure(status) => GeminiResponse::TemporaryFailure { status, meta },
+            Status::PermanentFailure(status) => GeminiResponse::PermanentFailure { status, meta },
+            Status::ClientCertificateError(status) => {
+                GeminiResponse::ClientCertificateError { status, meta }
+            }
+            // Read above with their body
+            Status::Success => bail!("Unexpected success response"),
+        })
+    }
+
+    /// Like `request`, requesting again up to `retries` times while the body may be cut short,
+    /// for copies that are kept. Also returns whether the last body may still be cut short.
+    pub fn request_whole(&self, url: Url, retries: usize) -> Result<(GeminiResponse, bool)> {
+        for retry in 0.. {
+            let response = self.request(url.clone(), &mut |_| Ok(()))?;
+            let truncated = self
+                .response_info(&url)
+                .is_some_and(|info| info.possibly_truncated());
+            if !truncated || retry == retries {
+                return Ok((response, truncated));
             }
-            b"40 " | b"41 " | b"42 " | b"43 " | b"44 " => {
-                let status = TemporaryFailureStatus::try_from(status.as_slice())?;
-                let error_msg = String::from_utf8(buffer)?;
-                let trimmed = error_msg.trim();
-                GeminiResponse::TemporaryFailure {
-                    status,
-                    error_msg: if trimmed.is_empty() {
-                        None
-                    } else {
-                        Some(trimmed.to_string())
-                    },
+            tracing::debug!(
+                "Requesting {} again, its body may be cut short",
+                logging::url(&url)
+            );
+        }
+        unreachable!("The loop returns at the last retry")
+    }
+
+    /// The certificate the server of `url` presents, without sending a request.
+    pub fn peer_certificate(&self, url: &Url) -> Result<Vec<u8>> {
+        let domain = url.domain().ok_or(anyhow!("Missing domain"))?;
+        let (config, _) = self.tls_for(url);
+        let mut conn = rustls::ClientConnection::new(config, domain.to_string().try_into()?)?;
+        let mut socket = self.connect(domain, url.port().unwrap_or(1965))?;
+        while conn.is_handshaking() {
+            if let Err(err) = conn.complete_io(&mut socket) {
+                if let Some(mismatch) = self.known_hosts.take_mismatch(domain) {
+                    return Err(mismatch.into());
                 }
+                return Err(err.into());
             }
-            b"50 " | b"51 " | b"52 " | b"53 " | b"59 " => {
-                let status = PermanentFailureStatus::try_from(status.as_slice())?;
-                let error_msg = String::from_utf8(buffer)?;
-                let trimmed = error_msg.trim();
-                GeminiResponse::PermanentFailure {
-                    status,
-                    error_msg: if trimmed.is_empty() {
-                        None
-                    } else {
-                        Some(trimmed.to_string())
-                    },
+        }
+        self.remember_certificate(domain, &conn);
+        conn.peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .map(|certificate| certificate.to_vec())
+            .ok_or(anyhow!("The server sent no certificate"))
+    }
+
+    /// Remembers the certificate of a host seen for the first time, now that the handshake
+    /// succeeded. It is not while it is verified, as the signature of the handshake is checked
+    /// after that.
+    fn remember_certificate(&self, domain: &str, conn: &rustls::ClientConnection) {
+        if let Some(certificate) = conn
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+        {
+            self.known_hosts
+                .handshake_completed(domain, certificate.as_ref());
+        }
+    }
+
+    /// Fingerprint of the certificate `host` is known by, once it was connected to
+    pub fn fingerprint(&self, host: &str) -> Option<String> {
+        self.known_hosts.get(host).map(|known| known.fingerprint)
+    }
+
+    pub fn options(&self) -> RwLockReadGuard<'_, ClientOptions> {
+        self.options.read().expect("Poisoned client options")
+    }
+
+    /// Changes the options of the client and of all its clones, for the next requests
+    pub fn update_options(&self, update: impl FnOnce(&mut ClientOptions)) {
+        update(&mut self.options.write().expect("Poisoned client options"));
+    }
+
+    pub fn follows_redirects(&self) -> bool {
+        self.options().follow_redirects
+    }
+
+    pub fn set_follow_redirects(&self, follow: bool) {
+        self.update_options(|options| options.follow_redirects = follow);
+    }
+
+    /// Name of the client certificate of the configuration, if there is one
+    pub fn identity(&self) -> Option<String> {
+        self.tls
+            .read()
+            .expect("Poisoned TLS configuration")
+            .identity
+            .clone()
+    }
+
+    /// Name of the client certificate sent for `url` to servers asking for one, if any
+    pub fn identity_of(&self, url: &Url) -> Option<String> {
+        self.tls_for(url).1
+    }
+
+    /// Whether the server of `url` asked for the client certificate and was sent it
+    pub fn identity_sent_to(&self, url: &Url) -> bool {
+        let (Some(host), Some(_)) = (url.host_str(), self.identity_of(url)) else {
+            return false;
+        };
+        let port = url.port().unwrap_or(1965);
+        let hosts = self.identified_hosts.lock().expect("Poisoned lock");
+        hosts.contains(&format!("{host}:{port}"))
+    }
+
+    /// Hosts and ports of the servers that were sent the client certificate, sorted
+    pub fn identified_hosts(&self) -> Vec<String> {
+        let hosts = self.identified_hosts.lock().expect("Poisoned lock");
+        let mut hosts: Vec<_> = hosts.iter().cloned().collect();
+        hosts.sort();
+        hosts
+    }
+
+    /// How the last response to `url` was received, when it was requested in this session
+    pub fn response_info(&self, url: &Url) -> Option<ResponseInfo> {
+        // As it was requested
+        let mut url = url.clone();
+        url.set_fragment(None);
+        if url.path().is_empty() {
+            url.set_path("/");
+        }
+        let responses = self.responses.lock().expect("Poisoned lock");
+        responses.get(url.as_str()).cloned()
+    }
+
+    fn record_response(&self, url: &Url, info: ResponseInfo) {
+        if !info.warnings.is_empty() {
+            let warnings = &info.warnings;
+            tracing::debug!("Protocol warnings for {}: {warnings:?}", logging::url(url));
+        }
+        let mut responses = self.responses.lock().expect("Poisoned lock");
+        responses.insert(url.to_string(), info);
+    }
+
+    /// A configuration for a single connection to `url`, to know if the server asked for the
+    /// client certificate during its handshake, with the name of that certificate.
+    fn connection_config(
+        &self,
+        url: &Url,
+    ) -> (Arc<ClientConfig>, Arc<IdentityResolver>, Option<String>) {
+        let (client_config, identity) = self.tls_for(url);
+        let resolver = Arc::new(IdentityResolver {
+            inner: client_config.client_auth_cert_resolver.clone(),
+            asked: AtomicBool::new(false),
+        });
+        let mut config = (*client_config).clone();
+        config.client_auth_cert_resolver = resolver.clone();
+        (Arc::new(config), resolver, identity)
+    }
+
+    /// Lets the message in the query of `url` be sent, once
+    pub fn confirm_misfin(&self, url: &Url) {
+        self.confirmed_messages
+            .lock()
+            .expect("Poisoned confirmed messages")
+            .insert(url.to_string());
+    }
+
+    /// Whether the user confirmed sending the message in the query of `url`, which they have to
+    /// do again to send it another time
+    pub fn take_misfin_confirmation(&self, url: &Url) -> bool {
+        self.confirmed_messages
+            .lock()
+            .expect("Poisoned confirmed messages")
+            .remove(url.as_str())
+    }
+
+    /// Sends `message` to a `misfin://user@host` address, signed with the client certificate.
+    /// Returns the fingerprint of the recipient's certificate the server answered with.
+    pub fn send_misfin(&self, address: &Url, message: &str) -> Result<String> {
+        let (config, identity) = self.tls_for(address);
+        if identity.is_none() {
+            bail!("Sending Misfin messages needs a client certificate, set cert_file and key_file");
+        }
+        let domain = address.domain().ok_or(anyhow!("Missing domain"))?;
+        let mut recipient = address.clone();
+        recipient.set_query(None);
+        recipient.set_fragment(None);
+        let request = format!("{recipient} {message}\r\n");
+        if request.len() > MAX_MISFIN_REQUEST_SIZE {
+            bail!("Message too long, Misfin requests are at most {MAX_MISFIN_REQUEST_SIZE} bytes");
+        }
+        let mut conn = rustls::ClientConnection::new(config, domain.to_string().try_into()?)?;
+        let mut socket = self.connect(domain, address.port().unwrap_or(1958))?;
+        let header_timeout = Duration::from_millis(self.options().header_timeout_ms);
+        socket.set_read_timeout(timeout(header_timeout))?;
+        let mut tls = rustls::Stream::new(&mut conn, &mut socket);
+        if let Err(err) = tls.write_all(request.as_bytes()) {
+            if let Some(mismatch) = self.known_hosts.take_mismatch(domain) {
+                return Err(mismatch.into());
+            }
+            return Err(err.into());
+        }
+        tls.flush()?;
+        self.remember_certificate(domain, tls.conn);
+        let (status, meta) = read_header(
+            &mut tls,
+            Instant::now() + header_timeout,
+            self.options().permissive_meta,
+            &mut Vec::new(),
+        )?;
+        // The meta of a refusal or a move may name the mailbox
+        if logging::full() {
+            tracing::debug!("Misfin answer {status} {meta}");
+        } else {
+            tracing::debug!("Misfin answer {status}");
+        }
+        let meta = meta.trim().to_string();
+        match status.as_bytes() {
+            [b'2', _] => Ok(meta),
+            [b'3', _] => bail!("The mailbox moved to {meta}"),
+            _ if meta.is_empty() => bail!("Message refused with status {status}"),
+            _ => bail!("Message refused with status {status}: {meta}"),
+        }
+    }
+
+    fn connect(&self, domain: &str, port: u16) -> Result<Transport> {
+        #[cfg(unix)]
+        let socket_path = self.options().unix_sockets.get(domain).cloned();
+        #[cfg(unix)]
+        if let Some(path) = socket_path {
+            let socket = UnixStream::connect(&path)?;
+            tracing::debug!("Connected to {path:?} for {}", logging::host(domain));
+            return Ok(Transport::Unix(socket));
+        }
+        let address = match self.options().connect_overrides.get(domain) {
+            Some(address) => address.clone(),
+            None => format!("{domain}:{port}"),
+        };
+        let addresses: Vec<_> = match address.to_socket_addrs() {
+            // Addresses without a port, from the overrides, are not a resolution failure
+            Err(err) if err.kind() == ErrorKind::InvalidInput => return Err(err.into()),
+            Err(err) => {
+                tracing::debug!("Could not resolve {}: {err}", logging::host(domain));
+                return Err(HostNotFound(domain.to_string()).into());
+            }
+            Ok(addresses) => addresses.collect(),
+        };
+        if addresses.is_empty() {
+            return Err(HostNotFound(domain.to_string()).into());
+        }
+        let socket = TcpStream::connect(&addresses[..])?;
+        tracing::debug!(
+            "Connected to {} for {}",
+            logging::host(&address),
+            logging::host(domain)
+        );
+        Ok(Transport::Tcp(socket))
+    }
+
+    /// Reads a success response body. An end without TLS close_notify is taken as the end of the
+    /// body, with `close_notify` set to false, as many servers do not send it.
+    fn read_body(
+        &self,
+        mut read: impl Read,
+        mime: &str,
+        close_notify: &mut bool,
+        on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<ResponseBody> {
+        let mut read = |chunk: &mut [u8]| match read.read(chunk) {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                *close_notify = false;
+                Ok(0)
+            }
+            read => read,
+        };
+        let threshold = self.options().spill_threshold_bytes;
+        let mut buffer = Vec::with_capacity(1024);
+        let mut chunk = vec![0; CHUNK_SIZE];
+        while buffer.len() <= threshold {
+            let read = match read(&mut chunk) {
+                Ok(read) => read,
+                Err(source) => {
+                    let body = ResponseBody::Bytes(buffer);
+                    return Err(TruncatedBody { body, source }.into());
                 }
+            };
+            if read == 0 {
+                return Ok(ResponseBody::Bytes(buffer));
             }
-            b"60 " | b"61 " | b"62 " => {
-                let status = ClientCertificateErrorStatus::try_from(status.as_slice())?;
-                let error_msg = String::from_utf8(buffer)?;
-                let trimmed = error_msg.trim();
-                GeminiResponse::ClientCertificateError {
-                    status,
-                    error_msg: if trimmed.is_empty() {
-                        None
-                    } else {
-                        Some(trimmed.to_string())
-                    },
+            buffer.extend_from_slice(&chunk[..read]);
+            on_progress(BodyProgress {
+                mime,
+                received: buffer.len() as u64,
+                bytes: &buffer,
+            })?;
+        }
+        let temp_file = TempFile::new();
+        let mut file = File::create(temp_file.path())?;
+        file.write_all(&buffer)?;
+        let mut len = buffer.len() as u64;
+        drop(buffer);
+        loop {
+            let read = match read(&mut chunk) {
+                Ok(read) => read,
+                Err(source) => {
+                    let body = ResponseBody::File {
+                        file: Arc::new(temp_file),
+                        len,
+                    };
+                    return Err(TruncatedBody { body, source }.into());
                 }
+            };
+            if read == 0 {
+                break;
             }
-            other => bail!("Invalid response code {}", String::from_utf8_lossy(other)),
+            file.write_all(&chunk[..read])?;
+            len += read as u64;
+            on_progress(BodyProgress {
+                mime,
+                received: len,
+                bytes: &[],
+            })?;
+        }
+        tracing::debug!("Spilled {len} bytes body to {:?}", temp_file.path());
+        Ok(ResponseBody::File {
+            file: Arc::new(temp_file),
+            len,
         })
     }
 }
 
+/// Reads the `<STATUS><SPACE><META><CR><LF>` line, returning the status and the meta. What it
+/// tolerates that the specification does not allow is added to `warnings`, like a meta longer
+/// than 1024 bytes when `permissive`.
+fn read_header(
+    read: &mut impl Read,
+    deadline: Instant,
+    permissive: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(String, String)> {
+    let max_size = if permissive {
+        MAX_PERMISSIVE_HEADER_SIZE
+    } else {
+        MAX_HEADER_SIZE
+    };
+    let mut line = Vec::with_capacity(64);
+    let mut byte = [0];
+    while !line.ends_with(b"\n") {
+        if line.len() == max_size {
+            return Err(MetaTooLong { permissive }.into());
+        }
+        if Instant::now() > deadline {
+            bail!("Timed out waiting for the response header");
+        }
+        let read = match read.read(&mut byte) {
+            // What rustls answers when the server does not close the TLS session first
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => 0,
+            read => read?,
+        };
+        if read == 0 {
+            return Err(NoResponse {
+                received: line.len(),
+            }
+            .into());
+        }
+        line.push(byte[0]);
+    }
+    if !line.ends_with(b"\r\n") {
+        warnings.push("The response header ends with LF instead of CRLF".to_string());
+    }
+    let line = String::from_utf8(line)?;
+    let line = line.trim_end_matches(['\r', '\n']);
+    let Some((status, meta)) = line
+        .split_at_checked(2)
+        .filter(|(status, _)| status.chars().all(|c| c.is_ascii_digit()))
+    else {
+        bail!("Invalid response header {line:?}");
+    };
+    // Only the space separating the status, the rest is kept as sent
+    let meta = meta.strip_prefix(' ').unwrap_or(meta);
+    if meta.len() > MAX_META_SIZE {
+        if !permissive {
+            return Err(MetaTooLong { permissive }.into());
+        }
+        warnings.push(format!(
+            "The meta is {} bytes, longer than the {MAX_META_SIZE} allowed",
+            meta.len()
+        ));
+    }
+    Ok((status.to_string(), meta.to_string()))
+}
+
+/// Whether anything follows the header of a response that should end with it. A closed
+/// connection, or one with nothing received yet, has nothing more.
+fn has_trailing_data(reader: &mut impl Read) -> bool {
+    let mut byte = [0];
+    matches!(reader.read(&mut byte), Ok(1))
+}
+
+/// The TLS version and cipher suite of an established connection
+fn tls_session(conn: &rustls::ClientConnection) -> Option<String> {
+    let version = match conn.protocol_version()? {
+        rustls::ProtocolVersion::TLSv1_2 => "TLS 1.2".to_string(),
+        rustls::ProtocolVersion::TLSv1_3 => "TLS 1.3".to_string(),
+        version => format!("{version:?}"),
+    };
+    let suite = conn.negotiated_cipher_suite()?.suite();
+    Some(format!("{version}, {suite:?}"))
+}
+
+/// Whether the handshake failed as the server and the client have no TLS version in common
+fn is_version_refused(err: &io::Error) -> bool {
+    matches!(
+        err.get_ref()
+            .and_then(|err| err.downcast_ref::<rustls::Error>()),
+        Some(
+            rustls::Error::AlertReceived(rustls::AlertDescription::ProtocolVersion)
+                | rustls::Error::PeerIncompatible(_)
+        )
+    )
+}
+
+/// Whether the identity used for `prefix` is sent to `url`: same scheme, host and port, and a
+/// path under the one of the prefix, cut at `/`. An identity for `gemini://host/app` is not sent
+/// to `gemini://host/application` nor to `gemini://host.evil/`.
+fn in_scope(prefix: &str, url: &Url) -> bool {
+    let Ok(prefix) = Url::parse(prefix) else {
+        return false;
+    };
+    if prefix.scheme() != url.scheme()
+        || prefix.host_str() != url.host_str()
+        || prefix.port_or_known_default() != url.port_or_known_default()
+    {
+        return false;
+    }
+    let (scope, path) = (prefix.path(), url.path());
+    match path.strip_prefix(scope) {
+        Some(rest) => rest.is_empty() || scope.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Socket timeouts of zero mean no timeout
+fn timeout(duration: Duration) -> Option<Duration> {
+    (!duration.is_zero()).then_some(duration)
+}
+
+/// Spaces out requests to the same host.
+#[derive(Default)]
+struct RateLimiter {
+    /// When the next request to each host may be sent, for the hosts that have to wait
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    /// Takes the next free slot for `host`, `interval` after the last one, returning how long to
+    /// wait for it. The interval is given each time, so a change of the setting applies at once.
+    fn reserve(&self, host: &str, interval: Duration, now: Instant) -> Duration {
+        let mut next = self.next.lock().expect("Poisoned rate limiter");
+        // The hosts that can be requested right away are forgotten
+        next.retain(|_, slot| *slot > now);
+        if interval.is_zero() {
+            return Duration::ZERO;
+        }
+        let slot = next.get(host).map_or(now, |next| (*next).max(now));
+        next.insert(host.to_string(), slot + interval);
+        slot - now
+    }
+}
+
+enum Transport {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Transport {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Transport::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Transport::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Transport::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Transport::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Transport::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Transport::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Transport::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Transport::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// The address of a host could not be found, which usually means it is misspelled, gone or
+/// that the network is down, unlike other connection errors.
+#[derive(Debug, Clone)]
+pub struct HostNotFound(pub String);
+
+impl Display for HostNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Host {} not found", self.0)
+    }
+}
+
+impl std::error::Error for HostNotFound {}
+
+/// The server closed the connection before sending a whole response header, which is not the
+/// fault of the page asked for and may not happen again.
+#[derive(Debug, Clone)]
+pub struct NoResponse {
+    /// Bytes of the header received before it closed
+    pub received: usize,
+}
+
+impl Display for NoResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.received {
+            0 => write!(f, "The server closed the connection without responding"),
+            received => write!(
+                f,
+                "The server closed the connection after {received} bytes, before the end of \
+                 the response header"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NoResponse {}
+
+/// The server sent a meta line longer than allowed, which is refused rather than guessing where
+/// the meta ends.
+#[derive(Debug, Clone)]
+pub struct MetaTooLong {
+    /// Whether the longer limit of `client.permissive_meta` was exceeded
+    pub permissive: bool,
+}
+
+impl Display for MetaTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.permissive {
+            write!(
+                f,
+                "The response header is longer than {MAX_PERMISSIVE_HEADER_SIZE} bytes"
+            )
+        } else {
+            write!(
+                f,
+                "The response meta is longer than the {MAX_META_SIZE} bytes allowed, \
+                 client.permissive_meta = true accepts it"
+            )
+        }
+    }
+}
+
+impl std::error::Error for MetaTooLong {}
+
+/// A success response whose body was cut short, by a disconnection or a timeout, with what was
+/// received of it
+#[derive(Debug)]
+pub struct TruncatedBody {
+    pub body: ResponseBody,
+    pub source: io::Error,
+}
+
+impl Display for TruncatedBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Body cut short after {} bytes: {}",
+            self.body.len(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for TruncatedBody {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+pub struct BodyProgress<'a> {
+    pub mime: &'a str,
+    /// Number of body bytes received so far
+    pub received: u64,
+    /// Everything received so far, while the body is still kept in memory
+    pub bytes: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub enum ResponseBody {
+    Bytes(Vec<u8>),
+    File { file: Arc<TempFile>, len: u64 },
+}
+
+impl ResponseBody {
+    pub fn len(&self) -> u64 {
+        match self {
+            ResponseBody::Bytes(bytes) => bytes.len() as u64,
+            ResponseBody::File { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the body to `path`, returning its size
+    pub fn save(&self, path: &Path) -> Result<u64> {
+        match self {
+            ResponseBody::Bytes(bytes) => fs::write(path, bytes)?,
+            ResponseBody::File { file, .. } => {
+                fs::copy(file.path(), path)?;
+            }
+        }
+        Ok(self.len())
+    }
+}
+
+/// A file in the temporary directory that is deleted when dropped.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    pub fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "taurus-{}-{}.body",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        Self {
+            path: std::env::temp_dir().join(name),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A response, with the fields parsed from its meta line next to the `meta` the server sent
 #[derive(Debug, Clone)]
 pub enum GeminiResponse {
     Input {
         status: InputStatus,
         prompt: String,
+        meta: String,
     },
     Success {
         mime: String,
-        body: Vec<u8>,
+        body: ResponseBody,
+        meta: String,
     },
     Redirect {
         status: RedirectStatus,
         url: Url,
+        meta: String,
     },
     TemporaryFailure {
         status: TemporaryFailureStatus,
-        error_msg: Option<String>,
+        meta: String,
     },
     PermanentFailure {
         status: PermanentFailureStatus,
-        error_msg: Option<String>,
+        meta: String,
     },
     ClientCertificateError {
         status: ClientCertificateErrorStatus,
-        error_msg: Option<String>,
+        meta: String,
     },
 }
 
-#[derive(Debug, Clone)]
-pub enum InputStatus {
-    Normal,
-    Sensitive,
-}
+impl GeminiResponse {
+    pub fn status(&self) -> Status {
+        match self {
+            GeminiResponse::Input { status, .. } => Status::Input(*status),
+            GeminiResponse::Success { .. } => Status::Success,
+            GeminiResponse::Redirect { status, .. } => Status::Redirect(*status),
+            GeminiResponse::TemporaryFailure { status, .. } => Status::TemporaryFailure(*status),
+            GeminiResponse::PermanentFailure { status, .. } => Status::PermanentFailure(*status),
+            GeminiResponse::ClientCertificateError { status, .. } => {
+                Status::ClientCertificateError(*status)
+            }
+        }
+    }
 
-impl TryFrom<&[u8]> for InputStatus {
-    type Error = anyhow::Error;
+    /// The two digits status the server sent
+    pub fn status_code(&self) -> u8 {
+        self.status().code()
+    }
 
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
-        Ok(match &value[0..2] {
-            b"10" => InputStatus::Normal,
-            b"11" => InputStatus::Sensitive,
-            _ => bail!("Invalid input status"),
-        })
+    /// The meta line exactly as the server sent it, after the space following the status
+    pub fn meta(&self) -> &str {
+        match self {
+            GeminiResponse::Input { meta, .. }
+            | GeminiResponse::Success { meta, .. }
+            | GeminiResponse::Redirect { meta, .. }
+            | GeminiResponse::TemporaryFailure { meta, .. }
+            | GeminiResponse::PermanentFailure { meta, .. }
+            | GeminiResponse::ClientCertificateError { meta, .. } => meta,
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum RedirectStatus {
-    Temporary,
-    Permanent,
+/// The status of a response, parsed once from its two digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Input(InputStatus),
+    Success,
+    Redirect(RedirectStatus),
+    TemporaryFailure(TemporaryFailureStatus),
+    PermanentFailure(PermanentFailureStatus),
+    ClientCertificateError(ClientCertificateErrorStatus),
 }
 
-impl TryFrom<&[u8]> for RedirectStatus {
+/// Every status with its code, the only place they are written
+const STATUSES: [(u8, Status); 18] = [
+    (10, Status::Input(InputStatus::Normal)),
+    (11, Status::Input(InputStatus::Sensitive)),
+    (20, Status::Success),
+    (30, Status::Redirect(RedirectStatus::Temporary)),
+    (31, Status::Redirect(RedirectStatus::Permanent)),
+    (
+        40,
+        Status::TemporaryFailure(TemporaryFailureStatus::Unspecified),
+    ),
+    (
+        41,
+        Status::TemporaryFailure(TemporaryFailureStatus::ServerUnavailable),
+    ),
+    (
+        42,
+        Status::TemporaryFailure(TemporaryFailureStatus::CGIError),
+    ),
+    (
+        43,
+        Status::TemporaryFailure(TemporaryFailureStatus::ProxyError),
+    ),
+    (
+        44,
+        Status::TemporaryFailure(TemporaryFailureStatus::SlowDown),
+    ),
+    (
+        50,
+        Status::PermanentFailure(PermanentFailureStatus::Unspecified),
+    ),
+    (
+        51,
+        Status::PermanentFailure(PermanentFailureStatus::NotFound),
+    ),
+    (52, Status::PermanentFailure(PermanentFailureStatus::Gone)),
+    (
+        53,
+        Status::PermanentFailure(PermanentFailureStatus::ProxyRequestRefused),
+    ),
+    (
+        59,
+        Status::PermanentFailure(PermanentFailureStatus::BadRequest),
+    ),
+    (
+        60,
+        Status::ClientCertificateError(ClientCertificateErrorStatus::Required),
+    ),
+    (
+        61,
+        Status::ClientCertificateError(ClientCertificateErrorStatus::NotAuthorized),
+    ),
+    (
+        62,
+        Status::ClientCertificateError(ClientCertificateErrorStatus::NotValid),
+    ),
+];
+
+impl Status {
+    pub fn code(self) -> u8 {
+        STATUSES
+            .iter()
+            .find(|(_, status)| *status == self)
+            .map(|(code, _)| *code)
+            .expect("Every status has a code")
+    }
+}
+
+impl TryFrom<&str> for Status {
     type Error = anyhow::Error;
 
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
-        Ok(match &value[0..2] {
-            b"30" => RedirectStatus::Temporary,
-            b"31" => RedirectStatus::Permanent,
-            _ => bail!("Invalid input status"),
-        })
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let Some(code) = Some(value)
+            .filter(|value| value.len() == 2 && value.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|value| value.parse::<u8>().ok())
+        else {
+            bail!("Invalid response code {value}");
+        };
+        // An unknown code is read as the first of its class, like 25 as 20
+        let find = |code: u8| STATUSES.iter().find(|(known, _)| *known == code);
+        find(code)
+            .or_else(|| find(code / 10 * 10))
+            .map(|(_, status)| *status)
+            .ok_or_else(|| anyhow!("Invalid response code {value}"))
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputStatus {
+    Normal,
+    Sensitive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectStatus {
+    Temporary,
+    Permanent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TemporaryFailureStatus {
     Unspecified,
     ServerUnavailable,
@@ -229,22 +1342,7 @@ pub enum TemporaryFailureStatus {
     SlowDown,
 }
 
-impl TryFrom<&[u8]> for TemporaryFailureStatus {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
-        Ok(match &value[0..2] {
-            b"40" => TemporaryFailureStatus::Unspecified,
-            b"41" => TemporaryFailureStatus::ServerUnavailable,
-            b"42" => TemporaryFailureStatus::CGIError,
-            b"43" => TemporaryFailureStatus::ProxyError,
-            b"44" => TemporaryFailureStatus::SlowDown,
-            _ => bail!("Invalid temporary failure status"),
-        })
-    }
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PermanentFailureStatus {
     Unspecified,
     NotFound,
@@ -253,63 +1351,90 @@ pub enum PermanentFailureStatus {
     BadRequest,
 }
 
-impl TryFrom<&[u8]> for PermanentFailureStatus {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
-        Ok(match &value[0..2] {
-            b"50" => PermanentFailureStatus::Unspecified,
-            b"51" => PermanentFailureStatus::NotFound,
-            b"52" => PermanentFailureStatus::Gone,
-            b"53" => PermanentFailureStatus::ProxyRequestRefused,
-            b"59" => PermanentFailureStatus::BadRequest,
-            _ => bail!("Invalid permanent failure status"),
-        })
-    }
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClientCertificateErrorStatus {
     Required,
     NotAuthorized,
     NotValid,
 }
 
-impl TryFrom<&[u8]> for ClientCertificateErrorStatus {
-    type Error = anyhow::Error;
+/// The common name of a certificate, or the name of its file when it has none.
+fn identity_name(certificate: &[u8], cert_file: &str) -> String {
+    let common_name = X509Certificate::from_der(certificate)
+        .ok()
+        .and_then(|(_, certificate)| {
+            let name = certificate.subject().iter_common_name().next()?;
+            name.as_str().ok().map(str::to_string)
+        });
+    common_name.unwrap_or_else(|| {
+        Path::new(cert_file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| cert_file.to_string())
+    })
+}
 
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
-        Ok(match &value[0..2] {
-            b"60" => ClientCertificateErrorStatus::Required,
-            b"61" => ClientCertificateErrorStatus::NotAuthorized,
-            b"62" => ClientCertificateErrorStatus::NotValid,
-            _ => bail!("Invalid client certificate status"),
-        })
+/// Gives the client certificate to servers asking for it, remembering that they did
+#[derive(Debug)]
+struct IdentityResolver {
+    inner: Arc<dyn ResolvesClientCert>,
+    asked: AtomicBool,
+}
+
+impl ResolvesClientCert for IdentityResolver {
+    fn resolve(
+        &self,
+        root_hint_subjects: &[&[u8]],
+        sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        self.asked.store(true, Ordering::Relaxed);
+        self.inner.resolve(root_hint_subjects, sigschemes)
+    }
+
+    fn has_certs(&self) -> bool {
+        self.inner.has_certs()
     }
 }
 
-#[derive(Debug, Clone)]
 struct TofuCertVerifier {
     provider: CryptoProvider,
+    known_hosts: Arc<KnownHosts>,
 }
 
 impl TofuCertVerifier {
-    pub fn new(provider: CryptoProvider) -> Self {
-        Self { provider }
+    pub fn new(provider: CryptoProvider, known_hosts: Arc<KnownHosts>) -> Self {
+        Self {
+            provider,
+            known_hosts,
+        }
+    }
+}
+
+impl std::fmt::Debug for TofuCertVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TofuCertVerifier").finish_non_exhaustive()
     }
 }
 
-/// We still need to actual store the cert in the first time and reutilize it afterwards
+/// Certificates are trusted the first time a host is seen and must not change afterwards
 impl ServerCertVerifier for TofuCertVerifier {
     fn verify_server_cert(
         &self,
-        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        end_entity: &rustls::pki_types::CertificateDer<'_>,
         _intermediates: &[rustls::pki_types::CertificateDer<'_>],
-        _server_name: &rustls::pki_types::ServerName<'_>,
+        server_name: &rustls::pki_types::ServerName<'_>,
         _ocsp_response: &[u8],
         _now: rustls::pki_types::UnixTime,
     ) -> std::result::Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
+        match self
+            .known_hosts
+            .verify(&server_name.to_str(), end_entity.as_ref())
+        {
+            Verification::FirstUse | Verification::Known => Ok(ServerCertVerified::assertion()),
+            Verification::Mismatch => Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+        }
     }
 
     fn verify_tls12_signature(
@@ -346,3 +1471,209 @@ impl ServerCertVerifier for TofuCertVerifier {
             .supported_schemes()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identities_scoped_to_paths() {
+        let url = |url| Url::parse(url).unwrap();
+        let prefix = "gemini://host/app";
+        assert!(in_scope(prefix, &url("gemini://host/app")));
+        assert!(in_scope(prefix, &url("gemini://host/app/page?query")));
+        assert!(in_scope(prefix, &url("gemini://host:1965/app")));
+        assert!(!in_scope(prefix, &url("gemini://host/application")));
+        assert!(!in_scope(prefix, &url("gemini://host.evil/app")));
+        assert!(!in_scope(prefix, &url("gemini://host:1966/app")));
+        assert!(in_scope("gemini://host/", &url("gemini://host/any")));
+    }
+
+    #[test]
+    fn identity_named_after_file_without_common_name() {
+        assert_eq!("alice", identity_name(b"", "certs/alice.pem"));
+    }
+
+    #[test]
+    fn identities_checked_when_loaded() {
+        let dir = std::env::temp_dir().join(format!("taurus-test-{}-identity", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files = |name: &str| Certificates {
+            cert_file: dir.join(format!("{name}-cert.pem")).display().to_string(),
+            key_file: dir.join(format!("{name}-key.pem")).display().to_string(),
+        };
+        let (alice, bob) = (files("alice"), files("bob"));
+        let err = Identity::load(&alice, None).err().unwrap();
+        assert!(err.to_string().starts_with("cert_file: "), "{err}");
+        assert_eq!(
+            "alice",
+            Identity::create("alice", &alice, None).unwrap().name
+        );
+        Identity::create("bob", &bob, None).unwrap();
+        let mixed = Certificates {
+            cert_file: alice.cert_file.clone(),
+            key_file: bob.key_file.clone(),
+        };
+        let err = Identity::load(&mixed, None).err().unwrap();
+        let expected = format!(
+            "key_file: {} is not the key of {}",
+            bob.key_file, alice.cert_file
+        );
+        assert_eq!(expected, err.to_string());
+        // The files replaced are kept
+        Identity::create("alice", &alice, None).unwrap();
+        assert!(Path::new(&format!("{}.old", alice.key_file)).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn end_without_close_notify_kept() {
+        struct Cut(ErrorKind);
+        impl Read for Cut {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(self.0.into())
+            }
+        }
+        let path = std::env::temp_dir().join(format!("taurus-test-{}-cut", process::id()));
+        let known_hosts = Arc::new(KnownHosts::load(path).unwrap());
+        let client = Client::new(false, None, ClientOptions::default(), known_hosts);
+        let mut close_notify = true;
+        let read = (&b"# Page"[..]).chain(Cut(ErrorKind::UnexpectedEof));
+        let body = client.read_body(read, "text/gemini", &mut close_notify, &mut |_| Ok(()));
+        assert_eq!(6, body.unwrap().len());
+        assert!(!close_notify);
+        let read = (&b"# Page"[..]).chain(Cut(ErrorKind::TimedOut));
+        let err = client
+            .read_body(read, "text/gemini", &mut close_notify, &mut |_| Ok(()))
+            .unwrap_err();
+        assert_eq!(6, err.downcast::<TruncatedBody>().unwrap().body.len());
+    }
+
+    #[test]
+    fn requests_to_a_host_are_spaced() {
+        let limiter = RateLimiter::default();
+        let interval = Duration::from_millis(100);
+        let now = Instant::now();
+        assert_eq!(Duration::ZERO, limiter.reserve("a.com", interval, now));
+        assert_eq!(
+            Duration::from_millis(100),
+            limiter.reserve("a.com", interval, now)
+        );
+        assert_eq!(
+            Duration::from_millis(200),
+            limiter.reserve("a.com", interval, now)
+        );
+        assert_eq!(Duration::ZERO, limiter.reserve("b.com", interval, now));
+        // A shorter interval applies to the next slots
+        let shorter = Duration::from_millis(10);
+        assert_eq!(
+            Duration::from_millis(300),
+            limiter.reserve("a.com", shorter, now)
+        );
+        let later = now + Duration::from_secs(1);
+        assert_eq!(Duration::ZERO, limiter.reserve("c.com", interval, later));
+        // Only the host requested last is kept
+        assert_eq!(1, limiter.next.lock().unwrap().len());
+    }
+
+    fn header(bytes: &[u8]) -> Result<(String, String)> {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        read_header(&mut &bytes[..], deadline, false, &mut Vec::new())
+    }
+
+    #[test]
+    fn response_header() {
+        let (status, meta) = header(b"20 text/gemini; lang=en\r\n# Body").unwrap();
+        assert_eq!(
+            ("20", "text/gemini; lang=en"),
+            (status.as_str(), meta.as_str())
+        );
+        let (status, meta) = header(b"51\r\n").unwrap();
+        assert_eq!(("51", ""), (status.as_str(), meta.as_str()));
+        // Kept as sent but for the space after the status
+        let (_, meta) = header(b"20  text/gemini \r\n").unwrap();
+        assert_eq!(" text/gemini ", meta);
+        let err = header(b"20 text/gemini").unwrap_err();
+        assert_eq!(14, err.downcast::<NoResponse>().unwrap().received);
+        let err = header(b"").unwrap_err();
+        assert_eq!(0, err.downcast::<NoResponse>().unwrap().received);
+        assert!(header(b"OK text/gemini\r\n").is_err());
+    }
+
+    #[test]
+    fn status_codes() {
+        let status = Status::try_from("51").unwrap();
+        assert_eq!(
+            Status::PermanentFailure(PermanentFailureStatus::NotFound),
+            status
+        );
+        assert_eq!(51, status.code());
+        for (code, status) in STATUSES {
+            assert_eq!(code, status.code());
+        }
+        assert_eq!(
+            Status::Redirect(RedirectStatus::Temporary),
+            Status::try_from("38").unwrap()
+        );
+        assert_eq!(Status::Success, Status::try_from("25").unwrap());
+        // Short, long and unknown codes are errors, not panics
+        for code in ["", "2", "200", "70", "05", "+1", "é"] {
+            assert!(Status::try_from(code).is_err());
+        }
+    }
+
+    #[test]
+    fn header_without_cr_tolerated() {
+        let mut warnings = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let (status, meta) = read_header(
+            &mut &b"20 text/gemini\n# Body"[..],
+            deadline,
+            false,
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(("20", "text/gemini"), (status.as_str(), meta.as_str()));
+        assert_eq!(
+            vec!["The response header ends with LF instead of CRLF"],
+            warnings
+        );
+    }
+
+    #[test]
+    fn trailing_data() {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let mut reader = &b"51 Not found\r\nThe body"[..];
+        read_header(&mut reader, deadline, false, &mut Vec::new()).unwrap();
+        assert!(has_trailing_data(&mut reader));
+        let mut reader = &b"51 Not found\r\n"[..];
+        read_header(&mut reader, deadline, false, &mut Vec::new()).unwrap();
+        assert!(!has_trailing_data(&mut reader));
+    }
+
+    #[test]
+    fn response_header_size_limit() {
+        let meta = "a".repeat(1024);
+        assert!(header(format!("20 {meta}\r\n").as_bytes()).is_ok());
+        let err = header(format!("20 {meta}a\r\n").as_bytes()).unwrap_err();
+        assert!(!err.downcast::<MetaTooLong>().unwrap().permissive);
+        // Without the CR the line fits, the meta is still too long
+        assert!(header(format!("20 {meta}a\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn permissive_meta() {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let mut warnings = Vec::new();
+        let header = format!("20 {}\r\n", "a".repeat(2000));
+        let (_, meta) = read_header(&mut header.as_bytes(), deadline, true, &mut warnings).unwrap();
+        assert_eq!(2000, meta.len());
+        assert_eq!(
+            vec!["The meta is 2000 bytes, longer than the 1024 allowed"],
+            warnings
+        );
+        let header = "a".repeat(MAX_PERMISSIVE_HEADER_SIZE + 1);
+        let err = read_header(&mut header.as_bytes(), deadline, true, &mut warnings).unwrap_err();
+        assert!(err.downcast::<MetaTooLong>().unwrap().permissive);
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::{bail, Result};
+use serde::de::DeserializeOwned;
+use serde_path_to_error::Segment;
+
+pub const DIAGNOSTICS_URL: &str = "about:diagnostics";
+
+/// Reads a configuration, leaving out the settings that are invalid or unknown instead of
+/// failing, so one typo does not prevent starting. Each setting left out is described. Only
+/// files that are not valid TOML fail.
+pub fn parse<T: DeserializeOwned + Default>(contents: &str) -> Result<(T, Vec<String>)> {
+    let mut problems = Vec::new();
+    let mut table: toml::Table = match contents.parse() {
+        Ok(table) => table,
+        Err(err) => {
+            let line = err
+                .span()
+                .map_or(0, |span| contents[..span.start].lines().count().max(1));
+            bail!("Line {line}: {}", err.message().trim_end());
+        }
+    };
+    loop {
+        let value = toml::Value::Table(table.clone());
+        let err = match serde_path_to_error::deserialize::<_, T>(value) {
+            Ok(_) => break,
+            Err(err) => err,
+        };
+        // Errors of values nested in tables end with their path again, on another line
+        let message = err.inner().message().lines().next().unwrap_or_default();
+        problems.push(format!("{}: {message}", err.path()));
+        // Settings in lists are left out with their whole list
+        let keys: Vec<&str> = err
+            .path()
+            .iter()
+            .map_while(|segment| match segment {
+                Segment::Map { key } => Some(key.as_str()),
+                _ => None,
+            })
+            .collect();
+        if !remove(&mut table, &keys) {
+            return Ok((T::default(), problems));
+        }
+    }
+    let config = serde_ignored::deserialize(toml::Value::Table(table), |path| {
+        problems.push(format!("{path}: unknown setting"));
+    })
+    .unwrap_or_default();
+    Ok((config, problems))
+}
+
+/// Removes the setting at the path of `keys`, returning whether there was one
+fn remove(table: &mut toml::Table, keys: &[&str]) -> bool {
+    let Some((last, parents)) = keys.split_last() else {
+        return false;
+    };
+    let mut table = table;
+    for key in parents {
+        match table.get_mut(*key).and_then(toml::Value::as_table_mut) {
+            Some(child) => table = child,
+            None => return false,
+        }
+    }
+    table.remove(*last).is_some()
+}
+
+/// The problems found in the configuration file, as a page
+pub fn page(file: &str, problems: &[String]) -> String {
+    let mut page = String::from("# Configuration problems\n\n");
+    if problems.is_empty() {
+        page.push_str(&format!("No problem found in {file}.\n"));
+        return page;
+    }
+    page.push_str(&format!(
+        "These settings of {file} were left out, their defaults are used instead:\n\n"
+    ));
+    for problem in problems {
+        page.push_str(&format!("* {problem}\n"));
+    }
+    page.push_str("\nFix them and the file is read again.\n");
+    page
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Default, Debug, PartialEq)]
+    #[serde(default)]
+    struct Settings {
+        name: String,
+        size: u32,
+        nested: Nested,
+    }
+
+    #[derive(Deserialize, Default, Debug, PartialEq)]
+    #[serde(default)]
+    struct Nested {
+        flag: bool,
+        list: Vec<u8>,
+    }
+
+    #[test]
+    fn invalid_settings_left_out() {
+        let (settings, problems) = parse::<Settings>(
+            "name = \"a\"\nsize = \"big\"\ncolour = 1\n[nested]\nflag = true\nlist = [1, -1]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Settings {
+                name: "a".to_string(),
+                size: 0,
+                nested: Nested {
+                    flag: true,
+                    list: Vec::new()
+                },
+            },
+            settings
+        );
+        assert_eq!(
+            vec![
+                "nested.list[1]: invalid value: integer `-1`, expected u8",
+                "size: invalid type: string \"big\", expected u32",
+                "colour: unknown setting",
+            ],
+            problems
+        );
+    }
+
+    #[test]
+    fn syntax_errors_reported_with_their_line() {
+        let err = parse::<Settings>("name = \"a\"\nsize = \n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 2: "), "{err}");
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    env,
+    fmt::Display,
+    fs,
+    io::{stdin, stdout, IsTerminal},
+    path::Path,
+    process,
+    sync::Arc,
+    time::Instant,
+};
+
+use anyhow::{bail, Result};
+use url::Url;
+
+use crate::{
+    client::{Client, GeminiResponse},
+    known_hosts::KnownHosts,
+    Config, CONFIG_FILE,
+};
+
+/// Requested when the home page is not a Gemini page
+const TEST_CAPSULE: &str = "gemini://geminiprotocol.net/";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Status {
+    Ok,
+    Warning,
+    Failed,
+}
+
+/// The results of the checks, printed as they are made
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn section(&self, title: &str) {
+        println!("\n{title}");
+    }
+
+    fn check(&mut self, status: Status, text: impl Display) {
+        let label = match status {
+            Status::Ok => "ok",
+            Status::Warning => {
+                self.warnings += 1;
+                "warn"
+            }
+            Status::Failed => {
+                self.failures += 1;
+                "fail"
+            }
+        };
+        println!("  {label:<5} {text}");
+    }
+}
+
+/// Checks what taurus needs from the terminal, the configuration, the files it writes and the
+/// network, and prints what it found. The report is meant to be pasted in bug reports.
+pub fn run(config: Config) -> Result<()> {
+    let mut report = Report::default();
+    println!(
+        "taurus {} on {} {}",
+        env!("CARGO_PKG_VERSION"),
+        env::consts::OS,
+        env::consts::ARCH
+    );
+
+    report.section("Terminal");
+    if stdin().is_terminal() && stdout().is_terminal() {
+        match crossterm::terminal::size() {
+            Ok((columns, rows)) => report.check(Status::Ok, format!("{columns}x{rows}")),
+            Err(err) => report.check(Status::Failed, format!("size unknown, {err}")),
+        }
+    } else {
+        report.check(
+            Status::Warning,
+            "not a terminal, the full screen mode needs one, --linear does not",
+        );
+    }
+    match env::var("TERM") {
+        Ok(term) if term != "dumb" => report.check(Status::Ok, format!("TERM is {term}")),
+        Ok(term) => report.check(Status::Warning, format!("TERM is {term}, colors are off")),
+        Err(_) => report.check(Status::Warning, "TERM is not set"),
+    }
+    let colors = match env::var("COLORTERM") {
+        Ok(value) if value == "truecolor" || value == "24bit" => "24 bit",
+        _ if env::var("TERM").is_ok_and(|term| term.contains("256color")) => "256",
+        _ => "16",
+    };
+    report.check(Status::Ok, format!("{colors} colors"));
+    match locale_is_utf8(|name| env::var(name).ok()) {
+        Some(true) => report.check(Status::Ok, "UTF-8 locale"),
+        Some(false) => report.check(
+            Status::Warning,
+            "the locale is not UTF-8, borders and pages in other languages may look wrong",
+        ),
+        None => report.check(Status::Warning, "no locale set, UTF-8 may not be shown"),
+    }
+
+    report.section("Configuration");
+    if Path::new(CONFIG_FILE).exists() {
+        report.check(Status::Ok, format!("{CONFIG_FILE} read"));
+    } else {
+        report.check(
+            Status::Ok,
+            format!("no {CONFIG_FILE}, the defaults are used"),
+        );
+    }
+    for problem in &config.problems {
+        report.check(Status::Failed, problem);
+    }
+    let identity = match config.identity() {
+        Ok(Some(identity)) => {
+            report.check(Status::Ok, "client certificate loaded");
+            Some(identity)
+        }
+        Ok(None) => {
+            report.check(Status::Ok, "no client certificate");
+            None
+        }
+        Err(err) => {
+            report.check(Status::Failed, format!("no client certificate, {err}"));
+            None
+        }
+    };
+
+    report.section("Files");
+    let mut directories = vec![
+        ("working directory", "."),
+        ("known hosts", parent(&config.known_hosts_file)),
+        ("session", parent(&config.tabs.session_file)),
+        ("downloads", config.downloads_dir.as_deref().unwrap_or(".")),
+    ];
+    if !config.bookmarks_file.is_empty() {
+        directories.push(("bookmarks", parent(&config.bookmarks_file)));
+    }
+    if !config.tabs.positions_file.is_empty() {
+        directories.push(("reading positions", parent(&config.tabs.positions_file)));
+    }
+    if !config.cache.dir.is_empty() {
+        directories.push(("cache", &config.cache.dir));
+    }
+    for (name, dir) in directories {
+        match writable(Path::new(dir)) {
+            Ok(()) => report.check(Status::Ok, format!("{name} in {dir} is writable")),
+            Err(err) => report.check(Status::Failed, format!("{name} in {dir}: {err}")),
+        }
+    }
+
+    report.section("Network");
+    let url = match config.tabs.home_url.scheme() {
+        "gemini" => config.tabs.home_url.clone(),
+        _ => Url::parse(TEST_CAPSULE).expect("We know that this is a valid url"),
+    };
+    match KnownHosts::load(config.known_hosts_file.clone().into()) {
+        Ok(known_hosts) => {
+            let client = Client::new(true, identity, config.client, Arc::new(known_hosts));
+            let started = Instant::now();
+            match client.request(url.clone(), &mut |_| Ok(())) {
+                Ok(response) => {
+                    let elapsed = started.elapsed().as_millis();
+                    let status = match response {
+                        GeminiResponse::Success { .. } => Status::Ok,
+                        _ => Status::Warning,
+                    };
+                    report.check(
+                        status,
+                        format!("{url} answered {} in {elapsed}ms", response_kind(&response)),
+                    );
+                }
+                Err(err) => report.check(Status::Failed, format!("{url}: {err}")),
+            }
+        }
+        Err(err) => report.check(
+            Status::Failed,
+            format!("known hosts {}: {err}", config.known_hosts_file),
+        ),
+    }
+
+    println!(
+        "\n{} warnings, {} failures",
+        report.warnings, report.failures
+    );
+    if report.failures > 0 {
+        bail!("{} checks failed", report.failures);
+    }
+    Ok(())
+}
+
+/// Whether the first locale variable set asks for UTF-8, like the C library reads them
+fn locale_is_utf8(var: impl Fn(&str) -> Option<String>) -> Option<bool> {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))?;
+    let locale = locale.to_ascii_lowercase();
+    Some(locale.contains("utf-8") || locale.contains("utf8"))
+}
+
+/// The directory of a file, as a path to use
+fn parent(file: &str) -> &str {
+    match Path::new(file).parent().and_then(Path::to_str) {
+        Some("") | None => ".",
+        Some(parent) => parent,
+    }
+}
+
+/// Whether a file can be created in `dir`, which is created when missing like taurus does
+fn writable(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".taurus-doctor-{}", process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)?;
+    Ok(())
+}
+
+fn response_kind(response: &GeminiResponse) -> &'static str {
+    match response {
+        GeminiResponse::Success { .. } => "success",
+        GeminiResponse::Input { .. } => "input",
+        GeminiResponse::Redirect { .. } => "redirect",
+        GeminiResponse::TemporaryFailure { .. } => "temporary failure",
+        GeminiResponse::PermanentFailure { .. } => "permanent failure",
+        GeminiResponse::ClientCertificateError { .. } => "client certificate required",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locale_from_the_first_variable_set() {
+        let vars = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(None, locale_is_utf8(vars(&[])));
+        assert_eq!(Some(true), locale_is_utf8(vars(&[("LANG", "en_US.UTF-8")])));
+        assert_eq!(
+            Some(false),
+            locale_is_utf8(vars(&[("LC_ALL", "C"), ("LANG", "en_US.utf8")]))
+        );
+        assert_eq!(
+            Some(true),
+            locale_is_utf8(vars(&[("LC_CTYPE", ""), ("LANG", "de_DE.utf8")]))
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use clap::Args;
+use serde::Serialize;
+use url::Url;
+
+use crate::{
+    app::format_size,
+    client::{Client, GeminiResponse, ResponseBody},
+    gemtext::{GemTextLine, GemTextParser},
+    mirror::local_path,
+};
+
+#[derive(Args, Debug)]
+pub struct FetchArgs {
+    /// Pages to download, like gemini://myhost/notes.gmi
+    #[arg(required = true)]
+    pub urls: Vec<Url>,
+    /// Directory to save the pages to, in a directory per host
+    #[arg(long, default_value = ".")]
+    pub out: PathBuf,
+    /// Also download the pages linked from gemtext pages, on the same host
+    #[arg(long)]
+    pub recursive: bool,
+    /// How many links away from the given pages to follow with --recursive
+    #[arg(long, default_value_t = 5)]
+    pub depth: usize,
+    /// How many requests to make at the same time
+    #[arg(long, default_value_t = 4)]
+    pub jobs: usize,
+    /// Print every response as a line of JSON instead of saving it
+    #[arg(long)]
+    pub json: bool,
+    /// Times to request again the pages whose body may be cut short, as the server closed the
+    /// connection without TLS close_notify
+    #[arg(long, default_value_t = 2)]
+    pub retries: usize,
+}
+
+/// What is known of a response, saved next to its body as `<file>.meta`
+#[derive(Serialize, Debug)]
+struct Metadata {
+    url: String,
+    status: u8,
+    meta: String,
+    /// Size of the body, for successful responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    /// Whether the body may be cut short, as the server closed the connection without TLS
+    /// close_notify after the retries
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    possibly_truncated: bool,
+    /// Seconds since the Unix epoch
+    fetched: u64,
+}
+
+impl Metadata {
+    fn new(url: &Url, response: &GeminiResponse, possibly_truncated: bool) -> Self {
+        Self {
+            url: url.to_string(),
+            status: response.status_code(),
+            meta: response.meta().to_string(),
+            size: None,
+            possibly_truncated,
+            fetched: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+/// A response as printed with `--json`. Requests that failed only have an `error`.
+#[derive(Serialize, Debug, Default)]
+struct JsonResponse {
+    url: String,
+    status: Option<u8>,
+    meta: Option<String>,
+    mime: Option<String>,
+    body: Option<String>,
+    /// Whether the body is encoded in base64, as are those that are not text
+    base64: bool,
+    possibly_truncated: bool,
+    latency_ms: u128,
+    /// SHA-256 of the server certificate, in hexadecimal
+    fingerprint: Option<String>,
+    error: Option<String>,
+}
+
+struct Fetched {
+    /// Where the body was saved, unless it was printed
+    path: Option<PathBuf>,
+    metadata: Metadata,
+    /// Links of a gemtext page to other pages of the same host
+    links: Vec<Url>,
+}
+
+/// Downloads pages to a directory, `jobs` at a time. Responses other than successes and
+/// redirects only get a metadata file, and make the command fail once everything was tried.
+pub fn run(client: &Client, args: FetchArgs) -> Result<()> {
+    let jobs = args.jobs.max(1);
+    let mut visited = HashSet::new();
+    let mut failed = 0;
+    let mut saved = 0;
+    let mut downloaded = 0;
+    let mut wave = args.urls.clone();
+    for depth in 0.. {
+        wave.iter_mut().for_each(|url| url.set_fragment(None));
+        wave.retain(|url| visited.insert(url.clone()));
+        if wave.is_empty() {
+            break;
+        }
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..jobs.min(wave.len()) {
+                scope.spawn(|| {
+                    while let Some(url) = wave.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let result = if args.json {
+                            fetch_json(client, url, args.retries)
+                        } else {
+                            fetch(client, url, &args.out, args.retries)
+                        };
+                        match &result {
+                            Ok(fetched) if is_failure(fetched.metadata.status) => eprintln!(
+                                "{url}: {} {}",
+                                fetched.metadata.status, fetched.metadata.meta
+                            ),
+                            Ok(Fetched {
+                                path: Some(path),
+                                metadata,
+                                ..
+                            }) if metadata.possibly_truncated => println!(
+                                "{url} -> {}, may be cut short as the server closed the \
+                                 connection without TLS close_notify",
+                                path.display()
+                            ),
+                            Ok(Fetched {
+                                path: Some(path), ..
+                            }) => println!("{url} -> {}", path.display()),
+                            Ok(_) => {}
+                            Err(err) => eprintln!("{url}: {err}"),
+                        }
+                        results
+                            .lock()
+                            .expect("No thread panics holding the lock")
+                            .push(result);
+                    }
+                });
+            }
+        });
+
+        let mut links = Vec::new();
+        for result in results
+            .into_inner()
+            .expect("No thread panics holding the lock")
+        {
+            match result {
+                Ok(fetched) if is_failure(fetched.metadata.status) => failed += 1,
+                Ok(fetched) => {
+                    saved += 1;
+                    downloaded += fetched.metadata.size.unwrap_or_default();
+                    links.extend(fetched.links);
+                }
+                Err(_) => failed += 1,
+            }
+        }
+        if !args.recursive || depth >= args.depth {
+            break;
+        }
+        wave = links;
+    }
+
+    if !args.json {
+        println!("Saved {saved} pages, {}", format_size(downloaded));
+    }
+    if failed > 0 {
+        bail!("{failed} of {} requests failed", visited.len());
+    }
+    Ok(())
+}
+
+/// Whether a status is neither a success nor a redirect
+pub(crate) fn is_failure(status: u8) -> bool {
+    !(20..40).contains(&status)
+}
+
+fn fetch(client: &Client, url: &Url, out: &Path, retries: usize) -> Result<Fetched> {
+    let (response, possibly_truncated) = client.request_whole(url.clone(), retries)?;
+    let path = out.join(host_dir(url)).join(local_path(url));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut metadata = Metadata::new(url, &response, possibly_truncated);
+    let mut links = Vec::new();
+    if let GeminiResponse::Success { mime, body, .. } = response {
+        let size = match body {
+            ResponseBody::Bytes(bytes) => {
+                fs::write(&path, &bytes)?;
+                bytes.len() as u64
+            }
+            ResponseBody::File { file, .. } => fs::copy(file.path(), &path)?,
+        };
+        metadata.size = Some(size);
+        if mime.starts_with("text/gemini") {
+            links = same_host_links(&fs::read_to_string(&path)?, url);
+        }
+    }
+    fs::write(sidecar_path(&path), toml::to_string(&metadata)?)?;
+    Ok(Fetched {
+        path: Some(path),
+        metadata,
+        links,
+    })
+}
+
+fn fetch_json(client: &Client, url: &Url, retries: usize) -> Result<Fetched> {
+    let started = Instant::now();
+    let response = client.request_whole(url.clone(), retries);
+    let mut json = JsonResponse {
+        url: url.to_string(),
+        latency_ms: started.elapsed().as_millis(),
+        ..JsonResponse::default()
+    };
+    let (response, possibly_truncated) = match response {
+        Ok(response) => response,
+        Err(err) => {
+            json.error = Some(err.to_string());
+            println!("{}", serde_json::to_string(&json)?);
+            return Err(err);
+        }
+    };
+    json.fingerprint = url.host_str().and_then(|host| client.fingerprint(host));
+    json.possibly_truncated = possibly_truncated;
+    let mut metadata = Metadata::new(url, &response, possibly_truncated);
+    json.status = Some(metadata.status);
+    json.meta = Some(metadata.meta.clone());
+    let mut links = Vec::new();
+    if let GeminiResponse::Success { mime, body, .. } = response {
+        let bytes = match body {
+            ResponseBody::Bytes(bytes) => bytes,
+            ResponseBody::File { file, .. } => fs::read(file.path())?,
+        };
+        metadata.size = Some(bytes.len() as u64);
+        if mime.starts_with("text/gemini") {
+            links = same_host_links(&String::from_utf8_lossy(&bytes), url);
+        }
+        let (body, base64) = json_body(&mime, bytes);
+        json.body = Some(body);
+        json.base64 = base64;
+        json.mime = Some(mime);
+    }
+    println!("{}", serde_json::to_string(&json)?);
+    Ok(Fetched {
+        path: None,
+        metadata,
+        links,
+    })
+}
+
+/// The body as a JSON string: text as it is, anything else in base64
+fn json_body(mime: &str, bytes: Vec<u8>) -> (String, bool) {
+    if !mime.starts_with("text/") {
+        return (BASE64_STANDARD.encode(bytes), true);
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, false),
+        Err(err) => (BASE64_STANDARD.encode(err.into_bytes()), true),
+    }
+}
+
+/// Directory of the pages of a host, which also tells apart the ports it is served on
+fn host_dir(url: &Url) -> String {
+    let host = url.host_str().unwrap_or("localhost");
+    match url.port() {
+        Some(port) => format!("{host}_{port}"),
+        None => host.to_string(),
+    }
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".meta");
+    path.with_file_name(name)
+}
+
+/// Gemini links of a gemtext page to the same host, without the queries answering prompts
+fn same_host_links(text: &str, url: &Url) -> Vec<Url> {
+    GemTextParser::new(text, url.clone())
+        .filter_map(|line| match line {
+            Ok(GemTextLine::Link { url: link, .. }) => Some(link),
+            _ => None,
+        })
+        .filter(|link| {
+            link.scheme() == "gemini"
+                && link.host_str() == url.host_str()
+                && link.port() == url.port()
+                && link.query().is_none()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn output_paths() {
+        let url = Url::parse("gemini://test.com:1966/docs/").unwrap();
+        assert_eq!("test.com_1966", host_dir(&url));
+        assert_eq!(
+            "test.com",
+            host_dir(&Url::parse("gemini://test.com/").unwrap())
+        );
+        assert_eq!(
+            PathBuf::from("out/docs/index.gmi.meta"),
+            sidecar_path(Path::new("out/docs/index.gmi"))
+        );
+    }
+
+    #[test]
+    fn binary_bodies_in_base64() {
+        assert_eq!(
+            ("# Hi\n".to_string(), false),
+            json_body("text/gemini", b"# Hi\n".to_vec())
+        );
+        assert_eq!(
+            ("iVBO".to_string(), true),
+            json_body("image/png", vec![0x89, 0x50, 0x4e])
+        );
+        assert_eq!(
+            ("/w==".to_string(), true),
+            json_body("text/plain", vec![0xff])
+        );
+    }
+
+    #[test]
+    fn recursion_stays_on_host() {
+        let url = Url::parse("gemini://test.com/").unwrap();
+        let text = "=> /a A\n=> gemini://test.com:1966/ Other port\n=> gemini://b.com/ B\n\
+                    => search?q Search\n=> https://test.com/ Web";
+        assert_eq!(
+            vec![Url::parse("gemini://test.com/a").unwrap()],
+            same_host_links(text, &url)
+        );
+    }
+}
@@ -4,18 +4,36 @@ use url::Url;
 pub struct GemTextParser<'a> {
     raw_text: &'a str,
     url: Url,
+    pre_formatted: bool,
 }
 
 #[derive(Debug)]
 pub enum GemTextLine<'a> {
     Text(&'a str),
-    Link { url: Url, text: &'a str },
+    Link {
+        url: Url,
+        text: &'a str,
+    },
+    Heading {
+        level: u8,
+        text: &'a str,
+    },
+    ListItem(&'a str),
+    /// A quote, quoted again `level - 1` times, like `>> text` in replies to replies
+    Quote {
+        level: u8,
+        text: &'a str,
+    },
     PreFormatted(&'a str),
 }
 
 impl<'a> GemTextParser<'a> {
     pub fn new(raw_text: &'a str, url: Url) -> Self {
-        Self { raw_text, url }
+        Self {
+            raw_text,
+            url,
+            pre_formatted: false,
+        }
     }
 
     fn parse_next(&mut self) -> Result<GemTextLine<'a>> {
@@ -27,24 +45,41 @@ impl<'a> GemTextParser<'a> {
             self.raw_text = "";
             line
         };
+        if let Some(pre_formatted_line) = line.strip_prefix("```") {
+            self.pre_formatted = !self.pre_formatted;
+            return Ok(GemTextLine::PreFormatted(pre_formatted_line));
+        }
+        if self.pre_formatted {
+            return Ok(GemTextLine::PreFormatted(line));
+        }
         if let Some(link_line) = line.strip_prefix("=>") {
             let (link, text) = link_line
                 .trim()
                 .split_once(|x: char| x.is_whitespace())
                 .unwrap_or((link_line, ""));
-            if !link.contains("://") {
-                return Ok(GemTextLine::Link {
-                    url: self.url.join(link.trim())?,
-                    text,
-                });
-            }
             return Ok(GemTextLine::Link {
-                url: Url::parse(link.trim())?,
+                url: resolve_link(&self.url, link)?,
                 text,
             });
         }
-        if let Some(pre_formatted_line) = line.strip_prefix("```") {
-            return Ok(GemTextLine::PreFormatted(pre_formatted_line));
+        if line.starts_with('#') {
+            let level = line.chars().take_while(|c| *c == '#').count().min(3);
+            return Ok(GemTextLine::Heading {
+                level: level as u8,
+                text: line[level..].trim(),
+            });
+        }
+        if let Some(item) = line.strip_prefix("* ") {
+            return Ok(GemTextLine::ListItem(item.trim()));
+        }
+        if line.starts_with('>') {
+            // `> > text` is quoted twice as well, as some clients quote replies that way
+            let text = line.trim_start_matches(|c: char| c == '>' || c.is_whitespace());
+            let level = line[..line.len() - text.len()].matches('>').count();
+            return Ok(GemTextLine::Quote {
+                level: level.min(u8::MAX as usize) as u8,
+                text: text.trim_end(),
+            });
         }
         Ok(GemTextLine::Text(line))
     }
@@ -60,3 +95,164 @@ impl<'a> Iterator for GemTextParser<'a> {
         Some(self.parse_next())
     }
 }
+
+/// The URL a link of the page at `base` points to. Dot segments are resolved, even above the
+/// root, and Gemini URLs are normalized so a page always has the same URL however it is linked:
+/// the host in lower case, without the default port and with at least `/` as path.
+pub fn resolve_link(base: &Url, link: &str) -> Result<Url, url::ParseError> {
+    let mut url = base.join(link.trim())?;
+    if url.scheme() == "gemini" {
+        if let Some(host) = url
+            .host_str()
+            .filter(|host| host.chars().any(char::is_uppercase))
+        {
+            let host = host.to_lowercase();
+            url.set_host(Some(&host))?;
+        }
+        if url.port() == Some(1965) {
+            let _ = url.set_port(None);
+        }
+        if url.path().is_empty() {
+            url.set_path("/");
+        }
+    }
+    Ok(url)
+}
+
+/// Anchor of a heading, like `getting-started` for `## Getting started!`
+pub fn slug(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn headings() {
+        let url = Url::parse("gemini://test.com/").unwrap();
+        let lines: Vec<_> = GemTextParser::new("# One\n## Two\n#### Four", url)
+            .flatten()
+            .collect();
+        assert!(matches!(
+            lines[0],
+            GemTextLine::Heading {
+                level: 1,
+                text: "One"
+            }
+        ));
+        assert!(matches!(
+            lines[1],
+            GemTextLine::Heading {
+                level: 2,
+                text: "Two"
+            }
+        ));
+        assert!(matches!(
+            lines[2],
+            GemTextLine::Heading {
+                level: 3,
+                text: "# Four"
+            }
+        ));
+    }
+
+    #[test]
+    fn nested_quotes() {
+        let url = Url::parse("gemini://test.com/").unwrap();
+        let lines: Vec<_> = GemTextParser::new(">One\n>> Two\n> > Three\n> a > b", url)
+            .flatten()
+            .collect();
+        assert!(matches!(
+            lines[0],
+            GemTextLine::Quote {
+                level: 1,
+                text: "One"
+            }
+        ));
+        assert!(matches!(
+            lines[1],
+            GemTextLine::Quote {
+                level: 2,
+                text: "Two"
+            }
+        ));
+        assert!(matches!(
+            lines[2],
+            GemTextLine::Quote {
+                level: 2,
+                text: "Three"
+            }
+        ));
+        assert!(matches!(
+            lines[3],
+            GemTextLine::Quote {
+                level: 1,
+                text: "a > b"
+            }
+        ));
+    }
+
+    #[test]
+    fn relative_links() {
+        let resolve = |base: &str, link: &str| {
+            resolve_link(&Url::parse(base).unwrap(), link)
+                .unwrap()
+                .to_string()
+        };
+        // Without a trailing slash the last segment is a file, not a directory
+        assert_eq!("gemini://a.com/page", resolve("gemini://a.com/dir", "page"));
+        assert_eq!(
+            "gemini://a.com/x",
+            resolve("gemini://a.com/dir/sub", "../x")
+        );
+        assert_eq!("gemini://a.com/a/", resolve("gemini://a.com/a/b.gmi", "./"));
+        assert_eq!(
+            "gemini://a.com/a/c/e",
+            resolve("gemini://a.com/a/b", "c/./d/../e")
+        );
+        assert_eq!(
+            "gemini://a.com/c",
+            resolve("gemini://a.com/a/b", "%2e%2e/c")
+        );
+        // Going above the root stays at the root
+        assert_eq!(
+            "gemini://a.com/x",
+            resolve("gemini://a.com/a/", "../../../x")
+        );
+        assert_eq!("gemini://a.com/", resolve("gemini://a.com/", ".."));
+        assert_eq!(
+            "gemini://b.com/x",
+            resolve("gemini://a.com/a/", " //b.com/x ")
+        );
+        assert_eq!(
+            "gemini://a.com/search?u=gemini://x",
+            resolve("gemini://a.com/a/", "/search?u=gemini://x")
+        );
+    }
+
+    #[test]
+    fn gemini_links_normalized() {
+        let base = Url::parse("gemini://a.com/").unwrap();
+        let resolve = |link: &str| resolve_link(&base, link).unwrap().to_string();
+        assert_eq!("gemini://b.com/Page", resolve("GEMINI://B.Com:1965/Page"));
+        assert_eq!("gemini://b.com/", resolve("gemini://b.com"));
+        assert_eq!("gemini://b.com:1966/", resolve("gemini://b.com:1966/"));
+        // Other schemes have their own rules
+        assert_eq!("spartan://B.com:1965", resolve("spartan://B.com:1965"));
+    }
+
+    #[test]
+    fn heading_slugs() {
+        assert_eq!("getting-started", slug("Getting  started!"));
+        assert_eq!("été-2024", slug("# Été 2024 "));
+    }
+}
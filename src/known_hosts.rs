@@ -0,0 +1,372 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::logging;
+
+/// Certificates seen for each host, trusted on first use.
+pub struct KnownHosts {
+    path: PathBuf,
+    hosts: Mutex<HashMap<String, KnownHost>>,
+    /// Certificates that did not match the known one during the last handshake with a host
+    mismatches: Mutex<HashMap<String, CertificateMismatch>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KnownHost {
+    pub fingerprint: String,
+    #[serde(default)]
+    pub policy: TrustPolicy,
+    /// When the certificate was accepted, in seconds since the Unix epoch. Unknown for hosts
+    /// remembered before it was recorded.
+    #[serde(default)]
+    pub accepted: Option<u64>,
+}
+
+impl KnownHost {
+    pub fn accepted_at(&self) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(self.accepted?))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustPolicy {
+    /// Ask before accepting a certificate different from the known one
+    #[default]
+    Tofu,
+    /// Silently accept whatever certificate the host presents
+    AlwaysTrust,
+    /// Refuse any certificate other than the known one, without asking
+    Pinned,
+}
+
+impl TrustPolicy {
+    pub fn next(self) -> Self {
+        match self {
+            TrustPolicy::Tofu => TrustPolicy::AlwaysTrust,
+            TrustPolicy::AlwaysTrust => TrustPolicy::Pinned,
+            TrustPolicy::Pinned => TrustPolicy::Tofu,
+        }
+    }
+}
+
+impl Display for TrustPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TrustPolicy::Tofu => "trust on first use",
+            TrustPolicy::AlwaysTrust => "always trust the current certificate",
+            TrustPolicy::Pinned => "pinned certificate",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CertificateMismatch {
+    pub host: String,
+    pub fingerprint: String,
+    pub policy: TrustPolicy,
+}
+
+impl Display for CertificateMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.policy {
+            TrustPolicy::Pinned => write!(
+                f,
+                "The pinned certificate of {} changed, refusing to connect",
+                self.host
+            ),
+            _ => write!(f, "The certificate of {} changed", self.host),
+        }
+    }
+}
+
+impl std::error::Error for CertificateMismatch {}
+
+pub enum Verification {
+    FirstUse,
+    Known,
+    Mismatch,
+}
+
+impl KnownHosts {
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let hosts = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            hosts: Mutex::new(hosts),
+            mismatches: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Checks the certificate a host presented. A new certificate, of a host seen for the first
+    /// time or always trusted, is only remembered once the handshake succeeded, see
+    /// [`KnownHosts::handshake_completed`].
+    pub fn verify(&self, host: &str, certificate: &[u8]) -> Verification {
+        let fingerprint = fingerprint(certificate);
+        let hosts = self.hosts.lock().expect("Poisoned known hosts");
+        match hosts.get(host) {
+            None => Verification::FirstUse,
+            Some(known)
+                if known.fingerprint == fingerprint || known.policy == TrustPolicy::AlwaysTrust =>
+            {
+                Verification::Known
+            }
+            Some(known) => {
+                tracing::warn!(
+                    "Certificate of {} does not match the known one",
+                    logging::host(host)
+                );
+                let mismatch = CertificateMismatch {
+                    host: host.to_string(),
+                    fingerprint,
+                    policy: known.policy,
+                };
+                self.mismatches
+                    .lock()
+                    .expect("Poisoned known hosts")
+                    .insert(host.to_string(), mismatch);
+                Verification::Mismatch
+            }
+        }
+    }
+
+    /// Remembers the certificate of a host seen for the first time, or the new one of a host
+    /// always trusted, once the handshake proved that the server holds its key. A peer failing
+    /// the handshake never gets its certificate trusted.
+    pub fn handshake_completed(&self, host: &str, certificate: &[u8]) {
+        let fingerprint = fingerprint(certificate);
+        let mut hosts = self.hosts.lock().expect("Poisoned known hosts");
+        match hosts.get_mut(host) {
+            None => {
+                hosts.insert(
+                    host.to_string(),
+                    KnownHost {
+                        fingerprint,
+                        policy: TrustPolicy::default(),
+                        accepted: now(),
+                    },
+                );
+            }
+            Some(known)
+                if known.policy == TrustPolicy::AlwaysTrust && known.fingerprint != fingerprint =>
+            {
+                tracing::info!(
+                    "Certificate of {} changed, trusting it as configured",
+                    logging::host(host)
+                );
+                known.fingerprint = fingerprint;
+                known.accepted = now();
+            }
+            Some(_) => return,
+        }
+        self.save(&hosts);
+    }
+
+    /// The mismatch that made the last handshake with `host` fail, if that was the reason.
+    pub fn take_mismatch(&self, host: &str) -> Option<CertificateMismatch> {
+        self.mismatches
+            .lock()
+            .expect("Poisoned known hosts")
+            .remove(host)
+    }
+
+    /// Accepts a new certificate for a host, keeping its policy.
+    pub fn trust(&self, host: &str, fingerprint: String) {
+        let mut hosts = self.hosts.lock().expect("Poisoned known hosts");
+        let policy = hosts
+            .get(host)
+            .map(|known| known.policy)
+            .unwrap_or_default();
+        hosts.insert(
+            host.to_string(),
+            KnownHost {
+                fingerprint,
+                policy,
+                accepted: now(),
+            },
+        );
+        self.save(&hosts);
+    }
+
+    pub fn get(&self, host: &str) -> Option<KnownHost> {
+        self.hosts
+            .lock()
+            .expect("Poisoned known hosts")
+            .get(host)
+            .cloned()
+    }
+
+    /// All the known hosts, sorted by name
+    pub fn hosts(&self) -> Vec<(String, KnownHost)> {
+        let hosts = self.hosts.lock().expect("Poisoned known hosts");
+        let mut hosts: Vec<_> = hosts
+            .iter()
+            .map(|(host, known)| (host.clone(), known.clone()))
+            .collect();
+        hosts.sort_by(|(a, _), (b, _)| a.cmp(b));
+        hosts
+    }
+
+    /// Forgets the certificate of a host, so the next one it presents is trusted on first use.
+    pub fn forget(&self, host: &str) -> bool {
+        let mut hosts = self.hosts.lock().expect("Poisoned known hosts");
+        if hosts.remove(host).is_none() {
+            return false;
+        }
+        self.save(&hosts);
+        true
+    }
+
+    pub fn set_policy(&self, host: &str, policy: TrustPolicy) -> bool {
+        let mut hosts = self.hosts.lock().expect("Poisoned known hosts");
+        let Some(known) = hosts.get_mut(host) else {
+            return false;
+        };
+        known.policy = policy;
+        self.save(&hosts);
+        true
+    }
+
+    fn save(&self, hosts: &HashMap<String, KnownHost>) {
+        let result = toml::to_string(hosts)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(fs::write(&self.path, contents)?));
+        if let Err(err) = result {
+            tracing::error!("Error saving known hosts to {:?}: {err}", self.path);
+        }
+    }
+}
+
+fn now() -> Option<u64> {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_secs())
+}
+
+pub fn fingerprint(certificate: &[u8]) -> String {
+    Sha256::digest(certificate)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn known_hosts(name: &str) -> KnownHosts {
+        let path =
+            std::env::temp_dir().join(format!("taurus-test-{}-{name}.toml", std::process::id()));
+        let _ = fs::remove_file(&path);
+        KnownHosts::load(path).unwrap()
+    }
+
+    /// Verifies the certificate in a handshake that succeeds
+    fn seen(known_hosts: &KnownHosts, host: &str, certificate: &[u8]) -> Verification {
+        let verification = known_hosts.verify(host, certificate);
+        known_hosts.handshake_completed(host, certificate);
+        verification
+    }
+
+    #[test]
+    fn trust_on_first_use() {
+        let known_hosts = known_hosts("tofu");
+        // The handshake failed
+        known_hosts.verify("a.com", b"forged");
+        assert!(known_hosts.get("a.com").is_none());
+        assert!(matches!(
+            seen(&known_hosts, "a.com", b"one"),
+            Verification::FirstUse
+        ));
+        let accepted = known_hosts.get("a.com").unwrap().accepted_at().unwrap();
+        assert!(accepted.elapsed().unwrap() < Duration::from_secs(60));
+        assert!(matches!(
+            seen(&known_hosts, "a.com", b"one"),
+            Verification::Known
+        ));
+        assert!(matches!(
+            seen(&known_hosts, "a.com", b"two"),
+            Verification::Mismatch
+        ));
+        let mismatch = known_hosts.take_mismatch("a.com").unwrap();
+        assert_eq!(TrustPolicy::Tofu, mismatch.policy);
+        known_hosts.trust("a.com", mismatch.fingerprint);
+        assert!(matches!(
+            seen(&known_hosts, "a.com", b"two"),
+            Verification::Known
+        ));
+
+        let reloaded = KnownHosts::load(known_hosts.path.clone()).unwrap();
+        assert!(matches!(
+            seen(&reloaded, "a.com", b"two"),
+            Verification::Known
+        ));
+    }
+
+    #[test]
+    fn policies() {
+        let known_hosts = known_hosts("policies");
+        seen(&known_hosts, "always.com", b"one");
+        seen(&known_hosts, "pinned.com", b"one");
+        known_hosts.set_policy("always.com", TrustPolicy::AlwaysTrust);
+        known_hosts.set_policy("pinned.com", TrustPolicy::Pinned);
+        // The handshake failed
+        assert!(matches!(
+            known_hosts.verify("always.com", b"forged"),
+            Verification::Known
+        ));
+        assert_eq!(
+            fingerprint(b"one"),
+            known_hosts.get("always.com").unwrap().fingerprint
+        );
+        assert!(matches!(
+            seen(&known_hosts, "always.com", b"two"),
+            Verification::Known
+        ));
+        assert_eq!(
+            fingerprint(b"two"),
+            known_hosts.get("always.com").unwrap().fingerprint
+        );
+        assert!(matches!(
+            seen(&known_hosts, "pinned.com", b"two"),
+            Verification::Mismatch
+        ));
+        assert_eq!(
+            TrustPolicy::Pinned,
+            known_hosts.take_mismatch("pinned.com").unwrap().policy
+        );
+    }
+
+    #[test]
+    fn forgotten_host_trusted_again() {
+        let known_hosts = known_hosts("forget");
+        seen(&known_hosts, "b.com", b"one");
+        seen(&known_hosts, "a.com", b"one");
+        let hosts: Vec<_> = known_hosts
+            .hosts()
+            .into_iter()
+            .map(|(host, _)| host)
+            .collect();
+        assert_eq!(vec!["a.com", "b.com"], hosts);
+        assert!(known_hosts.forget("a.com"));
+        assert!(!known_hosts.forget("a.com"));
+        assert!(matches!(
+            seen(&known_hosts, "a.com", b"two"),
+            Verification::FirstUse
+        ));
+    }
+}
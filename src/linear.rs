@@ -0,0 +1,208 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::Result;
+use url::Url;
+
+use crate::{
+    app::url_bar,
+    client::{Client, GeminiResponse, InputStatus, ResponseBody},
+    gemtext::{GemTextLine, GemTextParser},
+};
+
+const HELP: &str = "Type a link number to follow it, a URL or words to search, \
+b to go back, r to reload, h for this help and q to quit.";
+
+/// Browses with plain lines on stdout and a prompt, instead of the full screen interface, which
+/// terminal screen readers follow far better.
+pub fn run(client: &Client, start: Url, search_url: &Url) -> Result<()> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut history = vec![start];
+    let mut links = Vec::new();
+    let mut reload = true;
+    println!("{HELP}");
+    loop {
+        let current = history.last().expect("History is never empty").clone();
+        if reload {
+            reload = false;
+            match show(client, &current, &mut lines)? {
+                Shown::Page(page_links) => links = page_links,
+                Shown::Url(url) => {
+                    history.push(url);
+                    reload = true;
+                    continue;
+                }
+                Shown::Nothing => {}
+            }
+        }
+        print!("> ");
+        io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(());
+        };
+        match line.trim() {
+            "" => {}
+            "q" => return Ok(()),
+            "h" | "?" => println!("{HELP}"),
+            "r" => reload = true,
+            "b" if history.len() > 1 => {
+                history.pop();
+                reload = true;
+            }
+            "b" => println!("Nothing to go back to"),
+            text => {
+                let url = match text.parse::<usize>() {
+                    Ok(n) => match links.get(n) {
+                        Some(url) => Ok(url.clone()),
+                        None => {
+                            println!("There is no link {n}");
+                            continue;
+                        }
+                    },
+                    Err(_) => url_bar::resolve(&current, text, search_url),
+                };
+                match url {
+                    Ok(url) => {
+                        history.push(url);
+                        reload = true;
+                    }
+                    Err(err) => println!("Invalid URL: {err}"),
+                }
+            }
+        }
+    }
+}
+
+enum Shown {
+    /// A page was printed, with these links
+    Page(Vec<Url>),
+    /// The server asked to go somewhere else, with the input that was asked for
+    Url(Url),
+    Nothing,
+}
+
+fn show(
+    client: &Client,
+    url: &Url,
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+) -> Result<Shown> {
+    println!("Loading {url}");
+    let response = match client.request(url.clone(), &mut |_| Ok(())) {
+        Ok(response) => response,
+        Err(err) => {
+            println!("Error: {err}");
+            return Ok(Shown::Nothing);
+        }
+    };
+    Ok(match response {
+        GeminiResponse::Success { mime, body, .. } => {
+            let bytes = match body {
+                ResponseBody::Bytes(bytes) => bytes,
+                ResponseBody::File { file, .. } => std::fs::read(file.path())?,
+            };
+            if mime.starts_with("text/gemini") {
+                let (text, links) = render(&String::from_utf8_lossy(&bytes), url.clone());
+                print!("{text}");
+                println!("End of page, {} links", links.len());
+                Shown::Page(links)
+            } else if mime.starts_with("text/") {
+                println!("{}", String::from_utf8_lossy(&bytes));
+                println!("End of page");
+                Shown::Page(Vec::new())
+            } else {
+                println!("This page is {mime}, which can not be shown as text");
+                Shown::Nothing
+            }
+        }
+        GeminiResponse::Input { status, prompt, .. } => {
+            if let InputStatus::Sensitive = status {
+                println!("Sensitive input, what you type is shown");
+            }
+            print!("{prompt}: ");
+            io::stdout().flush()?;
+            let Some(input) = lines.next().transpose()? else {
+                return Ok(Shown::Nothing);
+            };
+            let mut url = url.clone();
+            url.set_query(Some(&input));
+            Shown::Url(url)
+        }
+        GeminiResponse::Redirect { url, .. } => {
+            println!("Redirected to {url}");
+            Shown::Url(url)
+        }
+        response => {
+            println!("The page could not be loaded: {response:?}");
+            Shown::Nothing
+        }
+    })
+}
+
+/// Gemtext as lines to read one after the other, with headings announced and links numbered.
+fn render(gemtext: &str, url: Url) -> (String, Vec<Url>) {
+    let mut text = String::new();
+    let mut links = Vec::new();
+    // The parser does not tell the lines opening and closing preformatted blocks apart from
+    // the lines in them, so they are found in the raw text, which it splits the same way
+    let raw_lines = gemtext.split('\n');
+    let mut pre_formatted = false;
+    for (line, raw) in GemTextParser::new(gemtext, url).zip(raw_lines) {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                text.push_str(&format!("Invalid link: {err}\n"));
+                continue;
+            }
+        };
+        match line {
+            GemTextLine::Text(line) => text.push_str(&format!("{line}\n")),
+            GemTextLine::Heading { level, text: line } => {
+                text.push_str(&format!("Heading {level}: {line}\n"))
+            }
+            GemTextLine::ListItem(line) => text.push_str(&format!("Item: {line}\n")),
+            GemTextLine::Quote { level, text: line } => {
+                text.push_str(&format!("Quote {level}: {line}\n"))
+            }
+            GemTextLine::Link { url, text: line } => {
+                let line = if line.trim().is_empty() {
+                    url.as_str()
+                } else {
+                    line.trim()
+                };
+                text.push_str(&format!("Link {}: {line}\n", links.len()));
+                links.push(url);
+            }
+            GemTextLine::PreFormatted(alt) if raw.starts_with("```") => {
+                pre_formatted = !pre_formatted;
+                if pre_formatted {
+                    text.push_str(&format!("Preformatted text {alt}\n"));
+                } else {
+                    text.push_str("End of preformatted text\n");
+                }
+            }
+            GemTextLine::PreFormatted(line) => text.push_str(&format!("{line}\n")),
+        }
+    }
+    (text, links)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rendered_for_reading() {
+        let page =
+            "# Title\nSome text\n=> /a First\n```ascii art\n=> not a link\n```\n=> gemini://b.com/";
+        let (text, links) = render(page, Url::parse("gemini://a.com/").unwrap());
+        assert_eq!(
+            "Heading 1: Title\nSome text\nLink 0: First\nPreformatted text ascii art\n\
+             => not a link\nEnd of preformatted text\nLink 1: gemini://b.com/\n",
+            text
+        );
+        assert_eq!(
+            vec!["gemini://a.com/a", "gemini://b.com/"],
+            links.iter().map(Url::as_str).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use url::Url;
+
+static URLS: OnceLock<UrlLogging> = OnceLock::new();
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    /// How much of the visited URLs is written to taurus.log
+    pub log_urls: UrlLogging,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlLogging {
+    #[default]
+    Full,
+    /// Only the host, without the path and the query which may hold what was typed
+    Host,
+    None,
+}
+
+/// Sets how URLs are logged, before anything is.
+pub fn init(urls: UrlLogging) {
+    let _ = URLS.set(urls);
+}
+
+fn urls() -> UrlLogging {
+    URLS.get().copied().unwrap_or_default()
+}
+
+/// `url` as it may be written to the log
+pub fn url(url: &Url) -> String {
+    redact_url(url, urls())
+}
+
+/// `host` as it may be written to the log
+pub fn host(host: &str) -> String {
+    redact_host(host, urls())
+}
+
+/// Whether URLs are logged in full, so messages that may contain one can be
+pub fn full() -> bool {
+    urls() == UrlLogging::Full
+}
+
+fn redact_url(url: &Url, urls: UrlLogging) -> String {
+    match urls {
+        UrlLogging::Full => url.to_string(),
+        UrlLogging::Host => match url.host_str() {
+            Some(host) => format!("{}://{host}/…", url.scheme()),
+            None => format!("{}:…", url.scheme()),
+        },
+        UrlLogging::None => "<url>".to_string(),
+    }
+}
+
+fn redact_host(host: &str, urls: UrlLogging) -> String {
+    match urls {
+        UrlLogging::Full | UrlLogging::Host => host.to_string(),
+        UrlLogging::None => "<host>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redacted_urls() {
+        let url = Url::parse("gemini://a.com/login?password").unwrap();
+        assert_eq!(
+            "gemini://a.com/login?password",
+            redact_url(&url, UrlLogging::Full)
+        );
+        assert_eq!("gemini://a.com/…", redact_url(&url, UrlLogging::Host));
+        assert_eq!("<url>", redact_url(&url, UrlLogging::None));
+        assert_eq!("a.com", redact_host("a.com", UrlLogging::Host));
+        assert_eq!("<host>", redact_host("a.com", UrlLogging::None));
+    }
+}
@@ -1,21 +1,205 @@
-use std::fs::{read_to_string, File};
+use std::{
+    collections::HashMap,
+    fs::{read_to_string, File},
+    io::{stdin, stdout, ErrorKind, IsTerminal},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use anyhow::Result;
-use app::App;
+use anyhow::{bail, Result};
+use app::{
+    diff::DiffConfig, downloads::MimeHandler, history::HistoryConfig, identities::Identities,
+    page_cache::CacheConfig, speech::SpeechConfig, tabs::TabsConfig, theme::ThemeConfig,
+    translate::TranslationConfig, App, UiConfig,
+};
+use clap::{Parser, Subcommand};
+use client::{Certificates, Client, ClientOptions, Identity};
+use known_hosts::KnownHosts;
+use logging::PrivacyConfig;
+use secrets::Passphrase;
 use serde::Deserialize;
 use tracing::Level;
+use url::Url;
 
 mod app;
+mod check;
 mod client;
+mod diagnostics;
+mod doctor;
+mod fetch;
 mod gemtext;
+mod known_hosts;
+mod linear;
+mod logging;
+mod mirror;
+mod schemes;
+mod secrets;
+mod setup;
+mod watch;
+
+#[derive(Parser)]
+#[command(version, about = "A terminal Gemini browser")]
+struct Cli {
+    /// Print pages as plain lines with a prompt instead of using the full screen, which works
+    /// better with screen readers
+    #[arg(long)]
+    linear: bool,
+    /// Check the terminal, the configuration, the files and the network, and print a report to
+    /// join to bug reports
+    #[arg(long, conflicts_with = "linear")]
+    doctor: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Crawl a capsule reporting broken links, slow responses and other problems
+    Check(check::CheckArgs),
+    /// Download pages to a directory, each with a `.meta` file holding the response status, or
+    /// print them as JSON
+    Fetch(fetch::FetchArgs),
+    /// Download a capsule to a directory, to browse it offline
+    Mirror(mirror::MirrorArgs),
+    /// Request a page at an interval, failing or running a hook when it keeps failing
+    Watch(watch::WatchArgs),
+}
+
+/// Where the configuration is read from, in the working directory
+const CONFIG_FILE: &str = "Config.toml";
 
 #[derive(Deserialize)]
+#[serde(default)]
 struct Config {
-    cert_file: String,
-    key_file: String,
+    cert_file: Option<String>,
+    key_file: Option<String>,
+    search_url: Url,
+    known_hosts_file: String,
+    /// Where the identities used for some pages are kept, empty to keep none
+    identities_dir: String,
+    /// Where bookmarks are kept, empty to keep none
+    bookmarks_file: String,
+    /// Directory shared between machines where the bookmarks and the reading positions are kept
+    /// in place of their files, empty to share nothing
+    sync_dir: String,
+    downloads_dir: Option<String>,
+    /// Where answers to prompts are remembered, empty to remember none
+    remembered_inputs_file: String,
+    /// Whether the private key and the remembered answers are encrypted with a passphrase
+    encrypt_secrets: bool,
+    theme: ThemeConfig,
+    ui: UiConfig,
+    tabs: TabsConfig,
+    translation: TranslationConfig,
+    speech: SpeechConfig,
+    cache: CacheConfig,
+    history: HistoryConfig,
+    diff: DiffConfig,
+    privacy: PrivacyConfig,
+    client: ClientOptions,
+    handlers: HashMap<String, MimeHandler>,
+    /// Commands opening the links of schemes taurus does not load, by scheme
+    external_schemes: HashMap<String, String>,
+    /// The settings that were left out, as they were invalid or unknown
+    #[serde(skip)]
+    problems: Vec<String>,
+    /// Asked when taurus starts, with `encrypt_secrets`
+    #[serde(skip)]
+    passphrase: Option<Passphrase>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            cert_file: None,
+            key_file: None,
+            search_url: Url::parse("gemini://tlgs.one/search")
+                .expect("We know that this is a valid url"),
+            known_hosts_file: "known_hosts.toml".to_string(),
+            identities_dir: "identities".to_string(),
+            bookmarks_file: "bookmarks.toml".to_string(),
+            sync_dir: String::new(),
+            downloads_dir: None,
+            remembered_inputs_file: String::new(),
+            encrypt_secrets: false,
+            theme: ThemeConfig::default(),
+            ui: UiConfig::default(),
+            tabs: TabsConfig::default(),
+            translation: TranslationConfig::default(),
+            speech: SpeechConfig::default(),
+            cache: CacheConfig::default(),
+            history: HistoryConfig::default(),
+            diff: DiffConfig::default(),
+            privacy: PrivacyConfig::default(),
+            client: ClientOptions::default(),
+            handlers: HashMap::new(),
+            external_schemes: HashMap::new(),
+            problems: Vec::new(),
+            passphrase: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration file, with the defaults when there is none. The settings that are
+    /// wrong are left out and listed in `problems`, only a file that cannot be parsed fails.
+    fn load() -> Result<Self> {
+        let contents = match read_to_string(CONFIG_FILE) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let (mut config, problems): (Self, _) = diagnostics::parse(&contents)?;
+        config.problems = problems;
+        Ok(config)
+    }
+
+    fn certificates(&self) -> Option<Certificates> {
+        match (&self.cert_file, &self.key_file) {
+            (Some(cert_file), Some(key_file)) => Some(Certificates {
+                cert_file: cert_file.clone(),
+                key_file: key_file.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The client certificate, which fails when its files are missing or do not go together
+    fn identity(&self) -> Result<Option<Identity>> {
+        match (&self.cert_file, &self.key_file) {
+            (Some(_), None) => bail!("cert_file is set without key_file"),
+            (None, Some(_)) => bail!("key_file is set without cert_file"),
+            _ => self
+                .certificates()
+                .map(|files| Identity::load(&files, self.passphrase.as_ref()))
+                .transpose(),
+        }
+    }
+
+    /// The files encrypted with the passphrase, once they are
+    fn secret_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.key_file.iter().map(PathBuf::from).collect();
+        if !self.remembered_inputs_file.is_empty() {
+            files.push(secrets::key_path(Path::new(&self.remembered_inputs_file)));
+        }
+        // A list that cannot be read is reported when the identities are loaded
+        if let Ok(identities) = Identities::load(&self.identities_dir) {
+            files.extend(identities.key_files());
+        }
+        files
+    }
+}
+
+/// The client certificate for the commands, which go on without one when it cannot be loaded
+fn command_identity(config: &Config) -> Option<Identity> {
+    config.identity().unwrap_or_else(|err| {
+        eprintln!("{CONFIG_FILE}: {err}, no client certificate is used");
+        None
+    })
 }
 
 fn main() -> Result<()> {
+    let cli = Cli::parse();
     let writer = File::create("taurus.log")?;
     tracing_subscriber::fmt()
         .with_writer(writer)
@@ -24,16 +208,70 @@ fn main() -> Result<()> {
         .with_max_level(Level::DEBUG)
         .init();
 
-    let config_contents = read_to_string("Config.toml");
-    let config: Option<Config> = if let Ok(contents) = config_contents {
-        Some(toml::from_str(&contents)?)
-    } else {
-        None
-    };
+    let config_path = Path::new(CONFIG_FILE);
+    let interactive = stdin().is_terminal() && stdout().is_terminal();
+    if cli.command.is_none() && !cli.doctor && interactive && !config_path.exists() {
+        setup::run(config_path)?;
+    }
+    let mut config = Config::load().unwrap_or_else(|err| Config {
+        problems: vec![format!("{err}, all the settings are left out")],
+        ..Config::default()
+    });
+    logging::init(config.privacy.log_urls);
     tracing::info!("Started taurus");
-    let app = App::new(config);
+    if config.encrypt_secrets {
+        config.passphrase = Some(secrets::ask_passphrase(&config.secret_files())?);
+    }
+    if cli.doctor {
+        return doctor::run(config);
+    }
+    if cli.command.is_some() || cli.linear {
+        for problem in &config.problems {
+            eprintln!("{CONFIG_FILE}: {problem}");
+        }
+    }
+    if let Some(command) = cli.command {
+        let known_hosts = Arc::new(KnownHosts::load(config.known_hosts_file.clone().into())?);
+        let identity = command_identity(&config);
+        return match command {
+            Command::Check(args) => {
+                let client = Client::new(false, identity, config.client, known_hosts);
+                check::run(&client, args)
+            }
+            Command::Fetch(args) => {
+                let client = Client::new(true, identity, config.client, known_hosts);
+                fetch::run(&client, args)
+            }
+            Command::Mirror(args) => {
+                let client = Client::new(true, identity, config.client, known_hosts);
+                mirror::run(&client, args)
+            }
+            Command::Watch(args) => {
+                let client = Client::new(false, identity, config.client, known_hosts);
+                watch::run(&client, args)
+            }
+        };
+    }
+    if cli.linear {
+        let known_hosts = Arc::new(KnownHosts::load(config.known_hosts_file.clone().into())?);
+        let identity = command_identity(&config);
+        let client = Client::new(true, identity, config.client, known_hosts);
+        return linear::run(&client, config.tabs.home_url.clone(), &config.search_url);
+    }
+    let app = App::new(config)?;
     let mut terminal = ratatui::init();
     let result = app.run(&mut terminal);
     ratatui::restore();
     result
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn example_config_parses() {
+        let config: Config = toml::from_str(include_str!("../Config.toml")).unwrap();
+        assert_eq!(Some("cert.pem"), config.cert_file.as_deref());
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use clap::Args;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::{
+    app::format_size,
+    client::{Client, GeminiResponse, ResponseBody},
+    gemtext::resolve_link,
+};
+
+#[derive(Args, Debug)]
+pub struct MirrorArgs {
+    /// Capsule to download, like gemini://myhost/
+    pub url: Url,
+    /// Directory to save the capsule to
+    pub dir: PathBuf,
+    /// How many links away from the first page to follow
+    #[arg(long, default_value_t = 5)]
+    pub depth: usize,
+    /// Stop once this many bytes were downloaded
+    #[arg(long, default_value_t = 100 * 1024 * 1024)]
+    pub max_bytes: u64,
+    /// Time to wait between requests, in milliseconds
+    #[arg(long, default_value_t = 500)]
+    pub delay_ms: u64,
+    /// Times to request again the pages whose body may be cut short, as the server closed the
+    /// connection without TLS close_notify
+    #[arg(long, default_value_t = 2)]
+    pub retries: usize,
+}
+
+/// Downloads the pages of a capsule that are on the same host, rewriting their links so the copy
+/// can be browsed offline through `file://` URLs.
+pub fn run(client: &Client, args: MirrorArgs) -> Result<()> {
+    let Some(host) = args.url.host_str().map(str::to_string) else {
+        bail!("{} has no host", args.url);
+    };
+    let delay = Duration::from_millis(args.delay_ms);
+    let mut downloaded = 0;
+    let mut saved = 0;
+    let mut truncated = 0;
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([(args.url.clone(), 0)]);
+    while let Some((mut url, depth)) = queue.pop_front() {
+        url.set_fragment(None);
+        if !visited.insert(url.clone()) {
+            continue;
+        }
+        if downloaded >= args.max_bytes {
+            println!("Stopping, {} were downloaded", format_size(downloaded));
+            break;
+        }
+        if visited.len() > 1 {
+            thread::sleep(delay);
+        }
+        let path = args.dir.join(local_path(&url));
+        let response = match client.request_whole(url.clone(), args.retries) {
+            Ok((response, possibly_truncated)) => {
+                if possibly_truncated {
+                    eprintln!(
+                        "{url}: may be cut short, the server closed the connection without TLS \
+                         close_notify"
+                    );
+                    truncated += 1;
+                }
+                response
+            }
+            Err(err) => {
+                eprintln!("{url}: {err}");
+                continue;
+            }
+        };
+        let GeminiResponse::Success { mime, body, .. } = response else {
+            eprintln!("{url}: not saved, the response was {response:?}");
+            continue;
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let size = if mime.starts_with("text/gemini") {
+            let text = match body {
+                ResponseBody::Bytes(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                ResponseBody::File { file, .. } => fs::read_to_string(file.path())?,
+            };
+            let (text, links) = rewrite_links(&text, &url, &host);
+            if depth < args.depth {
+                queue.extend(links.into_iter().map(|link| (link, depth + 1)));
+            }
+            fs::write(&path, &text)?;
+            text.len() as u64
+        } else {
+            match body {
+                ResponseBody::Bytes(bytes) => {
+                    fs::write(&path, &bytes)?;
+                    bytes.len() as u64
+                }
+                ResponseBody::File { file, .. } => fs::copy(file.path(), &path)?,
+            }
+        };
+        downloaded += size;
+        saved += 1;
+        println!("{url} -> {}", path.display());
+    }
+
+    let index = fs::canonicalize(args.dir.join(local_path(&args.url)))?;
+    println!(
+        "Saved {} pages, {}. Browse them at file://{}",
+        saved,
+        format_size(downloaded),
+        index.display()
+    );
+    if truncated > 0 {
+        println!("{truncated} of them may be cut short, mirror again to retry them");
+    }
+    Ok(())
+}
+
+/// Whether `url` is part of the capsule being mirrored. Queries are left out, as they are
+/// usually answers to input prompts.
+fn is_mirrored(url: &Url, host: &str) -> bool {
+    url.scheme() == "gemini" && url.host_str() == Some(host) && url.query().is_none()
+}
+
+/// Where a URL is saved, relative to the mirror directory, as percent encoded segments.
+/// Directories get an `index.gmi` and names without an extension are assumed to be gemtext.
+fn local_segments(url: &Url) -> Vec<String> {
+    let mut segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| segments.map(str::to_string).collect())
+        .unwrap_or_default();
+    match segments.last_mut() {
+        None => segments.push("index.gmi".to_string()),
+        Some(last) if last.is_empty() => *last = "index.gmi".to_string(),
+        Some(last) if !last.contains('.') => last.push_str(".gmi"),
+        Some(_) => {}
+    }
+    segments
+}
+
+pub(crate) fn local_path(url: &Url) -> PathBuf {
+    local_segments(url)
+        .iter()
+        .map(|segment| {
+            percent_decode_str(segment)
+                .decode_utf8_lossy()
+                .replace(['/', '\\'], "_")
+        })
+        .filter(|segment| !matches!(segment.as_str(), "" | "." | ".."))
+        .fold(PathBuf::new(), |path, segment| path.join(segment))
+}
+
+/// The link from the saved copy of `from` to the saved copy of `to`
+fn relative_link(from: &Url, to_url: &Url) -> String {
+    let from = local_segments(from);
+    let to = local_segments(to_url);
+    let from_dir = &from[..from.len() - 1];
+    let common = from_dir
+        .iter()
+        .zip(&to[..to.len() - 1])
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut link = "../".repeat(from_dir.len() - common);
+    link.push_str(&to[common..].join("/"));
+    if let Some(fragment) = to_url.fragment() {
+        link.push('#');
+        link.push_str(fragment);
+    }
+    link
+}
+
+/// Points the links to pages of the capsule to their saved copies, returning the new text and
+/// the pages it links to.
+fn rewrite_links(text: &str, url: &Url, host: &str) -> (String, Vec<Url>) {
+    let mut links = Vec::new();
+    let mut pre_formatted = false;
+    let mut rewritten = String::with_capacity(text.len());
+    for line in text.lines() {
+        if line.starts_with("```") {
+            pre_formatted = !pre_formatted;
+        }
+        let target = line
+            .strip_prefix("=>")
+            .filter(|_| !pre_formatted)
+            .and_then(|link_line| {
+                let link_line = link_line.trim();
+                let (link, text) = link_line
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((link_line, ""));
+                let target = resolve_link(url, link).ok()?;
+                is_mirrored(&target, host).then_some((target, text.trim()))
+            });
+        match target {
+            Some((target, text)) => {
+                rewritten.push_str(&format!("=> {} {text}", relative_link(url, &target)));
+                links.push(target);
+            }
+            None => rewritten.push_str(line),
+        }
+        rewritten.push('\n');
+    }
+    (rewritten, links)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn local_paths() {
+        assert_eq!(
+            PathBuf::from("index.gmi"),
+            local_path(&url("gemini://test.com/"))
+        );
+        assert_eq!(
+            PathBuf::from("docs/index.gmi"),
+            local_path(&url("gemini://test.com/docs/"))
+        );
+        assert_eq!(
+            PathBuf::from("about.gmi"),
+            local_path(&url("gemini://test.com/about"))
+        );
+        assert_eq!(
+            PathBuf::from("my notes.txt"),
+            local_path(&url("gemini://test.com/my%20notes.txt"))
+        );
+    }
+
+    #[test]
+    fn relative_links() {
+        let page = url("gemini://test.com/blog/post.gmi");
+        assert_eq!(
+            "../index.gmi",
+            relative_link(&page, &url("gemini://test.com/"))
+        );
+        assert_eq!(
+            "other.gmi#end",
+            relative_link(&page, &url("gemini://test.com/blog/other.gmi#end"))
+        );
+        assert_eq!(
+            "../docs/a%20b.txt",
+            relative_link(&page, &url("gemini://test.com/docs/a%20b.txt"))
+        );
+    }
+
+    #[test]
+    fn only_capsule_links_are_rewritten() {
+        let text = "# Home\n=> /blog/ Blog\n=> gemini://other.com/ Other\n=> search?q Search\n```\n=> /x\n```\n";
+        let (rewritten, links) = rewrite_links(text, &url("gemini://test.com/"), "test.com");
+        assert_eq!(
+            "# Home\n=> blog/index.gmi Blog\n=> gemini://other.com/ Other\n=> search?q Search\n```\n=> /x\n```\n",
+            rewritten
+        );
+        assert_eq!(vec![url("gemini://test.com/blog/")], links);
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Result};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use super::SchemeHandler;
+use crate::client::{BodyProgress, GeminiResponse, ResponseBody};
+
+/// `data:text/gemini;base64,...` URLs carry the page in themselves, RFC 2397
+pub struct DataHandler;
+
+impl SchemeHandler for DataHandler {
+    fn fetch(
+        &self,
+        url: Url,
+        _on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse> {
+        let (mime, bytes) = decode(&url)?;
+        Ok(GeminiResponse::Success {
+            meta: mime.clone(),
+            mime,
+            body: ResponseBody::Bytes(bytes),
+        })
+    }
+}
+
+fn decode(url: &Url) -> Result<(String, Vec<u8>)> {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    let data = url.as_str().strip_prefix("data:").unwrap_or_default();
+    let (meta, data) = data
+        .split_once(',')
+        .ok_or(anyhow!("Missing comma in data URL"))?;
+    let data: Vec<u8> = percent_decode_str(data).collect();
+    let (mime, bytes) = match meta.strip_suffix(";base64") {
+        Some(mime) => (mime, BASE64_STANDARD.decode(data)?),
+        None => (meta, data),
+    };
+    let mime = if mime.is_empty() {
+        "text/plain;charset=US-ASCII".to_string()
+    } else {
+        percent_decode_str(mime).decode_utf8_lossy().into_owned()
+    };
+    Ok((mime, bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decoded(url: &str) -> (String, String) {
+        let (mime, bytes) = decode(&Url::parse(url).unwrap()).unwrap();
+        (mime, String::from_utf8(bytes).unwrap())
+    }
+
+    #[test]
+    fn plain_and_base64() {
+        assert_eq!(
+            ("text/plain".to_string(), "hello world".to_string()),
+            decoded("data:text/plain,hello%20world")
+        );
+        assert_eq!(
+            ("text/gemini".to_string(), "# Hi\n".to_string()),
+            decoded("data:text/gemini;base64,IyBIaQo=")
+        );
+        assert_eq!("text/plain;charset=US-ASCII", decoded("data:,a#fragment").0);
+        assert_eq!("a", decoded("data:,a#fragment").1);
+        assert!(decode(&Url::parse("data:text/plain").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Result};
+use url::Url;
+
+use super::SchemeHandler;
+use crate::client::{BodyProgress, GeminiResponse, ResponseBody};
+
+/// Local files, like a capsule saved with `taurus mirror`
+pub struct FileHandler;
+
+impl SchemeHandler for FileHandler {
+    fn fetch(
+        &self,
+        url: Url,
+        _on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse> {
+        let Ok(mut path) = url.to_file_path() else {
+            bail!("Invalid file URL {url}");
+        };
+        if path.is_dir() {
+            path.push("index.gmi");
+        }
+        Ok(GeminiResponse::Success {
+            mime: mime_type(&path).to_string(),
+            meta: mime_type(&path).to_string(),
+            body: ResponseBody::Bytes(fs::read(&path)?),
+        })
+    }
+}
+
+/// Guessed from the extension, files having no mime type
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("gmi" | "gemini") => "text/gemini",
+        Some("txt") => "text/plain",
+        Some("md") => "text/markdown",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn directory_index() {
+        let dir = std::env::temp_dir().join(format!("taurus-test-{}-file", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("index.gmi"), "# Index").unwrap();
+        let url = Url::from_directory_path(&dir).unwrap();
+        let response = FileHandler.fetch(url, &mut |_| Ok(()));
+        fs::remove_dir_all(&dir).unwrap();
+        let Ok(GeminiResponse::Success { mime, body, .. }) = response else {
+            panic!("Unexpected response {response:?}");
+        };
+        assert_eq!("text/gemini", mime);
+        assert!(matches!(body, ResponseBody::Bytes(bytes) if bytes == b"# Index"));
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use url::Url;
+
+use super::SchemeHandler;
+use crate::client::{BodyProgress, GeminiResponse, ResponseBody};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Finger answers are short texts, anything longer is cut
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+/// `finger://host/user` asks the finger server of host about user, RFC 1288
+pub struct FingerHandler;
+
+impl SchemeHandler for FingerHandler {
+    fn fetch(
+        &self,
+        url: Url,
+        _on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse> {
+        let host = url.host_str().ok_or(anyhow!("Missing host"))?;
+        let mut stream = TcpStream::connect((host, url.port().unwrap_or(79)))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let user = url.path().trim_start_matches('/');
+        stream.write_all(format!("{user}\r\n").as_bytes())?;
+        let mut bytes = Vec::new();
+        stream.take(MAX_RESPONSE_BYTES).read_to_end(&mut bytes)?;
+        Ok(GeminiResponse::Success {
+            mime: "text/plain".to_string(),
+            meta: "text/plain".to_string(),
+            body: ResponseBody::Bytes(bytes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::BufRead, io::BufReader, net::TcpListener, thread};
+
+    use super::*;
+
+    #[test]
+    fn asks_for_the_user() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request).unwrap();
+            stream.write_all(b"Plan: none\r\n").unwrap();
+            request
+        });
+        let url = Url::parse(&format!("finger://127.0.0.1:{port}/alice")).unwrap();
+        let response = FingerHandler.fetch(url, &mut |_| Ok(())).unwrap();
+        assert_eq!("alice\r\n", server.join().unwrap());
+        let GeminiResponse::Success { mime, body, .. } = response else {
+            panic!("Unexpected response {response:?}");
+        };
+        assert_eq!("text/plain", mime);
+        assert!(matches!(body, ResponseBody::Bytes(bytes) if bytes == b"Plan: none\r\n"));
+    }
+}
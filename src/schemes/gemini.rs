@@ -0,0 +1,15 @@
+use anyhow::Result;
+use url::Url;
+
+use super::SchemeHandler;
+use crate::client::{BodyProgress, Client, GeminiResponse};
+
+impl SchemeHandler for Client {
+    fn fetch(
+        &self,
+        url: Url,
+        on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse> {
+        self.request(url, on_progress)
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use super::SchemeHandler;
+use crate::client::{BodyProgress, Client, GeminiResponse, InputStatus, ResponseBody};
+
+/// `misfin://user@host` links ask for a message, then send it like an input query. Messages are
+/// only sent once the user confirmed them, a link with a query only fills in the prompt, as it
+/// would send mail signed with the user's identity in one click otherwise.
+pub struct MisfinHandler {
+    client: Client,
+}
+
+impl MisfinHandler {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl SchemeHandler for MisfinHandler {
+    fn fetch(
+        &self,
+        url: Url,
+        _on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse> {
+        let address = format!("{}@{}", url.username(), url.host_str().unwrap_or_default());
+        let query = url
+            .query()
+            .filter(|_| self.client.take_misfin_confirmation(&url));
+        let Some(query) = query else {
+            let prompt = format!("Message to {address}");
+            return Ok(GeminiResponse::Input {
+                status: InputStatus::Normal,
+                meta: prompt.clone(),
+                prompt,
+            });
+        };
+        let message = percent_decode_str(query).decode_utf8_lossy();
+        let fingerprint = self.client.send_misfin(&url, &message)?;
+        let page = format!(
+            "# Message sent\n\nDelivered to {address}, whose certificate fingerprint is {fingerprint}.\n\n> {message}\n"
+        );
+        Ok(GeminiResponse::Success {
+            mime: "text/gemini".to_string(),
+            meta: "text/gemini".to_string(),
+            body: ResponseBody::Bytes(page.into_bytes()),
+        })
+    }
+}
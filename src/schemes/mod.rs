@@ -0,0 +1,57 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{bail, Result};
+use url::Url;
+
+use crate::client::{BodyProgress, Client, GeminiResponse};
+
+mod data;
+mod file;
+mod finger;
+mod gemini;
+mod misfin;
+
+/// Loads the URLs of a scheme. Every protocol answers with gemini responses, so pages are shown
+/// the same way whatever they were loaded with.
+pub trait SchemeHandler: Send + Sync {
+    /// `on_progress` is called as the body arrives, by the handlers that can report it.
+    fn fetch(
+        &self,
+        url: Url,
+        on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse>;
+}
+
+/// The handlers of the supported schemes. `about:` pages are not in it, as they show the state of
+/// the browser itself.
+#[derive(Clone, Default)]
+pub struct Schemes {
+    handlers: HashMap<String, Arc<dyn SchemeHandler>>,
+}
+
+impl Schemes {
+    pub fn new(client: Client) -> Self {
+        let mut schemes = Self::default();
+        schemes.register("misfin", misfin::MisfinHandler::new(client.clone()));
+        schemes.register("gemini", client);
+        schemes.register("data", data::DataHandler);
+        schemes.register("file", file::FileHandler);
+        schemes.register("finger", finger::FingerHandler);
+        schemes
+    }
+
+    pub fn register(&mut self, scheme: &str, handler: impl SchemeHandler + 'static) {
+        self.handlers.insert(scheme.to_string(), Arc::new(handler));
+    }
+
+    pub fn fetch(
+        &self,
+        url: Url,
+        on_progress: &mut dyn FnMut(BodyProgress) -> Result<()>,
+    ) -> Result<GeminiResponse> {
+        let Some(handler) = self.handlers.get(url.scheme()) else {
+            bail!("Unsupported scheme {}", url.scheme());
+        };
+        handler.fetch(url, on_progress)
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    fmt, fs,
+    io::{stderr, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use aws_lc_rs::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2, rand,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+/// Makes guessing passphrases slow, as recommended for PBKDF2 with SHA-256
+const PBKDF2_ITERATIONS: NonZeroU32 = NonZeroU32::new(600_000).expect("It is not zero");
+/// First line of the files encrypted with the passphrase, which tells them from the others
+const ENCRYPTED_HEADER: &str = "-----BEGIN TAURUS ENCRYPTED DATA-----";
+
+/// The passphrase protecting the secrets, asked once when taurus starts
+#[derive(Clone)]
+pub struct Passphrase(String);
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Encrypts the files holding secrets, so they cannot be read by just opening them
+pub struct SecretKey {
+    key: LessSafeKey,
+}
+
+impl SecretKey {
+    fn new(bytes: &[u8; KEY_LEN]) -> Self {
+        let key = UnboundKey::new(&CHACHA20_POLY1305, bytes).expect("The key has the right size");
+        Self {
+            key: LessSafeKey::new(key),
+        }
+    }
+
+    fn from_passphrase(passphrase: &Passphrase, salt: &[u8]) -> Self {
+        let mut bytes = [0; KEY_LEN];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            PBKDF2_ITERATIONS,
+            salt,
+            passphrase.0.as_bytes(),
+            &mut bytes,
+        );
+        Self::new(&bytes)
+    }
+
+    /// The key kept in `path`, made of random bytes written there the first time, and encrypted
+    /// with the passphrase when there is one. Without one it is kept in the clear, so what it
+    /// seals is only as private as the file holding it.
+    pub fn load_or_create(path: &Path, passphrase: Option<&Passphrase>) -> Result<Self> {
+        let mut bytes = [0; KEY_LEN];
+        if path.exists() {
+            let read = read_secret(path, passphrase)?;
+            if read.len() != KEY_LEN {
+                bail!("{} is not a key", path.display());
+            }
+            bytes.copy_from_slice(&read);
+        } else {
+            rand::fill(&mut bytes).map_err(|_| anyhow!("No random bytes for a key"))?;
+            write_secret(path, &bytes, passphrase)?;
+        }
+        Ok(Self::new(&bytes))
+    }
+
+    /// `plain` encrypted, after the nonce it was encrypted with
+    pub fn seal(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        rand::fill(&mut nonce).map_err(|_| anyhow!("No random bytes for a nonce"))?;
+        let mut sealed = plain.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Could not encrypt"))?;
+        sealed.splice(0..0, nonce);
+        Ok(sealed)
+    }
+
+    /// What `seal` encrypted, failing when it was changed or sealed with another key
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let Some((nonce, encrypted)) = sealed.split_at_checked(NONCE_LEN) else {
+            bail!("Too short to be encrypted");
+        };
+        let nonce = Nonce::try_assume_unique_for_key(nonce)?;
+        let mut plain = encrypted.to_vec();
+        let len = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut plain)
+            .map_err(|_| anyhow!("Could not decrypt, the key is not the one it was saved with"))?
+            .len();
+        plain.truncate(len);
+        Ok(plain)
+    }
+}
+
+/// `contents` encrypted with a key made from the passphrase, as text
+fn encrypt(passphrase: &Passphrase, contents: &[u8]) -> Result<String> {
+    let mut salt = [0; SALT_LEN];
+    rand::fill(&mut salt).map_err(|_| anyhow!("No random bytes for a salt"))?;
+    let mut encrypted = salt.to_vec();
+    encrypted.extend(SecretKey::from_passphrase(passphrase, &salt).seal(contents)?);
+    Ok(format!(
+        "{ENCRYPTED_HEADER}\n{}\n",
+        BASE64_STANDARD.encode(encrypted)
+    ))
+}
+
+/// What `encrypt` encrypted, failing with another passphrase
+fn decrypt(passphrase: &Passphrase, encrypted: &str) -> Result<Vec<u8>> {
+    let encrypted = BASE64_STANDARD.decode(encrypted.trim())?;
+    let Some((salt, sealed)) = encrypted.split_at_checked(SALT_LEN) else {
+        bail!("Too short to be encrypted");
+    };
+    SecretKey::from_passphrase(passphrase, salt)
+        .open(sealed)
+        .map_err(|_| anyhow!("The passphrase is not the one it was encrypted with"))
+}
+
+/// Reads a file holding a secret, decrypting it when it was encrypted with the passphrase. A
+/// file still in the clear is encrypted when there is a passphrase, so turning it on protects
+/// the files already there.
+pub fn read_secret(path: &Path, passphrase: Option<&Passphrase>) -> Result<Vec<u8>> {
+    let contents = fs::read(path)?;
+    let encrypted = std::str::from_utf8(&contents)
+        .ok()
+        .and_then(|contents| contents.strip_prefix(ENCRYPTED_HEADER));
+    match (encrypted, passphrase) {
+        (Some(encrypted), Some(passphrase)) => decrypt(passphrase, encrypted),
+        (Some(_), None) => {
+            bail!("Encrypted with a passphrase, set encrypt_secrets to be asked for it")
+        }
+        (None, Some(_)) => {
+            write_secret(path, &contents, passphrase)?;
+            Ok(contents)
+        }
+        (None, None) => Ok(contents),
+    }
+}
+
+/// Writes a file holding a secret, encrypted with the passphrase when there is one
+pub fn write_secret(path: &Path, contents: &[u8], passphrase: Option<&Passphrase>) -> Result<()> {
+    match passphrase {
+        Some(passphrase) => write_private(path, encrypt(passphrase, contents)?.as_bytes()),
+        None => write_private(path, contents),
+    }
+}
+
+/// Whether the file at `path` is encrypted with a passphrase
+fn is_encrypted(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|contents| contents.starts_with(ENCRYPTED_HEADER.as_bytes()))
+}
+
+/// `inputs.dat.key` for `inputs.dat`
+pub fn key_path(path: &Path) -> PathBuf {
+    let mut key = path.to_path_buf().into_os_string();
+    key.push(".key");
+    key.into()
+}
+
+/// Asks for the passphrase on the terminal without showing it. It is asked twice when none of
+/// `files` is encrypted yet, as a mistyped one would lock them all.
+pub fn ask_passphrase(files: &[PathBuf]) -> Result<Passphrase> {
+    let passphrase = read_hidden("Passphrase protecting the secrets: ")?;
+    if passphrase.is_empty() {
+        bail!("The passphrase cannot be empty");
+    }
+    if !files.iter().any(|file| is_encrypted(file))
+        && read_hidden("New passphrase, again: ")? != passphrase
+    {
+        bail!("The passphrases typed are not the same");
+    }
+    Ok(Passphrase(passphrase))
+}
+
+fn read_hidden(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    stderr().flush()?;
+    terminal::enable_raw_mode()?;
+    let read = read_keys();
+    terminal::disable_raw_mode()?;
+    eprintln!();
+    read
+}
+
+fn read_keys() -> Result<String> {
+    let mut typed = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(typed),
+            KeyCode::Esc => bail!("No passphrase typed"),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                bail!("No passphrase typed")
+            }
+            KeyCode::Backspace => {
+                typed.pop();
+            }
+            KeyCode::Char(c) => typed.push(c),
+            _ => {}
+        }
+    }
+}
+
+/// Writes a file only its owner can read. The contents go to a new file created that way next
+/// to it, which then replaces it, so others never get to open it with the secret in it and a
+/// crash while writing leaves the previous secret whole.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temporary = path.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    // Left by a write that did not finish
+    match fs::remove_file(&temporary) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temporary).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)
+    });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temporary);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sealed_with_the_key_kept() {
+        let path = std::env::temp_dir().join(format!("taurus-test-{}-key", std::process::id()));
+        let key = SecretKey::load_or_create(&path, None).unwrap();
+        let sealed = key.seal(b"token").unwrap();
+        assert!(!sealed.windows(5).any(|window| window == b"token"));
+        let loaded = SecretKey::load_or_create(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(b"token".to_vec(), loaded.open(&sealed).unwrap());
+        let other = SecretKey::new(&[1; KEY_LEN]);
+        assert!(other.open(&sealed).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn written_for_the_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("taurus-test-{}-private", std::process::id()));
+        fs::write(&path, "a longer secret").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, b"secret").unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        let contents = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(0o600, mode & 0o777);
+        assert_eq!(b"secret".to_vec(), contents);
+    }
+
+    #[test]
+    fn files_encrypted_with_the_passphrase() {
+        let path = std::env::temp_dir().join(format!("taurus-test-{}-secret", std::process::id()));
+        fs::write(&path, "private key").unwrap();
+        let passphrase = Passphrase("correct horse".to_string());
+        // Files in the clear are encrypted once there is a passphrase
+        assert_eq!(
+            b"private key".to_vec(),
+            read_secret(&path, Some(&passphrase)).unwrap()
+        );
+        assert!(is_encrypted(&path));
+        assert!(!fs::read_to_string(&path).unwrap().contains("private key"));
+        assert_eq!(
+            b"private key".to_vec(),
+            read_secret(&path, Some(&passphrase)).unwrap()
+        );
+        let wrong = Passphrase("battery staple".to_string());
+        let wrong = read_secret(&path, Some(&wrong)).err().unwrap().to_string();
+        let missing = read_secret(&path, None).err().unwrap().to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(wrong.ends_with("The passphrase is not the one it was encrypted with"));
+        assert!(missing.ends_with("set encrypt_secrets to be asked for it"));
+    }
+}
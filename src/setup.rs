@@ -0,0 +1,136 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use url::Url;
+
+use crate::client::{Certificates, Identity};
+
+const THEMES: [&str; 3] = ["default", "high-contrast", "monochrome"];
+
+/// What the setup asked for
+#[derive(Debug)]
+struct Answers {
+    home_url: Url,
+    theme: String,
+    downloads_dir: String,
+    /// Whether a client certificate is used, which the setup may have created
+    identity: bool,
+}
+
+/// Asks for the few settings worth choosing on the first start and writes them to `path`, so
+/// nobody has to write the configuration by hand before browsing.
+pub fn run(path: &Path) -> Result<()> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut ask = |question: &str, default: &str| -> Result<String> {
+        print!("{question} [{default}]: ");
+        io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            bail!("Setup interrupted");
+        };
+        Ok(match line.trim() {
+            "" => default.to_string(),
+            answer => answer.to_string(),
+        })
+    };
+
+    println!("No {} found, a few questions to write it.", path.display());
+    println!("Press Enter to keep the answer in brackets.\n");
+    let home_url = loop {
+        match Url::parse(&ask("Home page", "gemini://tlgs.one/")?) {
+            Ok(url) => break url,
+            Err(err) => println!("Invalid URL: {err}"),
+        }
+    };
+    let theme = loop {
+        let theme = ask(&format!("Theme, {}", THEMES.join(", ")), THEMES[0])?;
+        if THEMES.contains(&theme.as_str()) {
+            break theme;
+        }
+        println!("Unknown theme {theme}");
+    };
+    let downloads_dir = ask("Directory to save pages to", ".")?;
+    fs::create_dir_all(&downloads_dir)?;
+    let identity = ask(
+        "Create a client certificate, for capsules that ask who you are? y or n",
+        "n",
+    )?
+    .eq_ignore_ascii_case("y");
+    if identity {
+        let Certificates {
+            cert_file,
+            key_file,
+        } = &Certificates::default();
+        if Path::new(cert_file).exists() || Path::new(key_file).exists() {
+            println!("Using the existing {cert_file} and {key_file}");
+        } else {
+            let name = ask("Name capsules will see", "taurus")?;
+            Identity::create(&name, &Certificates::default(), None)?;
+            println!("Created {cert_file} and {key_file}");
+        }
+    }
+
+    let answers = Answers {
+        home_url,
+        theme,
+        downloads_dir,
+        identity,
+    };
+    fs::write(path, config_text(&answers))?;
+    println!(
+        "Wrote {}, see the Config.toml of taurus for the other settings.",
+        path.display()
+    );
+    Ok(())
+}
+
+fn config_text(answers: &Answers) -> String {
+    let string = |text: &str| toml::Value::String(text.to_string()).to_string();
+    let mut config = String::from(
+        "# Written on the first start, see the Config.toml of taurus for the other settings\n",
+    );
+    if answers.identity {
+        let certificates = Certificates::default();
+        config.push_str(&format!(
+            "cert_file = {}\n",
+            string(&certificates.cert_file)
+        ));
+        config.push_str(&format!("key_file = {}\n", string(&certificates.key_file)));
+    }
+    if answers.downloads_dir != "." {
+        config.push_str(&format!(
+            "downloads_dir = {}\n",
+            string(&answers.downloads_dir)
+        ));
+    }
+    config.push_str(&format!("\n[theme]\nname = {}\n", string(&answers.theme)));
+    config.push_str(&format!(
+        "\n[tabs]\nhome_url = {}\n",
+        string(answers.home_url.as_str())
+    ));
+    config
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn written_config_parses() {
+        let answers = Answers {
+            home_url: Url::parse("gemini://example.org/").unwrap(),
+            theme: "monochrome".to_string(),
+            downloads_dir: "my \"downloads\"".to_string(),
+            identity: true,
+        };
+        let config: Config = toml::from_str(&config_text(&answers)).unwrap();
+        assert_eq!(Some("cert.pem"), config.cert_file.as_deref());
+        assert_eq!(Some("my \"downloads\""), config.downloads_dir.as_deref());
+        assert_eq!("gemini://example.org/", config.tabs.home_url.as_str());
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use url::Url;
+
+use crate::{app::trail::format_time, client::Client, fetch::is_failure, logging};
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// Page to request, like gemini://myhost/
+    pub url: Url,
+    /// Time between two requests, like 30s, 5m or 1h
+    #[arg(long, default_value = "5m", value_parser = parse_interval)]
+    pub interval: Duration,
+    /// Failures in a row after which to stop, or to run the hook
+    #[arg(long, default_value_t = 3)]
+    pub failures: usize,
+    /// Command to run instead of stopping, like `notify-send`. It gets the URL and the last
+    /// failure as arguments, and runs again after as many failures.
+    #[arg(long)]
+    pub hook: Option<String>,
+}
+
+/// Requests a page forever, printing the status and latency of every response. Errors and
+/// statuses other than successes and redirects are failures.
+pub fn run(client: &Client, args: WatchArgs) -> Result<()> {
+    let mut failures = 0;
+    loop {
+        let started = Instant::now();
+        let response = client.request(args.url.clone(), &mut |_| Ok(()));
+        let latency = started.elapsed().as_millis();
+        let (outcome, failure) = match response {
+            Ok(response) => {
+                let outcome = format!("{} {}", response.status_code(), response.meta());
+                let failure = is_failure(response.status_code());
+                (outcome, failure)
+            }
+            Err(err) => (format!("error: {err}"), true),
+        };
+        println!(
+            "{} {outcome} in {latency}ms",
+            format_time(SystemTime::now())
+        );
+        if !failure {
+            failures = 0;
+        } else {
+            failures += 1;
+            tracing::warn!("{} failed: {outcome}", logging::url(&args.url));
+            if failures >= args.failures {
+                let Some(hook) = &args.hook else {
+                    bail!("{} failed {failures} times in a row", args.url);
+                };
+                run_hook(hook, &args.url, &outcome)?;
+                failures = 0;
+            }
+        }
+        thread::sleep(args.interval);
+    }
+}
+
+fn run_hook(command: &str, url: &Url, failure: &str) -> Result<()> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        bail!("Empty hook command");
+    };
+    let status = Command::new(program)
+        .args(parts)
+        .arg(url.as_str())
+        .arg(failure)
+        .stdin(Stdio::null())
+        .status()
+        .with_context(|| format!("Could not run {program}"))?;
+    if !status.success() {
+        eprintln!("{program} failed with {status}");
+    }
+    Ok(())
+}
+
+/// A number of seconds, minutes, hours or days, like `90s` or `5m`. Plain numbers are seconds.
+fn parse_interval(interval: &str) -> Result<Duration> {
+    let (number, unit) = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((interval, ""), |index| interval.split_at(index));
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("{interval} does not start with a number"))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => bail!("Unknown unit {unit}, use s, m, h or d"),
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intervals() {
+        assert_eq!(Duration::from_secs(300), parse_interval("5m").unwrap());
+        assert_eq!(Duration::from_secs(90), parse_interval("90").unwrap());
+        assert_eq!(Duration::from_secs(7200), parse_interval("2h").unwrap());
+        assert!(parse_interval("m").is_err());
+        assert!(parse_interval("5 minutes").is_err());
+    }
+}